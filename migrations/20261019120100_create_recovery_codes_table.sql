@@ -0,0 +1,8 @@
+-- Add migration script here
+CREATE TABLE recovery_codes (
+    user_id uuid NOT NULL
+        REFERENCES users (user_id),
+    code_hash TEXT NOT NULL,
+    used_at timestamptz NULL,
+    PRIMARY KEY (user_id, code_hash)
+);
@@ -0,0 +1,16 @@
+-- Add migration script here
+CREATE TABLE audit_events (
+    event_id uuid NOT NULL,
+    PRIMARY KEY (event_id),
+    occurred_at timestamptz NOT NULL,
+    actor_id uuid NULL
+        REFERENCES users (user_id),
+    action TEXT NOT NULL,
+    target TEXT NULL,
+    ip TEXT NULL,
+    user_agent TEXT NULL,
+    details JSONB NOT NULL DEFAULT '{}'
+);
+CREATE INDEX audit_events_occurred_at_idx ON audit_events (occurred_at DESC);
+CREATE INDEX audit_events_actor_id_idx ON audit_events (actor_id);
+CREATE INDEX audit_events_action_idx ON audit_events (action);
@@ -0,0 +1,4 @@
+-- Add migration script here
+ALTER TABLE subscriptions ADD COLUMN confirmed_at timestamptz NULL;
+CREATE INDEX subscriptions_subscribed_at_idx ON subscriptions (subscribed_at);
+CREATE INDEX subscriptions_confirmed_at_idx ON subscriptions (confirmed_at);
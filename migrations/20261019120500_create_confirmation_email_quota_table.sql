@@ -0,0 +1,5 @@
+-- Confirmation emails sent per day, to enforce the global daily cap.
+CREATE TABLE confirmation_email_quota (
+    day DATE NOT NULL PRIMARY KEY,
+    sent INTEGER NOT NULL
+);
@@ -0,0 +1,5 @@
+-- Foo@example.com and foo@example.com are the same subscriber.
+-- Fails if the table already holds addresses that only differ in case:
+-- those have to be merged by hand first.
+CREATE UNIQUE INDEX subscriptions_email_lower_key ON subscriptions (lower(email));
+ALTER TABLE subscriptions DROP CONSTRAINT subscriptions_email_key;
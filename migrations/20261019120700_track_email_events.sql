@@ -0,0 +1,12 @@
+-- Soft bounces since the last successful delivery.
+ALTER TABLE subscriptions ADD COLUMN soft_bounces INTEGER NOT NULL DEFAULT 0;
+
+-- Events reported by the email provider's webhooks, keyed by the
+-- provider's id so that redelivered webhooks are only applied once.
+CREATE TABLE email_events (
+    event_key TEXT NOT NULL PRIMARY KEY,
+    kind TEXT NOT NULL,
+    email TEXT NOT NULL,
+    payload JSONB NOT NULL,
+    received_at timestamptz NOT NULL
+);
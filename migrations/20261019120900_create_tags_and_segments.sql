@@ -0,0 +1,31 @@
+-- Where a subscription came from, e.g. the campaign that linked to the form.
+ALTER TABLE subscriptions ADD COLUMN source TEXT NULL;
+
+CREATE TABLE tags (
+    tag_id uuid NOT NULL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE
+);
+
+CREATE TABLE subscriber_tags (
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id) ON DELETE CASCADE,
+    tag_id uuid NOT NULL
+        REFERENCES tags (tag_id) ON DELETE CASCADE,
+    PRIMARY KEY (subscriber_id, tag_id)
+);
+CREATE INDEX subscriber_tags_tag_id_idx ON subscriber_tags (tag_id);
+
+-- Confirmed subscribers matching every condition that is set.
+CREATE TABLE segments (
+    segment_id uuid NOT NULL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    tag_id uuid NULL
+        REFERENCES tags (tag_id),
+    subscribed_from DATE NULL,
+    subscribed_until DATE NULL,
+    source TEXT NULL,
+    created_at timestamptz NOT NULL
+);
+
+ALTER TABLE newsletter_issues ADD COLUMN segment_id uuid NULL
+    REFERENCES segments (segment_id);
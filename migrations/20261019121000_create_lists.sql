@@ -0,0 +1,26 @@
+-- Each publication is a list, with its own subscriptions.
+CREATE TABLE lists (
+    list_id uuid NOT NULL PRIMARY KEY,
+    slug TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    created_at timestamptz NOT NULL
+);
+
+-- Everything so far went out as a single newsletter.
+INSERT INTO lists (list_id, slug, name, created_at)
+VALUES ('0f4f3c8e-6a3b-4d6e-9a51-3a1d2c7e5b10', 'newsletter', 'Newsletter', now());
+
+ALTER TABLE subscriptions ADD COLUMN list_id uuid NULL
+    REFERENCES lists (list_id);
+UPDATE subscriptions SET list_id = '0f4f3c8e-6a3b-4d6e-9a51-3a1d2c7e5b10';
+ALTER TABLE subscriptions ALTER COLUMN list_id SET NOT NULL;
+
+-- The same address can subscribe to several lists, once each.
+DROP INDEX subscriptions_email_lower_key;
+CREATE UNIQUE INDEX subscriptions_list_email_key ON subscriptions (list_id, lower(email));
+CREATE INDEX subscriptions_email_lower_idx ON subscriptions (lower(email));
+
+ALTER TABLE newsletter_issues ADD COLUMN list_id uuid NULL
+    REFERENCES lists (list_id);
+UPDATE newsletter_issues SET list_id = '0f4f3c8e-6a3b-4d6e-9a51-3a1d2c7e5b10';
+ALTER TABLE newsletter_issues ALTER COLUMN list_id SET NOT NULL;
@@ -0,0 +1,11 @@
+-- Paused subscriptions get no issues until then.
+ALTER TABLE subscriptions ADD COLUMN paused_until timestamptz NULL;
+
+-- A new address waiting to be confirmed from the preference center.
+CREATE TABLE email_change_requests (
+    token TEXT NOT NULL PRIMARY KEY,
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id) ON DELETE CASCADE,
+    new_email TEXT NOT NULL,
+    requested_at timestamptz NOT NULL
+);
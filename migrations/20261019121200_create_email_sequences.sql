@@ -0,0 +1,47 @@
+-- Automated emails sent to the subscribers of a list after they confirm.
+CREATE TABLE sequences (
+    sequence_id uuid NOT NULL PRIMARY KEY,
+    list_id uuid NOT NULL
+        REFERENCES lists (list_id),
+    name TEXT NOT NULL UNIQUE,
+    created_at timestamptz NOT NULL
+);
+
+CREATE TABLE sequence_steps (
+    step_id uuid NOT NULL PRIMARY KEY,
+    sequence_id uuid NOT NULL
+        REFERENCES sequences (sequence_id) ON DELETE CASCADE,
+    -- Counted from the confirmation of the subscription.
+    delay_hours INTEGER NOT NULL CHECK (delay_hours >= 0),
+    subject TEXT NOT NULL,
+    html_content TEXT NOT NULL,
+    text_content TEXT NOT NULL,
+    created_at timestamptz NOT NULL
+);
+CREATE INDEX sequence_steps_sequence_id_idx ON sequence_steps (sequence_id);
+
+CREATE TABLE sequence_enrollments (
+    sequence_id uuid NOT NULL
+        REFERENCES sequences (sequence_id) ON DELETE CASCADE,
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id) ON DELETE CASCADE,
+    enrolled_at timestamptz NOT NULL,
+    PRIMARY KEY (sequence_id, subscriber_id)
+);
+
+-- One row per step and subscriber once the step is due:
+-- queued, then sent, failed or cancelled.
+CREATE TABLE sequence_deliveries (
+    step_id uuid NOT NULL
+        REFERENCES sequence_steps (step_id) ON DELETE CASCADE,
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id) ON DELETE CASCADE,
+    status TEXT NOT NULL,
+    attempts INTEGER NOT NULL,
+    queued_at timestamptz NOT NULL,
+    next_attempt_at timestamptz NOT NULL,
+    finished_at timestamptz NULL,
+    PRIMARY KEY (step_id, subscriber_id)
+);
+CREATE INDEX sequence_deliveries_queued_idx ON sequence_deliveries (next_attempt_at)
+    WHERE status = 'queued';
@@ -0,0 +1,2 @@
+-- The language a subscriber gets emails and pages in.
+ALTER TABLE subscriptions ADD COLUMN locale TEXT NOT NULL DEFAULT 'en';
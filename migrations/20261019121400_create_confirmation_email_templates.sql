@@ -0,0 +1,13 @@
+-- Versions of the confirmation email edited from the admin UI, per locale.
+-- The latest version of a locale is the one sent; without any, the
+-- built-in copy is.
+CREATE TABLE confirmation_email_templates (
+    locale TEXT NOT NULL,
+    version INT NOT NULL CHECK (version > 0),
+    subject TEXT NOT NULL,
+    html_content TEXT NOT NULL,
+    text_content TEXT NOT NULL,
+    created_at timestamptz NOT NULL,
+    created_by uuid NULL REFERENCES users (user_id) ON DELETE SET NULL,
+    PRIMARY KEY (locale, version)
+);
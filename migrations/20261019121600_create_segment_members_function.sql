@@ -0,0 +1,26 @@
+-- The confirmed, unpaused subscriptions a segment reaches, in one list or in
+-- every list when `list` is NULL. Counting and sending both go through it,
+-- so that the count shown before sending matches who the issue goes to.
+CREATE FUNCTION segment_members(
+    tag TEXT,
+    subscribed_from DATE,
+    subscribed_until DATE,
+    source TEXT,
+    list uuid
+) RETURNS SETOF subscriptions
+LANGUAGE sql STABLE
+AS $$
+    SELECT s.*
+    FROM subscriptions s
+    WHERE s.status = 'confirmed' AND (list IS NULL OR s.list_id = list)
+        AND (tag IS NULL OR EXISTS (
+            SELECT 1 FROM subscriber_tags st JOIN tags t USING (tag_id)
+            WHERE st.subscriber_id = s.id AND t.name = tag
+        ))
+        AND (segment_members.subscribed_from IS NULL
+            OR s.subscribed_at >= segment_members.subscribed_from)
+        AND (segment_members.subscribed_until IS NULL
+            OR s.subscribed_at < segment_members.subscribed_until + 1)
+        AND (segment_members.source IS NULL OR s.source = segment_members.source)
+        AND (s.paused_until IS NULL OR s.paused_until <= now())
+$$;
@@ -0,0 +1,118 @@
+use std::future::{ready, Ready};
+
+use actix_web::{
+    body::MessageBody,
+    dev::{Payload, ServiceRequest, ServiceResponse},
+    error::ErrorForbidden,
+    http::{header::CONTENT_TYPE, Method},
+    web, FromRequest, HttpRequest,
+};
+use actix_web_lab::middleware::Next;
+use rand::RngCore;
+use subtle::ConstantTimeEq;
+
+use crate::{session_state::TypedSession, utils::e500};
+
+pub const CSRF_HEADER: &str = "X-CSRF-Token";
+
+/// The synchronizer token of the current session, created on first use.
+///
+/// Pages rendering a form extract it and embed it with the `_csrf.html` partial.
+pub struct CsrfToken(String);
+
+impl std::fmt::Display for CsrfToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl FromRequest for CsrfToken {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<CsrfToken, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        ready(get_or_create_token(req, payload).map(CsrfToken))
+    }
+}
+
+fn get_or_create_token(
+    req: &HttpRequest,
+    payload: &mut Payload,
+) -> Result<String, actix_web::Error> {
+    let session = TypedSession::from_request(req, payload).into_inner()?;
+    if let Some(token) = session.get_csrf_token().map_err(e500)? {
+        return Ok(token);
+    }
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    let token = hex::encode(bytes);
+    session.insert_csrf_token(&token).map_err(e500)?;
+    Ok(token)
+}
+
+#[derive(serde::Deserialize)]
+struct CsrfFormData {
+    csrf_token: Option<String>,
+}
+
+/// Reject state-changing requests that do not carry the session's CSRF token,
+/// either in the `csrf_token` form field or in the `X-CSRF-Token` header.
+pub async fn reject_invalid_csrf_tokens(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    if matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS) {
+        return next.call(req).await;
+    }
+
+    let session = {
+        let (http_request, payload) = req.parts_mut();
+        TypedSession::from_request(http_request, payload).await
+    }?;
+    let expected = session.get_csrf_token().map_err(e500)?;
+
+    let mut submitted = req
+        .headers()
+        .get(CSRF_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned);
+    let is_form = req
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/x-www-form-urlencoded"));
+    if submitted.is_none() && is_form {
+        // The handler still needs the body: read it, then put it back.
+        let body = req.extract::<web::Bytes>().await?;
+        submitted = serde_urlencoded::from_bytes::<CsrfFormData>(&body)
+            .ok()
+            .and_then(|form| form.csrf_token);
+        let (_, mut payload) = actix_http::h1::Payload::create(true);
+        payload.unread_data(body);
+        req.set_payload(payload.into());
+    }
+
+    match (expected, submitted) {
+        (Some(expected), Some(submitted)) if tokens_match(&expected, &submitted) => {
+            next.call(req).await
+        }
+        _ => Err(ErrorForbidden("Missing or invalid CSRF token")),
+    }
+}
+
+fn tokens_match(expected: &str, submitted: &str) -> bool {
+    expected.as_bytes().ct_eq(submitted.as_bytes()).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::tokens_match;
+
+    #[test]
+    fn only_the_exact_token_matches() {
+        assert!(tokens_match("abc123", "abc123"));
+        assert!(!tokens_match("abc123", "abc124"));
+        assert!(!tokens_match("abc123", "abc12"));
+        assert!(!tokens_match("abc123", ""));
+    }
+}
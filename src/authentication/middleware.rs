@@ -4,11 +4,12 @@ use actix_web::{
     body::MessageBody,
     dev::{ServiceRequest, ServiceResponse},
     error::InternalError,
-    FromRequest, HttpMessage,
+    web, FromRequest, HttpMessage,
 };
 use actix_web_lab::middleware::Next;
 use uuid::Uuid;
 
+use super::SessionRegistry;
 use crate::{
     session_state::TypedSession,
     utils::{e500, see_other},
@@ -42,9 +43,27 @@ pub async fn reject_anonymous_users(
 
     match session.get_user_id().map_err(e500)? {
         Some(user_id) => {
+            let registry = req
+                .app_data::<web::Data<SessionRegistry>>()
+                .ok_or_else(|| e500("Session registry is not configured"))?;
+            let is_active = match session.get_session_id().map_err(e500)? {
+                Some(session_id) => registry.touch(user_id, session_id).await.map_err(e500)?,
+                None => false,
+            };
+            if !is_active {
+                session.log_out();
+                let response = see_other("/login");
+                let e = anyhow::anyhow!("Session was revoked");
+                return Err(InternalError::from_response(e, response).into());
+            }
             req.extensions_mut().insert(UserId(user_id));
             next.call(req).await
         }
+        None if session.get_pending_totp().map_err(e500)?.is_some() => {
+            let response = see_other("/login/totp");
+            let e = anyhow::anyhow!("User has not completed two-factor authentication");
+            Err(InternalError::from_response(e, response).into())
+        }
         None => {
             let response = see_other("/login");
             let e = anyhow::anyhow!("User is not authenticated");
@@ -1,5 +1,18 @@
+mod csrf;
 mod middleware;
 mod password;
+mod password_policy;
+mod sessions;
+mod throttle;
+mod totp;
 
+pub use csrf::{reject_invalid_csrf_tokens, CsrfToken, CSRF_HEADER};
 pub use middleware::{reject_anonymous_users, UserId};
 pub use password::{change_password, validate_credentials, AuthError, Credentials};
+pub use password_policy::{estimate_strength, PasswordPolicy, PasswordPolicyViolation};
+pub use sessions::{ActiveSession, SessionRegistry};
+pub use throttle::{Lockout, LoginThrottle, ThrottleStatus, ThrottleTarget};
+pub use totp::{
+    enable_totp, generate_totp_secret, get_totp_secret, totp_qr_code_svg, totp_url,
+    use_recovery_code, use_totp_code, verify_totp_code,
+};
@@ -1,10 +1,13 @@
 use anyhow::Context;
-use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
+use argon2::{Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version};
 use secrecy::{ExposeSecret, Secret};
 use sqlx::PgPool;
 use uuid::Uuid;
 
-use crate::telemetry::spawn_blocking_with_tracing;
+use crate::{
+    configuration::PasswordHashingSettings, shutdown::BackgroundTasks,
+    telemetry::spawn_blocking_with_tracing,
+};
 
 #[derive(thiserror::Error, Debug)]
 pub enum AuthError {
@@ -19,18 +22,25 @@ pub struct Credentials {
     pub password: Secret<String>,
 }
 
-#[tracing::instrument(name = "Validate credentials", skip(pool, credentials))]
+#[tracing::instrument(
+    name = "Validate credentials",
+    skip(pool, credentials, hashing, background_tasks)
+)]
 pub async fn validate_credentials(
     pool: &PgPool,
     credentials: Credentials,
+    hashing: &PasswordHashingSettings,
+    background_tasks: &BackgroundTasks,
 ) -> Result<Uuid, AuthError> {
     let mut user_id = None;
-    let mut expected_password_hash = Secret::new(
-        "$argon2id$v=19$m=15000,t=2,p=1$\
+    // Verifying against a dummy hash with the current parameters means that
+    // unknown usernames take as long to reject as wrong passwords.
+    let mut expected_password_hash = Secret::new(format!(
+        "$argon2id$v=19$m={},t={},p={}$\
         gZiV/M1gPc22ElAH/Jh1Hw$\
-        CWOrkoo7oJBQ/iyn7uJ0LO2aLEfrHwTWllSAxT0zRno"
-            .to_string(),
-    );
+        CWOrkoo7oJBQ/iyn7uJ0LO2aLEfrHwTWllSAxT0zRno",
+        hashing.memory_kib, hashing.iterations, hashing.parallelism
+    ));
 
     if let Some((stored_user_id, stored_password_hash)) =
         get_stored_credentials(&credentials.username, pool)
@@ -40,8 +50,9 @@ pub async fn validate_credentials(
         user_id = Some(stored_user_id);
         expected_password_hash = stored_password_hash;
     }
-    println!("we got the stored credentials");
 
+    let password = credentials.password.clone();
+    let stored_password_hash = expected_password_hash.clone();
     spawn_blocking_with_tracing(|| {
         verify_password_hash(expected_password_hash, credentials.password)
     })
@@ -49,11 +60,76 @@ pub async fn validate_credentials(
     .context("Failed to spawn blocking task")
     .map_err(AuthError::UnexpectedError)??;
 
-    println!("validation is done");
-
-    user_id
+    let user_id = user_id
         .ok_or_else(|| anyhow::anyhow!("Invalid credentials"))
-        .map_err(AuthError::InvalidCredentials)
+        .map_err(AuthError::InvalidCredentials)?;
+
+    if needs_rehash(&stored_password_hash, hashing) {
+        let pool = pool.clone();
+        let hashing = hashing.clone();
+        background_tasks.spawn("password_rehash", async move {
+            if let Err(e) =
+                rehash_password(user_id, password, stored_password_hash, &pool, &hashing).await
+            {
+                tracing::error!(error.cause_chain = ?e, "Failed to upgrade password hash");
+            }
+        });
+    }
+
+    Ok(user_id)
+}
+
+/// Whether the stored hash was computed with weaker settings than the current ones.
+fn needs_rehash(stored_password_hash: &Secret<String>, hashing: &PasswordHashingSettings) -> bool {
+    let hash = match PasswordHash::new(stored_password_hash.expose_secret()) {
+        Ok(hash) => hash,
+        Err(_) => return false,
+    };
+    let params = match Params::try_from(&hash) {
+        Ok(params) => params,
+        Err(_) => return true,
+    };
+    hash.algorithm != Algorithm::Argon2id.ident()
+        || hash.version != Some(Version::V0x13.into())
+        || params.m_cost() < hashing.memory_kib
+        || params.t_cost() < hashing.iterations
+        || params.p_cost() < hashing.parallelism
+}
+
+#[tracing::instrument(
+    name = "Upgrade password hash",
+    skip(password, stored_password_hash, pool, hashing)
+)]
+async fn rehash_password(
+    user_id: Uuid,
+    password: Secret<String>,
+    stored_password_hash: Secret<String>,
+    pool: &PgPool,
+    hashing: &PasswordHashingSettings,
+) -> Result<(), anyhow::Error> {
+    let hashing = hashing.clone();
+    let password_hash =
+        spawn_blocking_with_tracing(move || compute_password_hash(password, &hashing))
+            .await?
+            .context("failed to spawn blocking task")?;
+
+    // Only replace the hash we verified against: if the password was changed
+    // in the meantime, the new one wins.
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET password_hash = $1
+        WHERE user_id = $2 AND password_hash = $3
+        "#,
+        password_hash.expose_secret(),
+        user_id,
+        stored_password_hash.expose_secret(),
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store upgraded password hash")?;
+
+    Ok(())
 }
 
 #[tracing::instrument(name = "Verify password hash", skip(expected_password_hash, password))]
@@ -61,17 +137,14 @@ fn verify_password_hash(
     expected_password_hash: Secret<String>,
     password: Secret<String>,
 ) -> Result<(), AuthError> {
-    println!("we are verifying the password hash");
     let expected_password_hash = PasswordHash::new(expected_password_hash.expose_secret())
         .context("Failed to parse password hash")?;
 
-    println!("we parsed the password hash");
-
+    // The parameters to verify with are read from the hash itself.
     Argon2::default()
         .verify_password(password.expose_secret().as_bytes(), &expected_password_hash)
         .context("failed to verify password hash")
         .map_err(AuthError::InvalidCredentials)?;
-    println!("we verified the password hash");
     Ok(())
 }
 
@@ -94,15 +167,18 @@ async fn get_stored_credentials(
     Ok(user)
 }
 
-#[tracing::instrument(name = "Change password", skip(password, pool))]
+#[tracing::instrument(name = "Change password", skip(password, pool, hashing))]
 pub async fn change_password(
     user_id: Uuid,
     password: Secret<String>,
     pool: &PgPool,
+    hashing: &PasswordHashingSettings,
 ) -> Result<(), anyhow::Error> {
-    let password_hash = spawn_blocking_with_tracing(move || compute_password_hash(password))
-        .await?
-        .context("failed to spawn blocking task")?;
+    let hashing = hashing.clone();
+    let password_hash =
+        spawn_blocking_with_tracing(move || compute_password_hash(password, &hashing))
+            .await?
+            .context("failed to spawn blocking task")?;
 
     sqlx::query!(
         r#"
@@ -120,12 +196,50 @@ pub async fn change_password(
     Ok(())
 }
 
-#[tracing::instrument(name = "Compute password hash", skip(password))]
-fn compute_password_hash(password: Secret<String>) -> Result<Secret<String>, anyhow::Error> {
+#[tracing::instrument(name = "Compute password hash", skip(password, hashing))]
+fn compute_password_hash(
+    password: Secret<String>,
+    hashing: &PasswordHashingSettings,
+) -> Result<Secret<String>, anyhow::Error> {
     let salt = argon2::password_hash::SaltString::generate(&mut rand::thread_rng());
-    let password_hash = Argon2::default()
+    let params = hashing.params().context("Invalid argon2 parameters")?;
+    let password_hash = Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
         .hash_password(password.expose_secret().as_bytes(), &salt)
         .context("Failed to hash password")?
         .to_string();
     Ok(Secret::new(password_hash))
 }
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+
+    use super::{compute_password_hash, needs_rehash};
+    use crate::configuration::PasswordHashingSettings;
+
+    fn hashing(memory_kib: u32) -> PasswordHashingSettings {
+        PasswordHashingSettings {
+            memory_kib,
+            iterations: 2,
+            parallelism: 1,
+        }
+    }
+
+    #[test]
+    fn a_hash_computed_with_the_current_settings_is_kept() {
+        let hash = compute_password_hash(Secret::new("password".into()), &hashing(8192)).unwrap();
+        assert!(!needs_rehash(&hash, &hashing(8192)));
+    }
+
+    #[test]
+    fn a_hash_computed_with_weaker_settings_is_upgraded() {
+        let hash = compute_password_hash(Secret::new("password".into()), &hashing(4096)).unwrap();
+        assert!(needs_rehash(&hash, &hashing(8192)));
+    }
+
+    #[test]
+    fn a_hash_computed_with_stronger_settings_is_kept() {
+        let hash = compute_password_hash(Secret::new("password".into()), &hashing(8192)).unwrap();
+        assert!(!needs_rehash(&hash, &hashing(4096)));
+    }
+}
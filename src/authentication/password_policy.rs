@@ -0,0 +1,237 @@
+use secrecy::{ExposeSecret, Secret};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// A short list of passwords (and password fragments) that show up at the top
+/// of every breach corpus. Any password built around one of them is weak no
+/// matter how long it is.
+const COMMON_PASSWORDS: &[&str] = &[
+    "password",
+    "passw0rd",
+    "123456",
+    "qwerty",
+    "azerty",
+    "letmein",
+    "welcome",
+    "monkey",
+    "dragon",
+    "football",
+    "baseball",
+    "iloveyou",
+    "admin",
+    "login",
+    "master",
+    "sunshine",
+    "princess",
+    "shadow",
+    "superman",
+    "trustno1",
+    "abc123",
+    "starwars",
+    "whatever",
+    "freedom",
+    "qazwsx",
+    "zxcvbn",
+    "asdfgh",
+    "secret",
+    "hello",
+    "charlie",
+    "newsletter",
+    "changeme",
+];
+
+/// Rules every new password has to satisfy, whichever flow sets it.
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub max_length: usize,
+    /// Minimum strength score, from 0 (trivially guessable) to 4 (very strong).
+    pub min_strength: u8,
+}
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum PasswordPolicyViolation {
+    #[error("The new password must be at least {0} characters long")]
+    TooShort(usize),
+    #[error("The new password must be at most {0} characters long")]
+    TooLong(usize),
+    #[error("The new password is too easy to guess, try a longer passphrase")]
+    TooWeak,
+    #[error("The new password must not contain your username")]
+    ContainsUsername,
+    #[error("The new password must be different from the current password")]
+    SameAsCurrent,
+}
+
+impl PasswordPolicy {
+    pub fn check(
+        &self,
+        password: &Secret<String>,
+        username: &str,
+        current_password: Option<&Secret<String>>,
+    ) -> Result<(), PasswordPolicyViolation> {
+        let password = password.expose_secret();
+        let length = password.graphemes(true).count();
+        if length < self.min_length {
+            return Err(PasswordPolicyViolation::TooShort(self.min_length));
+        }
+        if length > self.max_length {
+            return Err(PasswordPolicyViolation::TooLong(self.max_length));
+        }
+        if current_password.is_some_and(|current| current.expose_secret() == password) {
+            return Err(PasswordPolicyViolation::SameAsCurrent);
+        }
+        let username = username.trim().to_lowercase();
+        if username.chars().count() >= 3 && password.to_lowercase().contains(&username) {
+            return Err(PasswordPolicyViolation::ContainsUsername);
+        }
+        if estimate_strength(password) < self.min_strength {
+            return Err(PasswordPolicyViolation::TooWeak);
+        }
+        Ok(())
+    }
+}
+
+/// Rough strength score from 0 to 4, based on the entropy of the password
+/// once repeated characters, keyboard-style sequences and common passwords
+/// have been discounted.
+pub fn estimate_strength(password: &str) -> u8 {
+    let bits = estimate_entropy_bits(password);
+    match bits {
+        b if b < 28.0 => 0,
+        b if b < 36.0 => 1,
+        b if b < 50.0 => 2,
+        b if b < 70.0 => 3,
+        _ => 4,
+    }
+}
+
+fn estimate_entropy_bits(password: &str) -> f64 {
+    let mut pool = 0;
+    if password.chars().any(|c| c.is_ascii_lowercase()) {
+        pool += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_uppercase()) {
+        pool += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_digit()) {
+        pool += 10;
+    }
+    if password
+        .chars()
+        .any(|c| c.is_ascii_punctuation() || c == ' ')
+    {
+        pool += 33;
+    }
+    if !password.is_ascii() {
+        pool += 100;
+    }
+    if pool == 0 {
+        return 0.0;
+    }
+
+    // Characters that repeat or continue a sequence ("aaa", "abc", "321")
+    // add next to nothing to the search space.
+    let mut effective_length = 0usize;
+    let mut previous: Option<char> = None;
+    for c in password.chars() {
+        let predictable = previous.is_some_and(|p| (c as i64 - p as i64).abs() <= 1);
+        if !predictable {
+            effective_length += 1;
+        }
+        previous = Some(c);
+    }
+
+    // A common password hidden in a longer one counts as a single character.
+    let lowercase = password.to_lowercase();
+    for common in COMMON_PASSWORDS {
+        if lowercase.contains(common) {
+            effective_length = effective_length.saturating_sub(common.len() - 1);
+        }
+    }
+
+    effective_length as f64 * (pool as f64).log2()
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err_eq, assert_ok};
+    use secrecy::Secret;
+
+    use super::{estimate_strength, PasswordPolicy, PasswordPolicyViolation};
+
+    fn policy() -> PasswordPolicy {
+        PasswordPolicy {
+            min_length: 12,
+            max_length: 128,
+            min_strength: 3,
+        }
+    }
+
+    fn check(password: &str) -> Result<(), PasswordPolicyViolation> {
+        policy().check(&Secret::new(password.to_string()), "kcnklub", None)
+    }
+
+    #[test]
+    fn an_empty_password_is_rejected() {
+        assert_err_eq!(check(""), PasswordPolicyViolation::TooShort(12));
+    }
+
+    #[test]
+    fn a_password_longer_than_the_maximum_is_rejected() {
+        assert_err_eq!(
+            check(&"correct horse battery staple ".repeat(5)),
+            PasswordPolicyViolation::TooLong(128)
+        );
+    }
+
+    #[test]
+    fn a_password_containing_the_username_is_rejected() {
+        assert_err_eq!(
+            check("x9!KcnKlub-rules-2024"),
+            PasswordPolicyViolation::ContainsUsername
+        );
+    }
+
+    #[test]
+    fn the_current_password_cannot_be_reused() {
+        let password = Secret::new("correct horse battery staple".to_string());
+        assert_err_eq!(
+            policy().check(&password, "kcnklub", Some(&password)),
+            PasswordPolicyViolation::SameAsCurrent
+        );
+    }
+
+    #[test]
+    fn guessable_passwords_are_rejected() {
+        for password in [
+            "password1234",
+            "aaaaaaaaaaaaaaaa",
+            "abcdefghijklmnop",
+            "123456789012",
+            "Password123!",
+            "qwertyqwerty",
+        ] {
+            assert_err_eq!(
+                check(password),
+                PasswordPolicyViolation::TooWeak,
+                "{password}"
+            );
+        }
+    }
+
+    #[test]
+    fn strong_passwords_are_accepted() {
+        for password in [
+            "correct horse battery staple",
+            "Tr0ub4dor&3-extended",
+            "9vJ#qL2!mZ8@wX4p",
+        ] {
+            assert_ok!(check(password), "{password}");
+        }
+    }
+
+    #[test]
+    fn strength_grows_with_length() {
+        assert!(estimate_strength("kq7z") < estimate_strength("kq7zm2vx9tpw"));
+    }
+}
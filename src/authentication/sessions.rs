@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+
+use actix_web::HttpRequest;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use redis::{aio::ConnectionManager, AsyncCommands};
+use uuid::Uuid;
+
+use crate::utils::{client_ip, user_agent};
+
+/// How long an idle session stays in the registry. Every authenticated
+/// request pushes the expiry back.
+const SESSION_IDLE_SECONDS: usize = 60 * 60 * 24;
+
+fn session_key(session_id: Uuid) -> String {
+    format!("session:{session_id}")
+}
+
+fn user_sessions_key(user_id: Uuid) -> String {
+    format!("user_sessions:{user_id}")
+}
+
+#[derive(Debug)]
+pub struct ActiveSession {
+    pub session_id: Uuid,
+    pub user_agent: String,
+    pub ip: String,
+    pub created_at: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+}
+
+impl ActiveSession {
+    fn from_fields(session_id: Uuid, fields: &HashMap<String, String>) -> Option<Self> {
+        let timestamp = |name: &str| {
+            fields
+                .get(name)
+                .and_then(|value| DateTime::parse_from_rfc3339(value).ok())
+                .map(|value| value.with_timezone(&Utc))
+        };
+        Some(Self {
+            session_id,
+            user_agent: fields.get("user_agent").cloned().unwrap_or_default(),
+            ip: fields.get("ip").cloned().unwrap_or_default(),
+            created_at: timestamp("created_at")?,
+            last_seen: timestamp("last_seen")?,
+        })
+    }
+}
+
+/// Index of the logged in sessions of each user, stored in Redis next to the
+/// session state itself.
+///
+/// A session only counts as authenticated while its entry exists: deleting
+/// the entry revokes the session on its next request.
+#[derive(Clone)]
+pub struct SessionRegistry {
+    connection: ConnectionManager,
+}
+
+impl SessionRegistry {
+    pub fn new(connection: ConnectionManager) -> Self {
+        Self { connection }
+    }
+
+    /// Add a freshly logged in session, remembering the device it came from.
+    #[tracing::instrument(name = "Register session", skip(self, request))]
+    pub async fn register(
+        &self,
+        user_id: Uuid,
+        request: &HttpRequest,
+    ) -> Result<Uuid, anyhow::Error> {
+        let user_agent = user_agent(request);
+        let ip = client_ip(request);
+
+        let mut connection = self.connection.clone();
+        let session_id = Uuid::new_v4();
+        let now = Utc::now().to_rfc3339();
+        connection
+            .hset_multiple::<_, _, _, ()>(
+                session_key(session_id),
+                &[
+                    ("user_id", user_id.to_string()),
+                    ("user_agent", user_agent),
+                    ("ip", ip),
+                    ("created_at", now.clone()),
+                    ("last_seen", now),
+                ],
+            )
+            .await
+            .context("Failed to store session")?;
+        connection
+            .expire::<_, ()>(session_key(session_id), SESSION_IDLE_SECONDS)
+            .await
+            .context("Failed to set session expiry")?;
+        connection
+            .sadd::<_, _, ()>(user_sessions_key(user_id), session_id.to_string())
+            .await
+            .context("Failed to index session")?;
+        Ok(session_id)
+    }
+
+    /// Record activity on a session. Returns `false` if the session was
+    /// revoked or has expired.
+    #[tracing::instrument(name = "Touch session", skip(self))]
+    pub async fn touch(&self, user_id: Uuid, session_id: Uuid) -> Result<bool, anyhow::Error> {
+        let mut connection = self.connection.clone();
+        let owner: Option<String> = connection
+            .hget(session_key(session_id), "user_id")
+            .await
+            .context("Failed to read session")?;
+        if owner != Some(user_id.to_string()) {
+            return Ok(false);
+        }
+        connection
+            .hset::<_, _, _, ()>(
+                session_key(session_id),
+                "last_seen",
+                Utc::now().to_rfc3339(),
+            )
+            .await
+            .context("Failed to update session")?;
+        connection
+            .expire::<_, ()>(session_key(session_id), SESSION_IDLE_SECONDS)
+            .await
+            .context("Failed to set session expiry")?;
+        Ok(true)
+    }
+
+    /// The sessions of a user, most recently used first.
+    #[tracing::instrument(name = "List sessions", skip(self))]
+    pub async fn list(&self, user_id: Uuid) -> Result<Vec<ActiveSession>, anyhow::Error> {
+        let mut connection = self.connection.clone();
+        let session_ids: Vec<String> = connection
+            .smembers(user_sessions_key(user_id))
+            .await
+            .context("Failed to list sessions")?;
+
+        let mut sessions = Vec::new();
+        for raw_session_id in session_ids {
+            let mut session = None;
+            if let Ok(session_id) = Uuid::parse_str(&raw_session_id) {
+                let fields: HashMap<String, String> = connection
+                    .hgetall(session_key(session_id))
+                    .await
+                    .context("Failed to read session")?;
+                session = ActiveSession::from_fields(session_id, &fields);
+            }
+            match session {
+                Some(session) => sessions.push(session),
+                None => {
+                    // The session expired on its own, drop it from the index.
+                    connection
+                        .srem::<_, _, ()>(user_sessions_key(user_id), &raw_session_id)
+                        .await
+                        .context("Failed to prune session index")?;
+                }
+            }
+        }
+        sessions.sort_by_key(|session| std::cmp::Reverse(session.last_seen));
+        Ok(sessions)
+    }
+
+    /// Revoke one of the user's sessions. Returns `false` if the session does
+    /// not belong to the user.
+    #[tracing::instrument(name = "Revoke session", skip(self))]
+    pub async fn revoke(&self, user_id: Uuid, session_id: Uuid) -> Result<bool, anyhow::Error> {
+        let mut connection = self.connection.clone();
+        let owned: bool = connection
+            .sismember(user_sessions_key(user_id), session_id.to_string())
+            .await
+            .context("Failed to look up session")?;
+        if !owned {
+            return Ok(false);
+        }
+        connection
+            .del::<_, ()>(session_key(session_id))
+            .await
+            .context("Failed to delete session")?;
+        connection
+            .srem::<_, _, ()>(user_sessions_key(user_id), session_id.to_string())
+            .await
+            .context("Failed to prune session index")?;
+        Ok(true)
+    }
+
+    /// Revoke every session of the user, except `keep` if provided.
+    #[tracing::instrument(name = "Revoke all sessions", skip(self))]
+    pub async fn revoke_all(&self, user_id: Uuid, keep: Option<Uuid>) -> Result<(), anyhow::Error> {
+        let mut connection = self.connection.clone();
+        let session_ids: Vec<String> = connection
+            .smembers(user_sessions_key(user_id))
+            .await
+            .context("Failed to list sessions")?;
+        for session_id in session_ids
+            .iter()
+            .filter_map(|session_id| Uuid::parse_str(session_id).ok())
+            .filter(|session_id| Some(*session_id) != keep)
+        {
+            self.revoke(user_id, session_id).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use uuid::Uuid;
+
+    use super::ActiveSession;
+
+    #[test]
+    fn a_session_is_read_back_from_its_fields() {
+        let fields: HashMap<String, String> = [
+            ("user_id", "ignored"),
+            ("user_agent", "Firefox"),
+            ("ip", "10.0.0.1"),
+            ("created_at", "2024-01-01T10:00:00+00:00"),
+            ("last_seen", "2024-01-01T11:00:00+00:00"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+
+        let session = ActiveSession::from_fields(Uuid::new_v4(), &fields).unwrap();
+        assert_eq!(session.user_agent, "Firefox");
+        assert_eq!(session.ip, "10.0.0.1");
+        assert!(session.last_seen > session.created_at);
+    }
+
+    #[test]
+    fn an_expired_session_has_no_fields() {
+        assert!(ActiveSession::from_fields(Uuid::new_v4(), &HashMap::new()).is_none());
+    }
+}
@@ -0,0 +1,251 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use redis::{aio::ConnectionManager, AsyncCommands};
+
+use crate::configuration::LoginThrottleSettings;
+
+const LOCKOUT_INDEX: &str = "login_lockouts";
+
+/// Who a failed-attempt counter or a lockout applies to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ThrottleTarget {
+    Username(String),
+    Ip(String),
+}
+
+impl ThrottleTarget {
+    fn key(&self) -> String {
+        match self {
+            ThrottleTarget::Username(username) => format!("user:{username}"),
+            ThrottleTarget::Ip(ip) => format!("ip:{ip}"),
+        }
+    }
+
+    pub fn parse(key: &str) -> Option<Self> {
+        match key.split_once(':')? {
+            ("user", username) => Some(ThrottleTarget::Username(username.to_string())),
+            ("ip", ip) => Some(ThrottleTarget::Ip(ip.to_string())),
+            _ => None,
+        }
+    }
+
+    fn failures_key(&self) -> String {
+        format!("login_failures:{}", self.key())
+    }
+
+    fn lockout_key(&self) -> String {
+        format!("login_lockout:{}", self.key())
+    }
+}
+
+impl std::fmt::Display for ThrottleTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.key())
+    }
+}
+
+pub struct Lockout {
+    pub target: ThrottleTarget,
+    pub remaining: Duration,
+}
+
+pub enum ThrottleStatus {
+    /// The attempt may proceed once `delay` has elapsed.
+    Allowed {
+        delay: Duration,
+    },
+    Locked,
+}
+
+/// Failed login attempt counters and temporary lockouts, stored in Redis
+/// so that they are shared by every instance of the application.
+#[derive(Clone)]
+pub struct LoginThrottle {
+    connection: ConnectionManager,
+    settings: LoginThrottleSettings,
+}
+
+impl LoginThrottle {
+    pub fn new(connection: ConnectionManager, settings: LoginThrottleSettings) -> Self {
+        Self {
+            connection,
+            settings,
+        }
+    }
+
+    #[tracing::instrument(name = "Check login throttle", skip(self))]
+    pub async fn check(&self, username: &str, ip: &str) -> Result<ThrottleStatus, anyhow::Error> {
+        let mut connection = self.connection.clone();
+        let mut failures = 0;
+        for target in targets(username, ip) {
+            let locked: bool = connection
+                .exists(target.lockout_key())
+                .await
+                .context("Failed to check lockout")?;
+            if locked {
+                return Ok(ThrottleStatus::Locked);
+            }
+            let count: Option<u64> = connection
+                .get(target.failures_key())
+                .await
+                .context("Failed to read failed attempts")?;
+            failures = failures.max(count.unwrap_or(0));
+        }
+        Ok(ThrottleStatus::Allowed {
+            delay: self.settings.delay(failures),
+        })
+    }
+
+    #[tracing::instrument(name = "Record failed login attempt", skip(self))]
+    pub async fn record_failure(&self, username: &str, ip: &str) -> Result<(), anyhow::Error> {
+        let mut connection = self.connection.clone();
+        for target in targets(username, ip) {
+            let failures: u64 = connection
+                .incr(target.failures_key(), 1)
+                .await
+                .context("Failed to increment failed attempts")?;
+            if failures == 1 {
+                connection
+                    .expire::<_, ()>(
+                        target.failures_key(),
+                        self.settings.attempt_window_seconds as usize,
+                    )
+                    .await
+                    .context("Failed to set failed attempts expiry")?;
+            }
+
+            let max_attempts = match target {
+                ThrottleTarget::Username(_) => self.settings.max_attempts_per_username,
+                ThrottleTarget::Ip(_) => self.settings.max_attempts_per_ip,
+            };
+            if failures >= max_attempts {
+                tracing::warn!(%target, failures, "Locking out after too many failed logins");
+                connection
+                    .set_ex::<_, _, ()>(
+                        target.lockout_key(),
+                        failures,
+                        self.settings.lockout_seconds as usize,
+                    )
+                    .await
+                    .context("Failed to store lockout")?;
+                connection
+                    .sadd::<_, _, ()>(LOCKOUT_INDEX, target.key())
+                    .await
+                    .context("Failed to index lockout")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Forget past failures for a username after a successful login.
+    #[tracing::instrument(name = "Reset failed login attempts", skip(self))]
+    pub async fn record_success(&self, username: &str) -> Result<(), anyhow::Error> {
+        let mut connection = self.connection.clone();
+        connection
+            .del::<_, ()>(ThrottleTarget::Username(username.to_string()).failures_key())
+            .await
+            .context("Failed to reset failed attempts")?;
+        Ok(())
+    }
+
+    #[tracing::instrument(name = "List login lockouts", skip(self))]
+    pub async fn lockouts(&self) -> Result<Vec<Lockout>, anyhow::Error> {
+        let mut connection = self.connection.clone();
+        let keys: Vec<String> = connection
+            .smembers(LOCKOUT_INDEX)
+            .await
+            .context("Failed to list lockouts")?;
+
+        let mut lockouts = Vec::new();
+        for key in keys {
+            let target = match ThrottleTarget::parse(&key) {
+                Some(target) => target,
+                None => continue,
+            };
+            let ttl: i64 = connection
+                .ttl(target.lockout_key())
+                .await
+                .context("Failed to read lockout expiry")?;
+            if ttl > 0 {
+                lockouts.push(Lockout {
+                    target,
+                    remaining: Duration::from_secs(ttl as u64),
+                });
+            } else {
+                // The lockout expired on its own, drop it from the index.
+                connection
+                    .srem::<_, _, ()>(LOCKOUT_INDEX, &key)
+                    .await
+                    .context("Failed to prune lockout index")?;
+            }
+        }
+        Ok(lockouts)
+    }
+
+    #[tracing::instrument(name = "Clear login lockout", skip(self))]
+    pub async fn clear(&self, target: &ThrottleTarget) -> Result<(), anyhow::Error> {
+        let mut connection = self.connection.clone();
+        connection
+            .del::<_, ()>(&[target.lockout_key(), target.failures_key()])
+            .await
+            .context("Failed to clear lockout")?;
+        connection
+            .srem::<_, _, ()>(LOCKOUT_INDEX, target.key())
+            .await
+            .context("Failed to prune lockout index")?;
+        Ok(())
+    }
+}
+
+fn targets(username: &str, ip: &str) -> [ThrottleTarget; 2] {
+    [
+        ThrottleTarget::Username(username.to_string()),
+        ThrottleTarget::Ip(ip.to_string()),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::ThrottleTarget;
+    use crate::configuration::LoginThrottleSettings;
+
+    fn settings() -> LoginThrottleSettings {
+        LoginThrottleSettings {
+            max_attempts_per_username: 5,
+            max_attempts_per_ip: 20,
+            attempt_window_seconds: 900,
+            lockout_seconds: 900,
+            base_delay_milliseconds: 250,
+            max_delay_milliseconds: 4000,
+        }
+    }
+
+    #[test]
+    fn there_is_no_delay_without_failures() {
+        assert_eq!(settings().delay(0), Duration::ZERO);
+    }
+
+    #[test]
+    fn the_delay_doubles_with_each_failure_up_to_the_cap() {
+        let settings = settings();
+        assert_eq!(settings.delay(1), Duration::from_millis(250));
+        assert_eq!(settings.delay(2), Duration::from_millis(500));
+        assert_eq!(settings.delay(3), Duration::from_millis(1000));
+        assert_eq!(settings.delay(10), Duration::from_millis(4000));
+        assert_eq!(settings.delay(u64::MAX), Duration::from_millis(4000));
+    }
+
+    #[test]
+    fn targets_round_trip_through_their_key() {
+        for target in [
+            ThrottleTarget::Username("admin".into()),
+            ThrottleTarget::Ip("10.0.0.1".into()),
+            ThrottleTarget::Ip("::1".into()),
+        ] {
+            assert_eq!(ThrottleTarget::parse(&target.to_string()), Some(target));
+        }
+    }
+}
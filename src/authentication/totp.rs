@@ -0,0 +1,256 @@
+use anyhow::Context;
+use chrono::Utc;
+use rand::{distributions::Alphanumeric, thread_rng, Rng, RngCore};
+use secrecy::{ExposeSecret, Secret};
+use sha3::Digest;
+use sqlx::PgPool;
+use std::time::{SystemTime, UNIX_EPOCH};
+use totp_rs::{Algorithm, TOTP};
+use uuid::Uuid;
+
+const ISSUER: &str = "zero2prod";
+const RECOVERY_CODE_COUNT: usize = 10;
+
+/// Generate a new base32 encoded shared secret (160 bits, as recommended by RFC 4226).
+pub fn generate_totp_secret() -> Secret<String> {
+    let mut bytes = [0u8; 20];
+    thread_rng().fill_bytes(&mut bytes);
+    let encoded = totp_rs::Secret::Raw(bytes.to_vec())
+        .to_encoded()
+        .to_string();
+    Secret::new(encoded)
+}
+
+fn build_totp(secret: &Secret<String>, account_name: &str) -> Result<TOTP, anyhow::Error> {
+    let bytes = totp_rs::Secret::Encoded(secret.expose_secret().clone())
+        .to_bytes()
+        .map_err(|e| anyhow::anyhow!("Failed to decode TOTP secret: {e:?}"))?;
+    TOTP::new(
+        Algorithm::SHA1,
+        6,
+        1,
+        30,
+        bytes,
+        Some(ISSUER.to_string()),
+        account_name.replace(':', ""),
+    )
+    .context("Failed to build TOTP")
+}
+
+/// The `otpauth://` URL authenticator apps expect, either typed in or scanned as a QR code.
+pub fn totp_url(secret: &Secret<String>, account_name: &str) -> Result<String, anyhow::Error> {
+    Ok(build_totp(secret, account_name)?.get_url())
+}
+
+/// Render the `otpauth://` URL as an inline SVG QR code.
+pub fn totp_qr_code_svg(
+    secret: &Secret<String>,
+    account_name: &str,
+) -> Result<String, anyhow::Error> {
+    let url = totp_url(secret, account_name)?;
+    let code = qrcode::QrCode::new(url.as_bytes()).context("Failed to encode QR code")?;
+    Ok(code
+        .render::<qrcode::render::svg::Color>()
+        .min_dimensions(200, 200)
+        .build())
+}
+
+pub fn verify_totp_code(secret: &Secret<String>, code: &str) -> Result<bool, anyhow::Error> {
+    Ok(matching_time_step(secret, code)?.is_some())
+}
+
+/// The time step `code` was generated for, if it is valid now. One step of
+/// clock drift is tolerated either way.
+fn matching_time_step(secret: &Secret<String>, code: &str) -> Result<Option<u64>, anyhow::Error> {
+    let mut totp = build_totp(secret, "")?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .context("System time is before the UNIX epoch")?
+        .as_secs();
+    let current = now / totp.step;
+    let skew = u64::from(totp.skew);
+    // Check the steps one by one to learn which one matched.
+    totp.skew = 0;
+    Ok((current.saturating_sub(skew)..=current + skew)
+        .find(|step| totp.check(code.trim(), step * totp.step)))
+}
+
+/// Check a code entered at login. A code is only accepted once: codes for the
+/// time step of the last accepted one or an earlier step are rejected, so
+/// that an intercepted code cannot be replayed.
+#[tracing::instrument(name = "Use TOTP code", skip(secret, code, pool))]
+pub async fn use_totp_code(
+    user_id: Uuid,
+    secret: &Secret<String>,
+    code: &str,
+    pool: &PgPool,
+) -> Result<bool, anyhow::Error> {
+    let Some(step) = matching_time_step(secret, code)? else {
+        return Ok(false);
+    };
+    let result = sqlx::query!(
+        r#"
+        UPDATE users
+        SET totp_last_used_step = $1
+        WHERE user_id = $2
+            AND (totp_last_used_step IS NULL OR totp_last_used_step < $1)
+        "#,
+        step as i64,
+        user_id,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to record the TOTP time step")?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+#[tracing::instrument(name = "Get TOTP secret", skip(pool))]
+pub async fn get_totp_secret(
+    user_id: Uuid,
+    pool: &PgPool,
+) -> Result<Option<Secret<String>>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT totp_secret
+        FROM users
+        WHERE user_id = $1
+        "#,
+        user_id,
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to fetch TOTP secret")?;
+
+    Ok(row.totp_secret.map(Secret::new))
+}
+
+/// Store the verified secret for the user and replace any existing recovery codes.
+///
+/// The freshly generated recovery codes are returned in plain text: this is the
+/// only time they are available, only their hashes are persisted.
+#[tracing::instrument(name = "Enable TOTP", skip(secret, pool))]
+pub async fn enable_totp(
+    user_id: Uuid,
+    secret: Secret<String>,
+    pool: &PgPool,
+) -> Result<Vec<String>, anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection")?;
+
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET totp_secret = $1, totp_last_used_step = NULL
+        WHERE user_id = $2
+        "#,
+        secret.expose_secret(),
+        user_id,
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to store TOTP secret")?;
+
+    sqlx::query!(r#"DELETE FROM recovery_codes WHERE user_id = $1"#, user_id)
+        .execute(&mut transaction)
+        .await
+        .context("Failed to delete previous recovery codes")?;
+
+    let recovery_codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+        .map(|_| generate_recovery_code())
+        .collect();
+    for code in &recovery_codes {
+        sqlx::query!(
+            r#"
+            INSERT INTO recovery_codes (user_id, code_hash)
+            VALUES ($1, $2)
+            "#,
+            user_id,
+            hash_recovery_code(code),
+        )
+        .execute(&mut transaction)
+        .await
+        .context("Failed to store recovery code")?;
+    }
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction")?;
+
+    Ok(recovery_codes)
+}
+
+/// Mark a recovery code as used. Returns `false` if the code is unknown or was already used.
+#[tracing::instrument(name = "Use recovery code", skip(code, pool))]
+pub async fn use_recovery_code(
+    user_id: Uuid,
+    code: &str,
+    pool: &PgPool,
+) -> Result<bool, anyhow::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE recovery_codes
+        SET used_at = $1
+        WHERE user_id = $2 AND code_hash = $3 AND used_at IS NULL
+        "#,
+        Utc::now(),
+        user_id,
+        hash_recovery_code(code),
+    )
+    .execute(pool)
+    .await
+    .context("Failed to consume recovery code")?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+fn generate_recovery_code() -> String {
+    let code: String = thread_rng()
+        .sample_iter(Alphanumeric)
+        .map(char::from)
+        .take(10)
+        .collect();
+    code.to_lowercase()
+}
+
+fn hash_recovery_code(code: &str) -> String {
+    let normalized = code.trim().to_lowercase();
+    hex::encode(sha3::Sha3_256::digest(normalized.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_ok_eq, assert_some_eq};
+    use totp_rs::{Algorithm, TOTP};
+
+    use super::{generate_totp_secret, hash_recovery_code, verify_totp_code};
+
+    #[test]
+    fn a_code_generated_from_the_secret_is_accepted() {
+        let secret = generate_totp_secret();
+        let bytes = totp_rs::Secret::Encoded(secrecy::ExposeSecret::expose_secret(&secret).clone())
+            .to_bytes()
+            .unwrap();
+        let totp = TOTP::new(Algorithm::SHA1, 6, 1, 30, bytes, None, "".into()).unwrap();
+        let code = totp.generate_current().unwrap();
+
+        assert_ok_eq!(verify_totp_code(&secret, &code), true);
+    }
+
+    #[test]
+    fn a_wrong_code_is_rejected() {
+        let secret = generate_totp_secret();
+        assert_some_eq!(verify_totp_code(&secret, "not-a-code").ok(), false);
+    }
+
+    #[test]
+    fn recovery_code_hash_ignores_case_and_whitespace() {
+        assert_eq!(
+            hash_recovery_code(" AbCdE12345 "),
+            hash_recovery_code("abcde12345")
+        );
+    }
+}
@@ -0,0 +1,296 @@
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+
+use crate::configuration::BotProtectionSettings;
+
+/// What the verifiers get to look at for a subscription attempt.
+#[derive(Debug)]
+pub struct SubscriptionAttempt<'a> {
+    pub ip: &'a str,
+    /// A form field hidden from people, that only bots fill in.
+    pub honeypot: Option<&'a str>,
+    pub form_token: Option<&'a str>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Verdict {
+    Human,
+    Bot { reason: &'static str },
+}
+
+/// A check telling people from scripts, such as a CAPTCHA provider.
+#[async_trait]
+pub trait SubmissionVerifier: Send + Sync {
+    async fn verify(&self, attempt: &SubscriptionAttempt<'_>) -> Result<Verdict, anyhow::Error>;
+}
+
+/// Flags attempts that filled in the honeypot field.
+pub struct Honeypot;
+
+#[async_trait]
+impl SubmissionVerifier for Honeypot {
+    async fn verify(&self, attempt: &SubscriptionAttempt<'_>) -> Result<Verdict, anyhow::Error> {
+        Ok(match attempt.honeypot {
+            Some(value) if !value.is_empty() => Verdict::Bot {
+                reason: "honeypot filled in",
+            },
+            _ => Verdict::Human,
+        })
+    }
+}
+
+/// Flags attempts posted too quickly after the form was rendered, or with
+/// a render timestamp that we did not sign.
+pub struct FormTiming {
+    key: Secret<String>,
+    min_age: Duration,
+    max_age: Duration,
+}
+
+impl FormTiming {
+    pub fn new(key: Secret<String>, settings: &BotProtectionSettings) -> Self {
+        Self {
+            key,
+            min_age: Duration::from_secs(settings.min_submit_seconds),
+            max_age: Duration::from_secs(settings.form_token_max_age_seconds),
+        }
+    }
+
+    /// A token recording when the form was rendered, to embed in the form.
+    pub fn sign(&self, rendered_at: SystemTime) -> String {
+        let timestamp = rendered_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let tag = self.mac(timestamp).finalize().into_bytes();
+        format!("{timestamp}.{}", hex::encode(tag))
+    }
+
+    fn mac(&self, timestamp: u128) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.key.expose_secret().as_bytes())
+            .expect("HMAC can take a key of any size");
+        mac.update(format!("subscription-form:{timestamp}").as_bytes());
+        mac
+    }
+
+    fn check(&self, token: Option<&str>, now: SystemTime) -> Verdict {
+        let Some(rendered_at) = token.and_then(|token| self.rendered_at(token)) else {
+            return Verdict::Bot {
+                reason: "missing or invalid form token",
+            };
+        };
+        let age = now.duration_since(rendered_at).unwrap_or_default();
+        if age < self.min_age {
+            Verdict::Bot {
+                reason: "form submitted too quickly",
+            }
+        } else if age > self.max_age {
+            Verdict::Bot {
+                reason: "form token expired",
+            }
+        } else {
+            Verdict::Human
+        }
+    }
+
+    fn rendered_at(&self, token: &str) -> Option<SystemTime> {
+        let (timestamp, tag) = token.split_once('.')?;
+        let timestamp: u128 = timestamp.parse().ok()?;
+        self.mac(timestamp)
+            .verify_slice(&hex::decode(tag).ok()?)
+            .ok()?;
+        UNIX_EPOCH.checked_add(Duration::from_millis(timestamp.try_into().ok()?))
+    }
+}
+
+#[async_trait]
+impl SubmissionVerifier for FormTiming {
+    async fn verify(&self, attempt: &SubscriptionAttempt<'_>) -> Result<Verdict, anyhow::Error> {
+        Ok(self.check(attempt.form_token, SystemTime::now()))
+    }
+}
+
+/// The checks run on every subscription attempt: the honeypot and the form
+/// timing, followed by any verifier added with [`BotProtection::with_verifier`].
+#[derive(Clone)]
+pub struct BotProtection {
+    form_timing: Arc<FormTiming>,
+    verifiers: Vec<Arc<dyn SubmissionVerifier>>,
+}
+
+impl BotProtection {
+    pub fn new(key: Secret<String>, settings: &BotProtectionSettings) -> Self {
+        let form_timing = Arc::new(FormTiming::new(key, settings));
+        Self {
+            verifiers: vec![Arc::new(Honeypot), form_timing.clone()],
+            form_timing,
+        }
+    }
+
+    pub fn with_verifier(mut self, verifier: impl SubmissionVerifier + 'static) -> Self {
+        self.verifiers.push(Arc::new(verifier));
+        self
+    }
+
+    /// A fresh token for a subscription form being rendered.
+    pub fn form_token(&self) -> String {
+        self.form_timing.sign(SystemTime::now())
+    }
+
+    /// The verdict of the first verifier that takes the attempt for a bot.
+    #[tracing::instrument(name = "Check for bots", skip(self))]
+    pub async fn verify(
+        &self,
+        attempt: &SubscriptionAttempt<'_>,
+    ) -> Result<Verdict, anyhow::Error> {
+        for verifier in &self.verifiers {
+            if let verdict @ Verdict::Bot { .. } = verifier.verify(attempt).await? {
+                return Ok(verdict);
+            }
+        }
+        Ok(Verdict::Human)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use async_trait::async_trait;
+    use secrecy::Secret;
+
+    use super::{BotProtection, FormTiming, SubmissionVerifier, SubscriptionAttempt, Verdict};
+    use crate::configuration::BotProtectionSettings;
+
+    fn settings() -> BotProtectionSettings {
+        BotProtectionSettings {
+            min_submit_seconds: 3,
+            form_token_max_age_seconds: 3600,
+        }
+    }
+
+    fn form_timing() -> FormTiming {
+        FormTiming::new(Secret::new("secret".into()), &settings())
+    }
+
+    #[test]
+    fn a_form_posted_after_the_threshold_passes() {
+        let timing = form_timing();
+        let rendered_at = SystemTime::now() - Duration::from_secs(10);
+        let token = timing.sign(rendered_at);
+        assert_eq!(
+            timing.check(Some(&token), SystemTime::now()),
+            Verdict::Human
+        );
+    }
+
+    #[test]
+    fn a_form_posted_too_quickly_is_flagged() {
+        let timing = form_timing();
+        let token = timing.sign(SystemTime::now());
+        assert_eq!(
+            timing.check(Some(&token), SystemTime::now()),
+            Verdict::Bot {
+                reason: "form submitted too quickly"
+            }
+        );
+    }
+
+    #[test]
+    fn an_expired_token_is_flagged() {
+        let timing = form_timing();
+        let token = timing.sign(SystemTime::now() - Duration::from_secs(7200));
+        assert_eq!(
+            timing.check(Some(&token), SystemTime::now()),
+            Verdict::Bot {
+                reason: "form token expired"
+            }
+        );
+    }
+
+    #[test]
+    fn tampered_or_foreign_tokens_are_flagged() {
+        let timing = form_timing();
+        let token = timing.sign(SystemTime::now() - Duration::from_secs(10));
+        let (_, tag) = token.split_once('.').unwrap();
+        let backdated = format!("0.{tag}");
+        let foreign = FormTiming::new(Secret::new("other".into()), &settings())
+            .sign(SystemTime::now() - Duration::from_secs(10));
+
+        for token in [
+            None,
+            Some("garbage"),
+            Some(backdated.as_str()),
+            Some(foreign.as_str()),
+        ] {
+            assert_eq!(
+                timing.check(token, SystemTime::now()),
+                Verdict::Bot {
+                    reason: "missing or invalid form token"
+                }
+            );
+        }
+    }
+
+    /// Stands in for a CAPTCHA provider.
+    struct RejectEverything;
+
+    #[async_trait]
+    impl SubmissionVerifier for RejectEverything {
+        async fn verify(&self, _: &SubscriptionAttempt<'_>) -> Result<Verdict, anyhow::Error> {
+            Ok(Verdict::Bot {
+                reason: "captcha failed",
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn additional_verifiers_are_consulted() {
+        let protection = BotProtection::new(
+            Secret::new("secret".into()),
+            &BotProtectionSettings {
+                min_submit_seconds: 0,
+                ..settings()
+            },
+        );
+        let token = protection.form_token();
+        let attempt = SubscriptionAttempt {
+            ip: "127.0.0.1",
+            honeypot: Some(""),
+            form_token: Some(&token),
+        };
+        assert_eq!(protection.verify(&attempt).await.unwrap(), Verdict::Human);
+
+        let protection = protection.with_verifier(RejectEverything);
+        assert_eq!(
+            protection.verify(&attempt).await.unwrap(),
+            Verdict::Bot {
+                reason: "captcha failed"
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn a_filled_in_honeypot_is_flagged() {
+        let protection = BotProtection::new(Secret::new("secret".into()), &settings());
+        let token = protection.form_token();
+        let attempt = SubscriptionAttempt {
+            ip: "127.0.0.1",
+            honeypot: Some("https://spam.example"),
+            form_token: Some(&token),
+        };
+        assert_eq!(
+            protection.verify(&attempt).await.unwrap(),
+            Verdict::Bot {
+                reason: "honeypot filled in"
+            }
+        );
+    }
+}
@@ -1,4 +1,4 @@
-use std::time::Duration;
+use std::{net::IpAddr, time::Duration};
 
 use secrecy::{ExposeSecret, Secret};
 use serde_aux::prelude::deserialize_number_from_string;
@@ -7,14 +7,32 @@ use sqlx::{
     ConnectOptions,
 };
 
-use crate::domain::SubscriberEmail;
+use crate::{
+    authentication::PasswordPolicy,
+    domain::{Locale, SubscriberEmail},
+    email_client::EmailClient,
+};
 
 #[derive(serde::Deserialize, Clone)]
 pub struct Settings {
     pub database: DatabaseSettings,
     pub application_settings: ApplicationSettings,
     pub email_configuration: EmailConfiguration,
+    pub email_webhooks: EmailWebhookSettings,
     pub redis_url: Secret<String>,
+    pub login_throttle: LoginThrottleSettings,
+    pub subscribe_rate_limit: SubscribeRateLimitSettings,
+    pub bot_protection: BotProtectionSettings,
+    pub email_domains: EmailDomainSettings,
+    pub password_policy: PasswordPolicy,
+    pub password_hashing: PasswordHashingSettings,
+    pub metrics: MetricsSettings,
+    pub readiness: ReadinessSettings,
+    pub shutdown: ShutdownSettings,
+    pub telemetry: TelemetrySettings,
+    pub tracking: TrackingSettings,
+    pub sequences: SequenceSettings,
+    pub localization: LocalizationSettings,
 }
 
 #[derive(serde::Deserialize, Clone)]
@@ -24,6 +42,10 @@ pub struct ApplicationSettings {
     pub host: String,
     pub base_url: String,
     pub hmac_secret: Secret<String>,
+    /// Reverse proxies whose `Forwarded`/`X-Forwarded-For` headers are
+    /// believed. Anyone else could forge them to dodge per-IP limits.
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpAddr>,
 }
 
 #[derive(serde::Deserialize, Clone)]
@@ -75,6 +97,202 @@ impl EmailConfiguration {
     pub fn timeout(&self) -> Duration {
         Duration::from_millis(self.timeout_milliseconds)
     }
+
+    pub fn client(&self) -> Result<EmailClient, String> {
+        Ok(EmailClient::new(
+            self.base_url.clone(),
+            self.sender()?,
+            self.authorization_token.clone(),
+            self.timeout(),
+        ))
+    }
+}
+
+/// Authentication of the email provider's event webhooks. The endpoint is
+/// disabled unless credentials or a shared secret are set.
+#[derive(serde::Deserialize, Clone)]
+pub struct EmailWebhookSettings {
+    pub basic_auth: Option<WebhookCredentials>,
+    /// Sent by the provider in the `X-Webhook-Secret` header.
+    pub shared_secret: Option<Secret<String>>,
+    /// Soft bounces in a row after which an address counts as bounced.
+    pub soft_bounce_threshold: u32,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct WebhookCredentials {
+    pub username: String,
+    pub password: Secret<String>,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct LoginThrottleSettings {
+    pub max_attempts_per_username: u64,
+    pub max_attempts_per_ip: u64,
+    pub attempt_window_seconds: u64,
+    pub lockout_seconds: u64,
+    pub base_delay_milliseconds: u64,
+    pub max_delay_milliseconds: u64,
+}
+
+impl LoginThrottleSettings {
+    /// Exponential backoff based on the number of recent failures, capped at `max_delay_milliseconds`.
+    pub fn delay(&self, failures: u64) -> Duration {
+        if failures == 0 {
+            return Duration::ZERO;
+        }
+        let exponent = (failures - 1).min(16) as u32;
+        let delay = self
+            .base_delay_milliseconds
+            .saturating_mul(2u64.pow(exponent));
+        Duration::from_millis(delay.min(self.max_delay_milliseconds))
+    }
+}
+
+/// Limits on `POST /subscriptions`, which sends an email on every call.
+#[derive(serde::Deserialize, Clone)]
+pub struct SubscribeRateLimitSettings {
+    pub per_ip: TokenBucketSettings,
+    pub per_email: TokenBucketSettings,
+    /// Confirmation emails sent per UTC day, across all clients.
+    pub daily_confirmation_emails: u32,
+}
+
+/// A bucket holding up to `capacity` tokens, refilled with one token every
+/// `refill_interval_seconds`. Every request takes a token.
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct TokenBucketSettings {
+    pub capacity: u32,
+    pub refill_interval_seconds: u64,
+}
+
+/// Checks telling people from scripts on the public subscription form.
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct BotProtectionSettings {
+    /// Submissions posted sooner than this after the form was rendered are
+    /// assumed to come from a script.
+    pub min_submit_seconds: u64,
+    pub form_token_max_age_seconds: u64,
+}
+
+/// Files listing email domains, one per line, that subscribers may or may
+/// not sign up with. Paths are relative to the working directory.
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct EmailDomainSettings {
+    /// Domains refused, such as disposable email providers.
+    pub blocklist_path: Option<String>,
+    /// When set, only these domains are accepted.
+    pub allowlist_path: Option<String>,
+}
+
+/// Argon2id cost parameters for newly computed password hashes.
+///
+/// Raising them is safe: existing hashes are upgraded the next time their
+/// owner logs in.
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct PasswordHashingSettings {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl PasswordHashingSettings {
+    pub fn params(&self) -> Result<argon2::Params, argon2::Error> {
+        argon2::Params::new(self.memory_kib, self.iterations, self.parallelism, None)
+    }
+}
+
+/// Access to the `/metrics` endpoint.
+#[derive(serde::Deserialize, Clone)]
+pub struct MetricsSettings {
+    /// Off by default: the endpoint answers `404 Not Found`.
+    pub enabled: bool,
+    /// When set, scrapers must send it as a bearer token.
+    pub bearer_token: Option<Secret<String>>,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct ReadinessSettings {
+    /// How long each dependency has to answer.
+    pub timeout_milliseconds: u64,
+    /// The email provider is not critical: when checked, an outage is
+    /// reported without failing the probe.
+    pub check_email_provider: bool,
+}
+
+impl ReadinessSettings {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.timeout_milliseconds)
+    }
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct ShutdownSettings {
+    /// How long in-flight requests, then background tasks, get to finish
+    /// once shutdown starts.
+    pub timeout_seconds: u64,
+}
+
+impl ShutdownSettings {
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_seconds)
+    }
+}
+
+/// OpenTelemetry trace export. Nothing is exported without an endpoint.
+#[derive(serde::Deserialize, Clone)]
+pub struct TelemetrySettings {
+    /// Base URL of an OTLP/HTTP collector, e.g. `http://localhost:4318`.
+    pub otlp_endpoint: Option<String>,
+    pub service_name: String,
+    /// Share of new traces that get exported, between 0 and 1. Traces
+    /// started by a caller follow the caller's sampling decision.
+    pub sampling_ratio: f64,
+}
+
+/// Open and click tracking of newsletter issues.
+#[derive(serde::Deserialize, Clone)]
+pub struct TrackingSettings {
+    /// When off, issues cannot ask for tracking and nothing is recorded.
+    pub enabled: bool,
+}
+
+/// Delivery of the steps of automated email sequences.
+#[derive(serde::Deserialize, Clone)]
+pub struct SequenceSettings {
+    /// When off, due steps wait until it is turned back on.
+    pub enabled: bool,
+    pub poll_interval_seconds: u64,
+}
+
+impl SequenceSettings {
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_secs(self.poll_interval_seconds)
+    }
+}
+
+/// Which language subscribers get emails and pages in.
+#[derive(serde::Deserialize, Clone)]
+pub struct LocalizationSettings {
+    /// For people whose language we cannot tell or do not support.
+    pub default_locale: Locale,
+}
+
+impl LocalizationSettings {
+    /// The locale asked for explicitly, else the one the browser prefers,
+    /// else the default.
+    pub fn negotiate(&self, requested: Option<&str>, accept_language: Option<&str>) -> Locale {
+        requested
+            .and_then(Locale::parse)
+            .or_else(|| accept_language.and_then(Locale::from_accept_language))
+            .unwrap_or(self.default_locale)
+    }
+
+    /// The locale stored on a subscription, or the default if it is no
+    /// longer supported.
+    pub fn stored(&self, code: &str) -> Locale {
+        Locale::parse(code).unwrap_or(self.default_locale)
+    }
 }
 
 pub fn get_configuration() -> Result<Settings, config::ConfigError> {
@@ -0,0 +1,248 @@
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::domain::Locale;
+
+/// Replaced with the link that confirms the subscription. Both bodies must
+/// contain it.
+pub const CONFIRMATION_LINK: &str = "{{ confirmation_link }}";
+/// Replaced with the name of the list subscribed to.
+pub const LIST_NAME: &str = "{{ list_name }}";
+
+/// The confirmation email with its placeholders still in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfirmationEmail {
+    pub subject: String,
+    pub html_content: String,
+    pub text_content: String,
+}
+
+/// A confirmation email ready to be sent.
+pub struct RenderedEmail {
+    pub subject: String,
+    pub html_content: String,
+    pub text_content: String,
+}
+
+impl ConfirmationEmail {
+    /// The copy sent when no version was saved for `locale`.
+    pub fn built_in(locale: Locale) -> Self {
+        Self {
+            subject: locale.confirmation_subject(LIST_NAME),
+            html_content: locale.confirmation_html(LIST_NAME, CONFIRMATION_LINK),
+            text_content: locale.confirmation_text(LIST_NAME, CONFIRMATION_LINK),
+        }
+    }
+
+    /// Surrounding whitespace is dropped. Emails without a subject or
+    /// without the link in either body are rejected.
+    pub fn parse(subject: &str, html_content: &str, text_content: &str) -> Result<Self, String> {
+        let subject = subject.trim();
+        let html_content = html_content.trim();
+        let text_content = text_content.trim();
+        if subject.is_empty() {
+            return Err("The confirmation email needs a subject.".into());
+        }
+        if !html_content.contains(CONFIRMATION_LINK) {
+            return Err(format!("The HTML body must contain {CONFIRMATION_LINK}."));
+        }
+        if !text_content.contains(CONFIRMATION_LINK) {
+            return Err(format!("The text body must contain {CONFIRMATION_LINK}."));
+        }
+        Ok(Self {
+            subject: subject.into(),
+            html_content: html_content.into(),
+            text_content: text_content.into(),
+        })
+    }
+
+    /// Fill in the placeholders. The list name is escaped in the HTML body.
+    pub fn render(&self, list_name: &str, confirmation_link: &str) -> RenderedEmail {
+        let fill = |content: &str, list_name: &str| {
+            content
+                .replace(LIST_NAME, list_name)
+                .replace(CONFIRMATION_LINK, confirmation_link)
+        };
+        RenderedEmail {
+            subject: fill(&self.subject, list_name),
+            html_content: fill(&self.html_content, &htmlescape::encode_minimal(list_name)),
+            text_content: fill(&self.text_content, list_name),
+        }
+    }
+}
+
+/// A saved version of the confirmation email of a locale.
+pub struct TemplateVersion {
+    pub version: i32,
+    pub subject: String,
+    pub created_at: DateTime<Utc>,
+    /// `None` once the user who saved it was deleted.
+    pub created_by: Option<String>,
+}
+
+/// The latest version saved for `locale`, if any.
+#[tracing::instrument(name = "Get confirmation email", skip(pool))]
+pub async fn current_template(
+    pool: &PgPool,
+    locale: Locale,
+) -> Result<Option<ConfirmationEmail>, anyhow::Error> {
+    let template = sqlx::query_as!(
+        ConfirmationEmail,
+        r#"
+        SELECT subject, html_content, text_content
+        FROM confirmation_email_templates
+        WHERE locale = $1
+        ORDER BY version DESC
+        LIMIT 1
+        "#,
+        locale.code(),
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch the confirmation email")?;
+    Ok(template)
+}
+
+/// The confirmation email sent to subscribers speaking `locale`.
+pub async fn template_for(
+    pool: &PgPool,
+    locale: Locale,
+) -> Result<ConfirmationEmail, anyhow::Error> {
+    Ok(current_template(pool, locale)
+        .await?
+        .unwrap_or_else(|| ConfirmationEmail::built_in(locale)))
+}
+
+#[tracing::instrument(name = "Get confirmation email version", skip(pool))]
+pub async fn template_version(
+    pool: &PgPool,
+    locale: Locale,
+    version: i32,
+) -> Result<Option<ConfirmationEmail>, anyhow::Error> {
+    let template = sqlx::query_as!(
+        ConfirmationEmail,
+        r#"
+        SELECT subject, html_content, text_content
+        FROM confirmation_email_templates
+        WHERE locale = $1 AND version = $2
+        "#,
+        locale.code(),
+        version,
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch a confirmation email version")?;
+    Ok(template)
+}
+
+/// Every version saved for `locale`, latest first.
+#[tracing::instrument(name = "List confirmation email versions", skip(pool))]
+pub async fn template_versions(
+    pool: &PgPool,
+    locale: Locale,
+) -> Result<Vec<TemplateVersion>, anyhow::Error> {
+    let versions = sqlx::query_as!(
+        TemplateVersion,
+        r#"
+        SELECT t.version, t.subject, t.created_at, u.name AS "created_by?"
+        FROM confirmation_email_templates t
+        LEFT JOIN users u ON u.user_id = t.created_by
+        WHERE t.locale = $1
+        ORDER BY t.version DESC
+        "#,
+        locale.code(),
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to list confirmation email versions")?;
+    Ok(versions)
+}
+
+/// Store `template` as the new current version for `locale` and return its
+/// number.
+#[tracing::instrument(name = "Save confirmation email", skip(pool, template))]
+pub async fn save_template(
+    pool: &PgPool,
+    locale: Locale,
+    template: &ConfirmationEmail,
+    user_id: Uuid,
+) -> Result<i32, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        INSERT INTO confirmation_email_templates (
+            locale, version, subject, html_content, text_content, created_at, created_by
+        )
+        SELECT $1, COALESCE(MAX(version), 0) + 1, $2, $3, $4, $5, $6
+        FROM confirmation_email_templates
+        WHERE locale = $1
+        RETURNING version
+        "#,
+        locale.code(),
+        template.subject,
+        template.html_content,
+        template.text_content,
+        Utc::now(),
+        user_id,
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to save the confirmation email")?;
+    Ok(row.version)
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok};
+
+    use super::ConfirmationEmail;
+    use crate::domain::Locale;
+
+    #[test]
+    fn the_built_in_emails_are_valid() {
+        for locale in Locale::ALL {
+            let built_in = ConfirmationEmail::built_in(locale);
+            assert_ok!(ConfirmationEmail::parse(
+                &built_in.subject,
+                &built_in.html_content,
+                &built_in.text_content
+            ));
+        }
+    }
+
+    #[test]
+    fn both_bodies_need_the_confirmation_link() {
+        let link = "{{ confirmation_link }}";
+        assert_err!(ConfirmationEmail::parse(" ", link, link));
+        assert_err!(ConfirmationEmail::parse(
+            "Hi",
+            "{{confirmation_link}}",
+            link
+        ));
+        assert_err!(ConfirmationEmail::parse("Hi", link, "no link"));
+        assert_ok!(ConfirmationEmail::parse("Hi", link, link));
+    }
+
+    #[test]
+    fn the_list_name_is_escaped_in_html_only() {
+        let email = ConfirmationEmail::parse(
+            "Join {{ list_name }}",
+            "<p>{{ list_name }}: {{ confirmation_link }}</p>",
+            "{{ list_name }}: {{ confirmation_link }}",
+        )
+        .unwrap();
+
+        let rendered = email.render("Cats & Dogs", "https://example.com/confirm");
+
+        assert_eq!(rendered.subject, "Join Cats & Dogs");
+        assert_eq!(
+            rendered.html_content,
+            "<p>Cats &amp; Dogs: https://example.com/confirm</p>"
+        );
+        assert_eq!(
+            rendered.text_content,
+            "Cats & Dogs: https://example.com/confirm"
+        );
+    }
+}
@@ -0,0 +1,135 @@
+use std::collections::HashSet;
+
+use anyhow::Context;
+
+use crate::configuration::EmailDomainSettings;
+use crate::domain::SubscriberEmail;
+
+/// Which email domains may be used to subscribe. A listed domain also
+/// covers its subdomains.
+#[derive(Debug, Default, Clone)]
+pub struct EmailDomainPolicy {
+    blocked: HashSet<String>,
+    /// When set, every other domain is refused.
+    allowed: Option<HashSet<String>>,
+}
+
+impl EmailDomainPolicy {
+    pub fn new(blocked: HashSet<String>, allowed: Option<HashSet<String>>) -> Self {
+        Self { blocked, allowed }
+    }
+
+    pub fn from_settings(settings: &EmailDomainSettings) -> Result<Self, anyhow::Error> {
+        let blocked = match &settings.blocklist_path {
+            Some(path) => read_domains(path)?,
+            None => HashSet::new(),
+        };
+        let allowed = settings
+            .allowlist_path
+            .as_deref()
+            .map(read_domains)
+            .transpose()?;
+        Ok(Self::new(blocked, allowed))
+    }
+
+    pub fn check(&self, email: &SubscriberEmail) -> Result<(), String> {
+        let domain = email.domain();
+        let listed_in = |domains: &HashSet<String>| parents(domain).any(|d| domains.contains(d));
+        let allowed = match &self.allowed {
+            Some(allowed) => listed_in(allowed),
+            None => true,
+        };
+        if allowed && !listed_in(&self.blocked) {
+            Ok(())
+        } else {
+            Err(format!(
+                "Email addresses at {domain} are not accepted. Please use another address."
+            ))
+        }
+    }
+}
+
+/// `domain` followed by each of its parent domains.
+fn parents(domain: &str) -> impl Iterator<Item = &str> {
+    std::iter::successors(Some(domain), |d| {
+        d.split_once('.').map(|(_, parent)| parent)
+    })
+}
+
+/// One domain per line. Blank lines and lines starting with `#` are skipped.
+fn read_domains(path: &str) -> Result<HashSet<String>, anyhow::Error> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read the email domain list at {path}"))?;
+    parse_domains(&contents).with_context(|| format!("Invalid email domain list at {path}"))
+}
+
+fn parse_domains(contents: &str) -> Result<HashSet<String>, anyhow::Error> {
+    contents
+        .lines()
+        .enumerate()
+        .map(|(number, line)| (number + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(number, line)| {
+            idna::domain_to_ascii_strict(line)
+                .ok()
+                .filter(|domain| !domain.is_empty())
+                .with_context(|| format!("Line {number}: {line} is not a valid domain"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use claims::{assert_err, assert_ok};
+
+    use super::{parse_domains, EmailDomainPolicy};
+    use crate::domain::SubscriberEmail;
+
+    fn email(address: &str) -> SubscriberEmail {
+        SubscriberEmail::parse(address.to_string()).unwrap()
+    }
+
+    fn domains(list: &str) -> HashSet<String> {
+        parse_domains(list).unwrap()
+    }
+
+    #[test]
+    fn lists_skip_comments_and_are_canonicalized() {
+        let list = "# Disposable\n\nMailinator.com\n  bücher.example  \n";
+        assert_eq!(
+            domains(list),
+            HashSet::from(["mailinator.com".into(), "xn--bcher-kva.example".into()])
+        );
+        assert_err!(parse_domains("mailinator.com\nnot a domain\n"));
+    }
+
+    #[test]
+    fn blocked_domains_and_their_subdomains_are_refused() {
+        let policy = EmailDomainPolicy::new(domains("mailinator.com"), None);
+        assert_err!(policy.check(&email("ursula@mailinator.com")));
+        assert_err!(policy.check(&email("ursula@eu.Mailinator.com")));
+        assert_ok!(policy.check(&email("ursula@notmailinator.com")));
+        assert_ok!(policy.check(&email("ursula@example.com")));
+    }
+
+    #[test]
+    fn an_allowlist_refuses_every_other_domain() {
+        let policy =
+            EmailDomainPolicy::new(domains("mail.example.com"), Some(domains("example.com")));
+        assert_ok!(policy.check(&email("ursula@example.com")));
+        assert_ok!(policy.check(&email("ursula@staff.example.com")));
+        assert_err!(policy.check(&email("ursula@mail.example.com")));
+        assert_err!(policy.check(&email("ursula@example.org")));
+    }
+
+    #[test]
+    fn the_error_names_the_domain() {
+        let policy = EmailDomainPolicy::new(domains("mailinator.com"), None);
+        assert_eq!(
+            policy.check(&email("ursula@mailinator.com")).unwrap_err(),
+            "Email addresses at mailinator.com are not accepted. Please use another address."
+        );
+    }
+}
@@ -0,0 +1,68 @@
+/// A short identifier chosen by people, such as a tag or the source of a
+/// subscription: lowercase letters, digits, `-` and `_`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label(String);
+
+const MAX_LENGTH: usize = 50;
+
+impl Label {
+    /// Surrounding whitespace is dropped and letters are lowercased, so that
+    /// `VIP` and `vip ` are the same label.
+    pub fn parse(input: &str) -> Result<Label, String> {
+        let label = input.trim().to_lowercase();
+        let valid = !label.is_empty()
+            && label.len() <= MAX_LENGTH
+            && label
+                .bytes()
+                .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-' || b == b'_');
+        if valid {
+            Ok(Self(label))
+        } else {
+            Err(format!(
+                "{:?} is not a valid label: use up to {MAX_LENGTH} letters, digits, '-' or '_'.",
+                input.trim()
+            ))
+        }
+    }
+}
+
+impl AsRef<str> for Label {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for Label {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::assert_err;
+
+    use super::Label;
+
+    #[test]
+    fn labels_are_trimmed_and_lowercased() {
+        assert_eq!(
+            Label::parse("  Early-Adopters_2026 ").unwrap().as_ref(),
+            "early-adopters_2026"
+        );
+    }
+
+    #[test]
+    fn invalid_labels_are_rejected() {
+        for input in [
+            "",
+            "   ",
+            "two words",
+            "<script>",
+            "émigré",
+            &"a".repeat(51),
+        ] {
+            assert_err!(Label::parse(input));
+        }
+    }
+}
@@ -0,0 +1,92 @@
+/// A language we have translations for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(try_from = "String")]
+pub enum Locale {
+    English,
+    French,
+    German,
+}
+
+impl Locale {
+    pub const ALL: [Locale; 3] = [Locale::English, Locale::French, Locale::German];
+
+    /// The language tag stored on subscriptions and used in `lang` attributes.
+    pub fn code(self) -> &'static str {
+        match self {
+            Locale::English => "en",
+            Locale::French => "fr",
+            Locale::German => "de",
+        }
+    }
+
+    /// A language tag such as `fr` or `fr-CA`; only the language counts.
+    pub fn parse(tag: &str) -> Option<Locale> {
+        let language = tag.trim().split(['-', '_']).next()?;
+        Locale::ALL
+            .into_iter()
+            .find(|locale| locale.code().eq_ignore_ascii_case(language))
+    }
+
+    /// The supported language a browser prefers most, from an
+    /// `Accept-Language` header such as `de-CH, fr;q=0.8, en;q=0.5`.
+    pub fn from_accept_language(header: &str) -> Option<Locale> {
+        let mut best: Option<(f32, Locale)> = None;
+        for range in header.split(',') {
+            let mut parts = range.split(';');
+            let Some(locale) = parts.next().and_then(Locale::parse) else {
+                continue;
+            };
+            let quality = parts
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.trim().parse::<f32>().ok())
+                .unwrap_or(0.0);
+            // Ties go to the language listed first.
+            if quality > 0.0 && !matches!(best, Some((q, _)) if quality <= q) {
+                best = Some((quality, locale));
+            }
+        }
+        best.map(|(_, locale)| locale)
+    }
+}
+
+impl TryFrom<String> for Locale {
+    type Error = String;
+
+    fn try_from(tag: String) -> Result<Self, Self::Error> {
+        Locale::parse(&tag).ok_or_else(|| format!("{tag:?} is not a supported locale."))
+    }
+}
+
+impl std::fmt::Display for Locale {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.code())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Locale;
+
+    #[test]
+    fn only_the_language_of_a_tag_counts() {
+        assert_eq!(Locale::parse("fr"), Some(Locale::French));
+        assert_eq!(Locale::parse(" FR-ca"), Some(Locale::French));
+        assert_eq!(Locale::parse("de_AT"), Some(Locale::German));
+        assert_eq!(Locale::parse("es"), None);
+        assert_eq!(Locale::parse(""), None);
+    }
+
+    #[test]
+    fn the_most_preferred_supported_language_wins() {
+        for (header, expected) in [
+            ("de-CH, fr;q=0.8, en;q=0.5", Some(Locale::German)),
+            ("es, fr;q=0.8, en;q=0.9", Some(Locale::English)),
+            ("en;q=0.5, fr;q=0.5", Some(Locale::English)),
+            ("fr;q=0, en;q=0.1", Some(Locale::English)),
+            ("es, it", None),
+            ("", None),
+        ] {
+            assert_eq!(Locale::from_accept_language(header), expected, "{header}");
+        }
+    }
+}
@@ -1,7 +1,13 @@
+mod email_domain_policy;
+mod label;
+mod locale;
 mod new_subscriber;
 mod subscriber_email;
 mod subscriber_name;
 
+pub use email_domain_policy::EmailDomainPolicy;
+pub use label::Label;
+pub use locale::Locale;
 pub use new_subscriber::NewSubscriber;
 pub use subscriber_email::SubscriberEmail;
 pub use subscriber_name::SubscriberName;
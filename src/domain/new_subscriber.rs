@@ -1,6 +1,8 @@
-use super::{SubscriberEmail, SubscriberName};
+use super::{Label, SubscriberEmail, SubscriberName};
 
 pub struct NewSubscriber {
     pub email: SubscriberEmail,
     pub name: SubscriberName,
+    /// Where they signed up from, when known.
+    pub source: Option<Label>,
 }
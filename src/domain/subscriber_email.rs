@@ -2,17 +2,37 @@ use std::fmt::Formatter;
 
 use validator::validate_email;
 
+/// An email address in canonical form: trimmed, with the domain lowercased
+/// and internationalized domains converted to punycode.
+///
+/// The local part is kept as typed, since mail servers may treat it as case
+/// sensitive; uniqueness ignores its case in the database instead.
 #[derive(Debug, Clone)]
 pub struct SubscriberEmail(String);
 
 impl SubscriberEmail {
     pub fn parse(input: String) -> Result<SubscriberEmail, String> {
-        if validate_email(&input) {
-            Ok(Self(input))
+        let invalid = || format!("{} is not a valid email address.", input.trim());
+        let (local_part, domain) = input.trim().rsplit_once('@').ok_or_else(invalid)?;
+        let domain = idna::domain_to_ascii_strict(domain).map_err(|_| invalid())?;
+        if local_part.is_empty() || domain.is_empty() {
+            return Err(invalid());
+        }
+        let email = format!("{local_part}@{domain}");
+        if validate_email(&email) {
+            Ok(Self(email))
         } else {
-            Err(format!("{input} is not a valid email!"))
+            Err(invalid())
         }
     }
+
+    /// The part after the `@`, in ASCII.
+    pub fn domain(&self) -> &str {
+        self.0
+            .rsplit_once('@')
+            .map(|(_, domain)| domain)
+            .unwrap_or_default()
+    }
 }
 
 impl AsRef<str> for SubscriberEmail {
@@ -62,6 +82,32 @@ mod tests {
         assert_err!(SubscriberEmail::parse(input));
     }
 
+    #[test]
+    fn surrounding_whitespace_is_trimmed() {
+        let email = SubscriberEmail::parse("  ursula@example.com\n".to_string()).unwrap();
+        assert_eq!(email.as_ref(), "ursula@example.com");
+    }
+
+    #[test]
+    fn the_domain_is_lowercased_but_not_the_local_part() {
+        let email = SubscriberEmail::parse("Ursula.Le.Guin@Example.COM".to_string()).unwrap();
+        assert_eq!(email.as_ref(), "Ursula.Le.Guin@example.com");
+        assert_eq!(email.domain(), "example.com");
+    }
+
+    #[test]
+    fn internationalized_domains_are_converted_to_punycode() {
+        let email = SubscriberEmail::parse("ursula@Bücher.example".to_string()).unwrap();
+        assert_eq!(email.as_ref(), "ursula@xn--bcher-kva.example");
+    }
+
+    #[test]
+    fn invalid_domains_are_rejected() {
+        for input in ["ursula@", "ursula@exa mple.com", "ursula@-.com"] {
+            assert_err!(SubscriberEmail::parse(input.to_string()));
+        }
+    }
+
     #[quickcheck_macros::quickcheck]
     fn valid_emails_are_parsed_successfully(valid_email: ValidEmailFixture) -> bool {
         SubscriberEmail::parse(valid_email.0).is_ok()
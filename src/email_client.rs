@@ -1,9 +1,13 @@
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use reqwest::Client;
 use secrecy::{ExposeSecret, Secret};
 
-use crate::domain::SubscriberEmail;
+use crate::{
+    domain::SubscriberEmail,
+    metrics::{metrics, EmailOutcome},
+    telemetry::trace_context_headers,
+};
 
 #[derive(Clone)]
 pub struct EmailClient {
@@ -29,6 +33,11 @@ impl EmailClient {
         }
     }
 
+    #[tracing::instrument(
+        name = "Send email",
+        skip(self, recipient, subject, html_content, text_content),
+        fields(otel.kind = "client")
+    )]
     pub async fn send_email(
         &self,
         recipient: &SubscriberEmail,
@@ -44,16 +53,28 @@ impl EmailClient {
             html_body: html_content,
             text_body: text_content,
         };
-        self.http_client
+        let request = self
+            .http_client
             .post(&url)
             .header(
                 "X-Postmark-Server-Token",
                 self.authorization_token.expose_secret(),
             )
-            .json(&request_body)
+            .headers(trace_context_headers())
+            .json(&request_body);
+        let started_at = Instant::now();
+        let outcome = request
             .send()
-            .await?
-            .error_for_status()?;
+            .await
+            .and_then(|response| response.error_for_status());
+        metrics().observe_email_send(EmailOutcome::of(&outcome), started_at.elapsed());
+        outcome?;
+        Ok(())
+    }
+
+    /// Check that the provider can be reached. Any HTTP response will do.
+    pub async fn ping(&self) -> Result<(), reqwest::Error> {
+        self.http_client.get(&self.base_url).send().await?;
         Ok(())
     }
 }
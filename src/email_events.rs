@@ -0,0 +1,276 @@
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::PgPool;
+
+/// What the email provider tells us happened to a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmailEventKind {
+    /// The address does not exist or refuses our mail for good.
+    HardBounce,
+    /// A temporary failure, such as a full mailbox.
+    SoftBounce,
+    SpamComplaint,
+    Delivery,
+}
+
+impl EmailEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EmailEventKind::HardBounce => "hard_bounce",
+            EmailEventKind::SoftBounce => "soft_bounce",
+            EmailEventKind::SpamComplaint => "spam_complaint",
+            EmailEventKind::Delivery => "delivery",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct EmailEvent {
+    /// Unique per event, so that redelivered webhooks are only applied once.
+    pub key: String,
+    pub kind: EmailEventKind,
+    pub email: String,
+}
+
+/// The webhook payloads sent by Postmark, reduced to the fields we use.
+#[derive(serde::Deserialize)]
+#[serde(tag = "RecordType", rename_all_fields = "PascalCase")]
+enum PostmarkEvent {
+    Bounce {
+        #[serde(rename = "ID")]
+        id: i64,
+        #[serde(rename = "Type")]
+        bounce_type: String,
+        email: String,
+    },
+    SpamComplaint {
+        #[serde(rename = "ID")]
+        id: i64,
+        email: String,
+    },
+    Delivery {
+        #[serde(rename = "MessageID")]
+        message_id: String,
+        recipient: String,
+    },
+    /// Opens, clicks and subscription changes.
+    #[serde(other)]
+    Other,
+}
+
+impl EmailEvent {
+    /// `Ok(None)` for events that do not affect subscribers.
+    pub fn from_postmark(payload: &[u8]) -> Result<Option<Self>, serde_json::Error> {
+        let event = match serde_json::from_slice(payload)? {
+            PostmarkEvent::Bounce {
+                id,
+                bounce_type,
+                email,
+            } => {
+                let kind = match bounce_type.as_str() {
+                    "HardBounce" | "BadEmailAddress" | "ManuallyDeactivated" => {
+                        EmailEventKind::HardBounce
+                    }
+                    "SoftBounce" | "Transient" | "DnsError" => EmailEventKind::SoftBounce,
+                    // Auto-responders, challenge-response systems and the like.
+                    _ => return Ok(None),
+                };
+                Self {
+                    key: format!("bounce:{id}"),
+                    kind,
+                    email,
+                }
+            }
+            PostmarkEvent::SpamComplaint { id, email } => Self {
+                key: format!("spam_complaint:{id}"),
+                kind: EmailEventKind::SpamComplaint,
+                email,
+            },
+            PostmarkEvent::Delivery {
+                message_id,
+                recipient,
+            } => Self {
+                key: format!("delivery:{message_id}"),
+                kind: EmailEventKind::Delivery,
+                email: recipient,
+            },
+            PostmarkEvent::Other => return Ok(None),
+        };
+        Ok(Some(event))
+    }
+}
+
+/// Record `event` and update the subscriber it is about:
+/// - a hard bounce marks them `bounced`;
+/// - a spam complaint marks them `complained`;
+/// - soft bounces are counted, and mark them `bounced` once there have
+///   been `soft_bounce_threshold` of them in a row;
+/// - bounces only change the status of confirmed subscriptions, so that
+///   an unsubscribe is never undone;
+/// - a delivery resets that count.
+///
+/// Returns `false` when the event had been applied already.
+#[tracing::instrument(name = "Apply email event", skip(pool, payload))]
+pub async fn apply_email_event(
+    pool: &PgPool,
+    event: &EmailEvent,
+    payload: serde_json::Value,
+    soft_bounce_threshold: u32,
+) -> Result<bool, anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection")?;
+    let inserted = sqlx::query!(
+        r#"
+        INSERT INTO email_events (event_key, kind, email, payload, received_at)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (event_key) DO NOTHING
+        "#,
+        event.key,
+        event.kind.as_str(),
+        event.email,
+        payload,
+        Utc::now(),
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to record email event")?
+    .rows_affected();
+    if inserted == 0 {
+        return Ok(false);
+    }
+
+    match event.kind {
+        EmailEventKind::HardBounce => {
+            sqlx::query!(
+                r#"
+                UPDATE subscriptions SET status = 'bounced'
+                WHERE lower(email) = lower($1) AND status = 'confirmed'
+                "#,
+                event.email,
+            )
+            .execute(&mut transaction)
+            .await
+        }
+        EmailEventKind::SpamComplaint => {
+            sqlx::query!(
+                r#"
+                UPDATE subscriptions SET status = 'complained'
+                WHERE lower(email) = lower($1)
+                "#,
+                event.email,
+            )
+            .execute(&mut transaction)
+            .await
+        }
+        EmailEventKind::SoftBounce => {
+            sqlx::query!(
+                r#"
+                UPDATE subscriptions
+                SET
+                    soft_bounces = soft_bounces + 1,
+                    status = CASE
+                        WHEN soft_bounces + 1 >= $2 AND status = 'confirmed' THEN 'bounced'
+                        ELSE status
+                    END
+                WHERE lower(email) = lower($1)
+                "#,
+                event.email,
+                soft_bounce_threshold as i32,
+            )
+            .execute(&mut transaction)
+            .await
+        }
+        EmailEventKind::Delivery => {
+            sqlx::query!(
+                r#"UPDATE subscriptions SET soft_bounces = 0 WHERE lower(email) = lower($1)"#,
+                event.email,
+            )
+            .execute(&mut transaction)
+            .await
+        }
+    }
+    .context("Failed to update the subscriber")?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction")?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EmailEvent, EmailEventKind};
+
+    fn parse(payload: serde_json::Value) -> Option<EmailEvent> {
+        EmailEvent::from_postmark(payload.to_string().as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn bounces_are_classified_by_type() {
+        let bounce = |bounce_type| {
+            parse(serde_json::json!({
+                "RecordType": "Bounce",
+                "ID": 42,
+                "Type": bounce_type,
+                "TypeCode": 1,
+                "Email": "ursula@example.com",
+            }))
+            .map(|event| event.kind)
+        };
+        assert_eq!(bounce("HardBounce"), Some(EmailEventKind::HardBounce));
+        assert_eq!(bounce("BadEmailAddress"), Some(EmailEventKind::HardBounce));
+        assert_eq!(bounce("SoftBounce"), Some(EmailEventKind::SoftBounce));
+        assert_eq!(bounce("Transient"), Some(EmailEventKind::SoftBounce));
+        assert_eq!(bounce("AutoResponder"), None);
+    }
+
+    #[test]
+    fn complaints_and_deliveries_are_parsed() {
+        assert_eq!(
+            parse(serde_json::json!({
+                "RecordType": "SpamComplaint",
+                "ID": 7,
+                "Email": "ursula@example.com",
+            })),
+            Some(EmailEvent {
+                key: "spam_complaint:7".into(),
+                kind: EmailEventKind::SpamComplaint,
+                email: "ursula@example.com".into(),
+            })
+        );
+        assert_eq!(
+            parse(serde_json::json!({
+                "RecordType": "Delivery",
+                "MessageID": "883953f4-6105-42a2-a16a-77a8eac79483",
+                "Recipient": "ursula@example.com",
+            })),
+            Some(EmailEvent {
+                key: "delivery:883953f4-6105-42a2-a16a-77a8eac79483".into(),
+                kind: EmailEventKind::Delivery,
+                email: "ursula@example.com".into(),
+            })
+        );
+    }
+
+    #[test]
+    fn other_record_types_are_ignored() {
+        assert_eq!(
+            parse(serde_json::json!({ "RecordType": "Open", "Recipient": "ursula@example.com" })),
+            None
+        );
+    }
+
+    #[test]
+    fn malformed_payloads_are_rejected() {
+        for payload in [
+            "not json",
+            r#"{"RecordType": "Bounce", "ID": 1}"#,
+            r#"{"Email": "ursula@example.com"}"#,
+        ] {
+            assert!(EmailEvent::from_postmark(payload.as_bytes()).is_err());
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use actix_web::{
+    body::{BodySize, BoxBody, MessageBody},
+    dev::{ServiceRequest, ServiceResponse},
+    error::InternalError,
+    http::{
+        header::{HeaderMap, HeaderValue, ACCEPT, CONTENT_TYPE},
+        StatusCode,
+    },
+    HttpResponse, ResponseError,
+};
+use actix_web_lab::middleware::Next;
+use askama::Template;
+
+use crate::{
+    request_id::{RequestId, REQUEST_ID_HEADER},
+    routes::error_chain_fmt,
+    utils::e500,
+};
+
+/// Shown instead of the error itself when something went wrong on our side:
+/// the details only belong in the logs.
+const SERVER_ERROR_MESSAGE: &str = "Something went wrong on our side. Please try again later.";
+
+/// Errors for handlers that have no more specific error type of their own.
+#[derive(thiserror::Error)]
+pub enum AppError {
+    #[error("{0}")]
+    BadRequest(String),
+    #[error("{0}")]
+    Unauthorized(String),
+    #[error("{0}")]
+    NotFound(String),
+    #[error(transparent)]
+    Unexpected(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Unexpected(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// What API clients get back when a request fails.
+#[derive(serde::Serialize)]
+struct ErrorBody<'a> {
+    /// Machine readable, derived from the status code, e.g. `bad_request`.
+    error: String,
+    message: &'a str,
+    request_id: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "error.html")]
+struct ErrorPage<'a> {
+    status: u16,
+    reason: &'a str,
+    message: &'a str,
+    request_id: &'a str,
+}
+
+/// Give every error response the same shape: a JSON body for API clients
+/// and an error page for browsers.
+///
+/// Responses that a handler already gave a body of its own, such as the
+/// readiness report, are left alone. Only the error's `Display` reaches the
+/// client, and not even that for server errors. The full chain is recorded on the request span by
+/// `TracingLogger`.
+pub async fn render_errors(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    // The request cannot be cloned here: routing needs it to be unique.
+    let html = wants_html(req.headers());
+    let request_id = RequestId::of(&req).unwrap_or_else(RequestId::generate);
+
+    let response = match next.call(req).await {
+        Ok(response) => response.map_into_boxed_body(),
+        // Errors raised by inner middleware have not become responses yet.
+        // Keep the error around so that it still gets logged.
+        Err(e) => {
+            let status = e.as_response_error().status_code();
+            if !is_error(status) {
+                return Err(e);
+            }
+            let (content_type, body) =
+                render_error(Some(e.as_response_error()), status, html, &request_id)?;
+            let mut response = e.error_response().set_body(BoxBody::new(body));
+            set_headers(response.headers_mut(), content_type, &request_id);
+            return Err(InternalError::from_response(e, response).into());
+        }
+    };
+
+    let status = response.status();
+    if !is_error(status) || !needs_body(response.response()) {
+        return Ok(response);
+    }
+    let error = response.response().error().map(|e| e.as_response_error());
+    let (content_type, body) = render_error(error, status, html, &request_id)?;
+    let mut response = response.map_body(|_, _| BoxBody::new(body));
+    set_headers(response.headers_mut(), content_type, &request_id);
+    Ok(response)
+}
+
+fn is_error(status: StatusCode) -> bool {
+    status.is_client_error() || status.is_server_error()
+}
+
+/// Whether the response has no body of its own. The plain text body that
+/// actix gives errors by default does not count.
+fn needs_body(response: &HttpResponse) -> bool {
+    match response.body().size() {
+        BodySize::None | BodySize::Sized(0) => true,
+        _ => {
+            response.error().is_some()
+                && response
+                    .headers()
+                    .get(CONTENT_TYPE)
+                    .and_then(|value| value.to_str().ok())
+                    .is_some_and(|content_type| content_type.starts_with("text/plain"))
+        }
+    }
+}
+
+fn render_error(
+    error: Option<&dyn ResponseError>,
+    status: StatusCode,
+    html: bool,
+    request_id: &RequestId,
+) -> Result<(&'static str, String), actix_web::Error> {
+    let message = match error {
+        _ if status.is_server_error() => SERVER_ERROR_MESSAGE.to_string(),
+        Some(error) => error.to_string(),
+        None => status.canonical_reason().unwrap_or("Error").to_string(),
+    };
+    if html {
+        let page = ErrorPage {
+            status: status.as_u16(),
+            reason: status.canonical_reason().unwrap_or("Error"),
+            message: &message,
+            request_id: request_id.as_str(),
+        };
+        let body = page.render().map_err(e500)?;
+        Ok(("text/html; charset=utf-8", body))
+    } else {
+        let body = ErrorBody {
+            error: error_code(status),
+            message: &message,
+            request_id: request_id.as_str(),
+        };
+        let body = serde_json::to_string(&body).map_err(e500)?;
+        Ok(("application/json", body))
+    }
+}
+
+fn set_headers(headers: &mut HeaderMap, content_type: &'static str, request_id: &RequestId) {
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+    if let Ok(value) = HeaderValue::from_str(request_id.as_str()) {
+        headers.insert(REQUEST_ID_HEADER, value);
+    }
+}
+
+/// Browsers ask for HTML explicitly, API clients do not.
+fn wants_html(headers: &HeaderMap) -> bool {
+    headers
+        .get(ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/html"))
+}
+
+fn error_code(status: StatusCode) -> String {
+    status
+        .canonical_reason()
+        .unwrap_or("error")
+        .to_lowercase()
+        .replace([' ', '-'], "_")
+        .replace('\'', "")
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+
+    use super::error_code;
+
+    #[test]
+    fn error_codes_follow_the_status() {
+        assert_eq!(error_code(StatusCode::BAD_REQUEST), "bad_request");
+        assert_eq!(error_code(StatusCode::IM_A_TEAPOT), "im_a_teapot");
+        assert_eq!(
+            error_code(StatusCode::INTERNAL_SERVER_ERROR),
+            "internal_server_error"
+        );
+    }
+}
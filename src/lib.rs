@@ -1,9 +1,24 @@
+pub mod audit;
 pub mod authentication;
+pub mod bot_protection;
 pub mod configuration;
+pub mod confirmation_email;
 pub mod domain;
 pub mod email_client;
+pub mod email_events;
+pub mod errors;
+pub mod lists;
+pub mod metrics;
+pub mod preferences;
+pub mod rate_limit;
+pub mod request_id;
 pub mod routes;
+pub mod segments;
+pub mod sequences;
 pub mod session_state;
+pub mod shutdown;
 pub mod startup;
 pub mod telemetry;
+pub mod tracking;
+pub mod translations;
 pub mod utils;
@@ -0,0 +1,114 @@
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::domain::Label;
+
+/// A publication people subscribe to, with its own subscriptions and issues.
+pub struct List {
+    pub list_id: Uuid,
+    /// How the subscription form refers to the list.
+    pub slug: Label,
+    pub name: String,
+}
+
+struct ListRow {
+    list_id: Uuid,
+    slug: String,
+    name: String,
+}
+
+impl TryFrom<ListRow> for List {
+    type Error = anyhow::Error;
+
+    fn try_from(row: ListRow) -> Result<Self, Self::Error> {
+        Ok(List {
+            list_id: row.list_id,
+            slug: Label::parse(&row.slug).map_err(anyhow::Error::msg)?,
+            name: row.name,
+        })
+    }
+}
+
+#[tracing::instrument(name = "List lists", skip(pool))]
+pub async fn all_lists(pool: &PgPool) -> Result<Vec<List>, anyhow::Error> {
+    sqlx::query_as!(
+        ListRow,
+        r#"SELECT list_id, slug, name FROM lists ORDER BY created_at, name"#,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to list lists")?
+    .into_iter()
+    .map(List::try_from)
+    .collect()
+}
+
+#[tracing::instrument(name = "Get list", skip(pool))]
+pub async fn get_list(pool: &PgPool, list_id: Uuid) -> Result<Option<List>, anyhow::Error> {
+    sqlx::query_as!(
+        ListRow,
+        r#"SELECT list_id, slug, name FROM lists WHERE list_id = $1"#,
+        list_id,
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch list")?
+    .map(List::try_from)
+    .transpose()
+}
+
+#[tracing::instrument(name = "Get list by slug", skip(pool))]
+pub async fn get_list_by_slug(pool: &PgPool, slug: &Label) -> Result<Option<List>, anyhow::Error> {
+    sqlx::query_as!(
+        ListRow,
+        r#"SELECT list_id, slug, name FROM lists WHERE slug = $1"#,
+        slug.as_ref(),
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch list")?
+    .map(List::try_from)
+    .transpose()
+}
+
+/// `None` when there is a list with that slug already.
+#[tracing::instrument(name = "Create list", skip(pool))]
+pub async fn create_list(
+    pool: &PgPool,
+    slug: &Label,
+    name: &str,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        INSERT INTO lists (list_id, slug, name, created_at)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (slug) DO NOTHING
+        RETURNING list_id
+        "#,
+        Uuid::new_v4(),
+        slug.as_ref(),
+        name,
+        Utc::now(),
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to store list")?;
+    Ok(row.map(|row| row.list_id))
+}
+
+/// The list a form refers to: the one named by `slug`, or the only list
+/// when there is a single one and no slug was given.
+pub async fn resolve_list(
+    pool: &PgPool,
+    slug: Option<&Label>,
+) -> Result<Option<List>, anyhow::Error> {
+    match slug {
+        Some(slug) => get_list_by_slug(pool, slug).await,
+        None => {
+            let mut lists = all_lists(pool).await?;
+            Ok(if lists.len() == 1 { lists.pop() } else { None })
+        }
+    }
+}
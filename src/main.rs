@@ -1,17 +1,25 @@
 use zero2prod::{
     configuration::get_configuration,
     startup::Application,
-    telemetry::{get_subscriber, init_subscriber},
+    telemetry::{flush_tracer_provider, get_subscriber, init_subscriber, tracer_provider},
 };
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let subscriber = get_subscriber("zero2prod".into(), "info".into(), std::io::stdout);
-    init_subscriber(subscriber);
-
     let configuration = get_configuration().expect("Unable to get configuration");
 
+    let tracer_provider = tracer_provider(&configuration.telemetry)?;
+    let subscriber = get_subscriber(
+        "zero2prod".into(),
+        "info".into(),
+        std::io::stdout,
+        &tracer_provider,
+    );
+    init_subscriber(subscriber);
+
     let application = Application::build(configuration).await?;
-    application.run_until_stopped().await?;
+    let outcome = application.run_until_stopped().await;
+    flush_tracer_provider(tracer_provider).await;
+    outcome?;
     Ok(())
 }
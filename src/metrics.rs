@@ -0,0 +1,335 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+};
+use actix_web_lab::middleware::Next;
+use sqlx::PgPool;
+
+/// Upper bounds of the latency histogram buckets, in seconds.
+const LATENCY_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+/// The process-wide registry, in the spirit of Prometheus' default registry.
+pub fn metrics() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(Metrics::default)
+}
+
+#[derive(Default)]
+struct Histogram {
+    /// Cumulative count of observations per bucket.
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        for (bucket, bound) in self.buckets.iter_mut().zip(LATENCY_BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += seconds;
+    }
+
+    fn encode(&self, out: &mut String, name: &str, labels: &str) {
+        let separator = if labels.is_empty() { "" } else { "," };
+        for (bucket, bound) in self.buckets.iter().zip(LATENCY_BUCKETS) {
+            let _ = writeln!(
+                out,
+                "{name}_bucket{{{labels}{separator}le=\"{bound}\"}} {bucket}"
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{name}_bucket{{{labels}{separator}le=\"+Inf\"}} {}",
+            self.count
+        );
+        let labels = if labels.is_empty() {
+            String::new()
+        } else {
+            format!("{{{labels}}}")
+        };
+        let _ = writeln!(out, "{name}_sum{labels} {}", self.sum);
+        let _ = writeln!(out, "{name}_count{labels} {}", self.count);
+    }
+}
+
+/// Outcome of a call to the email delivery provider.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum EmailOutcome {
+    Delivered,
+    /// The provider answered with an error status.
+    Rejected,
+    Timeout,
+    /// The request could not be sent or its response could not be read.
+    Error,
+}
+
+impl EmailOutcome {
+    pub fn of<T>(result: &Result<T, reqwest::Error>) -> Self {
+        match result {
+            Ok(_) => EmailOutcome::Delivered,
+            Err(e) if e.is_timeout() => EmailOutcome::Timeout,
+            Err(e) if e.is_status() => EmailOutcome::Rejected,
+            Err(_) => EmailOutcome::Error,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EmailOutcome::Delivered => "delivered",
+            EmailOutcome::Rejected => "rejected",
+            EmailOutcome::Timeout => "timeout",
+            EmailOutcome::Error => "error",
+        }
+    }
+}
+
+#[derive(Default)]
+struct Registry {
+    /// Keyed by method, route pattern and status code.
+    http_requests: BTreeMap<(String, String, u16), Histogram>,
+    email_sends: BTreeMap<EmailOutcome, Histogram>,
+    subscriptions: u64,
+    confirmations: u64,
+    /// Sampled by the sequence scheduler after each round.
+    queued_sequence_steps: u64,
+}
+
+/// Application metrics, exposed in the Prometheus text format.
+#[derive(Default)]
+pub struct Metrics {
+    registry: Mutex<Registry>,
+}
+
+impl Metrics {
+    fn registry(&self) -> std::sync::MutexGuard<'_, Registry> {
+        // A panic while holding the lock cannot leave the counters inconsistent.
+        self.registry.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn observe_http_request(&self, method: &str, route: &str, status: u16, elapsed: Duration) {
+        self.registry()
+            .http_requests
+            .entry((method.to_owned(), route.to_owned(), status))
+            .or_default()
+            .observe(elapsed);
+    }
+
+    pub fn observe_email_send(&self, outcome: EmailOutcome, elapsed: Duration) {
+        self.registry()
+            .email_sends
+            .entry(outcome)
+            .or_default()
+            .observe(elapsed);
+    }
+
+    pub fn increment_subscriptions(&self) {
+        self.registry().subscriptions += 1;
+    }
+
+    pub fn increment_confirmations(&self) {
+        self.registry().confirmations += 1;
+    }
+
+    pub fn set_queued_sequence_steps(&self, queued: u64) {
+        self.registry().queued_sequence_steps = queued;
+    }
+
+    /// Render every metric, sampling the utilization of `pool` on the way.
+    pub fn encode(&self, pool: &PgPool) -> String {
+        let mut out = String::new();
+        self.encode_registry(&mut out);
+
+        let size = pool.size() as usize;
+        let idle = pool.num_idle();
+        out.push_str("# HELP db_pool_connections Open database connections by state.\n");
+        out.push_str("# TYPE db_pool_connections gauge\n");
+        let _ = writeln!(out, "db_pool_connections{{state=\"idle\"}} {idle}");
+        let _ = writeln!(
+            out,
+            "db_pool_connections{{state=\"in_use\"}} {}",
+            size.saturating_sub(idle)
+        );
+        out
+    }
+
+    fn encode_registry(&self, out: &mut String) {
+        let registry = self.registry();
+
+        out.push_str("# HELP http_requests_total HTTP requests handled.\n");
+        out.push_str("# TYPE http_requests_total counter\n");
+        for ((method, route, status), histogram) in &registry.http_requests {
+            let _ = writeln!(
+                out,
+                "http_requests_total{{{}}} {}",
+                http_labels(method, route, *status),
+                histogram.count
+            );
+        }
+        out.push_str("# HELP http_request_duration_seconds HTTP request latency.\n");
+        out.push_str("# TYPE http_request_duration_seconds histogram\n");
+        for ((method, route, status), histogram) in &registry.http_requests {
+            histogram.encode(
+                out,
+                "http_request_duration_seconds",
+                &http_labels(method, route, *status),
+            );
+        }
+
+        out.push_str("# HELP email_send_attempts_total Calls to the email delivery provider.\n");
+        out.push_str("# TYPE email_send_attempts_total counter\n");
+        for (outcome, histogram) in &registry.email_sends {
+            let _ = writeln!(
+                out,
+                "email_send_attempts_total{{outcome=\"{}\"}} {}",
+                outcome.as_str(),
+                histogram.count
+            );
+        }
+        let failures: u64 = registry
+            .email_sends
+            .iter()
+            .filter(|(outcome, _)| **outcome != EmailOutcome::Delivered)
+            .map(|(_, histogram)| histogram.count)
+            .sum();
+        out.push_str(
+            "# HELP email_send_failures_total Failed calls to the email delivery provider.\n",
+        );
+        out.push_str("# TYPE email_send_failures_total counter\n");
+        let _ = writeln!(out, "email_send_failures_total {failures}");
+        out.push_str("# HELP email_send_duration_seconds Email delivery provider latency.\n");
+        out.push_str("# TYPE email_send_duration_seconds histogram\n");
+        for (outcome, histogram) in &registry.email_sends {
+            histogram.encode(
+                out,
+                "email_send_duration_seconds",
+                &format!("outcome=\"{}\"", outcome.as_str()),
+            );
+        }
+
+        out.push_str("# HELP subscriptions_total Subscriptions created.\n");
+        out.push_str("# TYPE subscriptions_total counter\n");
+        let _ = writeln!(out, "subscriptions_total {}", registry.subscriptions);
+        out.push_str("# HELP subscription_confirmations_total Subscriptions confirmed.\n");
+        out.push_str("# TYPE subscription_confirmations_total counter\n");
+        let _ = writeln!(
+            out,
+            "subscription_confirmations_total {}",
+            registry.confirmations
+        );
+        out.push_str(
+            "# HELP sequence_steps_queued Sequence steps waiting in the delivery queue.\n",
+        );
+        out.push_str("# TYPE sequence_steps_queued gauge\n");
+        let _ = writeln!(
+            out,
+            "sequence_steps_queued {}",
+            registry.queued_sequence_steps
+        );
+    }
+}
+
+fn http_labels(method: &str, route: &str, status: u16) -> String {
+    format!(
+        "method=\"{}\",route=\"{}\",status=\"{status}\"",
+        escape_label(method),
+        escape_label(route)
+    )
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Count and time every request, labelled by its route pattern rather than
+/// its path so that path parameters do not blow up the number of series.
+pub async fn record_http_metrics(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let started_at = Instant::now();
+    let method = req.method().to_string();
+    let route = req
+        .match_pattern()
+        .unwrap_or_else(|| "unmatched".to_string());
+
+    let result = next.call(req).await;
+    let status = match &result {
+        Ok(response) => response.status(),
+        Err(e) => e.as_response_error().status_code(),
+    };
+    metrics().observe_http_request(&method, &route, status.as_u16(), started_at.elapsed());
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{escape_label, Histogram, Metrics};
+
+    #[test]
+    fn histogram_buckets_are_cumulative() {
+        let mut histogram = Histogram::default();
+        histogram.observe(Duration::from_millis(20));
+        histogram.observe(Duration::from_secs(20));
+
+        let mut out = String::new();
+        histogram.encode(&mut out, "latency", "");
+        assert!(out.contains("latency_bucket{le=\"0.01\"} 0\n"));
+        assert!(out.contains("latency_bucket{le=\"0.025\"} 1\n"));
+        assert!(out.contains("latency_bucket{le=\"10\"} 1\n"));
+        assert!(out.contains("latency_bucket{le=\"+Inf\"} 2\n"));
+        assert!(out.contains("latency_count 2\n"));
+    }
+
+    #[test]
+    fn requests_are_counted_per_route_and_status() {
+        let metrics = Metrics::default();
+        metrics.observe_http_request("GET", "/login", 200, Duration::from_millis(1));
+        metrics.observe_http_request("GET", "/login", 200, Duration::from_millis(1));
+        metrics.observe_http_request("GET", "/login", 303, Duration::from_millis(1));
+
+        let mut out = String::new();
+        metrics.encode_registry(&mut out);
+        assert!(
+            out.contains("http_requests_total{method=\"GET\",route=\"/login\",status=\"200\"} 2\n")
+        );
+        assert!(
+            out.contains("http_requests_total{method=\"GET\",route=\"/login\",status=\"303\"} 1\n")
+        );
+    }
+
+    #[test]
+    fn the_queue_depth_is_the_latest_sample() {
+        let metrics = Metrics::default();
+        metrics.set_queued_sequence_steps(7);
+        metrics.set_queued_sequence_steps(3);
+
+        let mut out = String::new();
+        metrics.encode_registry(&mut out);
+        assert!(out.contains("# TYPE sequence_steps_queued gauge\n"));
+        assert!(out.contains("sequence_steps_queued 3\n"));
+    }
+
+    #[test]
+    fn label_values_are_escaped() {
+        assert_eq!(escape_label("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+    }
+}
@@ -0,0 +1,458 @@
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    domain::{SubscriberEmail, SubscriberName},
+    sequences::{enroll, reschedule_paused},
+};
+
+/// Signs the links to the preference center put in every email.
+///
+/// A link stands for one subscription and, through its address, for every
+/// subscription of the same person. Links do not expire: old emails keep
+/// working.
+pub struct PreferenceLinks {
+    key: Secret<String>,
+    base_url: String,
+}
+
+impl PreferenceLinks {
+    pub fn new(key: Secret<String>, base_url: String) -> Self {
+        Self { key, base_url }
+    }
+
+    pub fn link(&self, subscriber_id: Uuid) -> String {
+        format!(
+            "{}/preferences?token={}",
+            self.base_url,
+            self.token(subscriber_id)
+        )
+    }
+
+    pub fn token(&self, subscriber_id: Uuid) -> String {
+        let tag = self.mac(subscriber_id).finalize().into_bytes();
+        format!("{subscriber_id}.{}", hex::encode(tag))
+    }
+
+    /// The subscription a token was signed for, unless it was tampered with.
+    pub fn verify(&self, token: &str) -> Option<Uuid> {
+        let (subscriber_id, tag) = token.split_once('.')?;
+        let subscriber_id = subscriber_id.parse().ok()?;
+        self.mac(subscriber_id)
+            .verify_slice(&hex::decode(tag).ok()?)
+            .ok()?;
+        Some(subscriber_id)
+    }
+
+    fn mac(&self, subscriber_id: Uuid) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.key.expose_secret().as_bytes())
+            .expect("HMAC can take a key of any size");
+        mac.update(format!("preferences:{subscriber_id}").as_bytes());
+        mac
+    }
+
+    /// The bodies of an email with a link to the preference center at the
+    /// end.
+    pub fn add_footer(&self, html: &str, text: &str, subscriber_id: Uuid) -> (String, String) {
+        let link = self.link(subscriber_id);
+        let footer = format!(
+            r#"<p><a href="{}">Manage your subscription</a></p>"#,
+            htmlescape::encode_minimal(&link)
+        );
+        let mut html = html.to_string();
+        match html.rfind("</body>") {
+            Some(end) => html.insert_str(end, &footer),
+            None => html.push_str(&footer),
+        }
+        let text = format!("{text}\n\n--\nManage your subscription: {link}");
+        (html, text)
+    }
+}
+
+/// The person behind a preference link. Their name and address are the
+/// same on all their subscriptions.
+pub struct Subscriber {
+    pub subscriber_id: Uuid,
+    pub email: String,
+    pub name: String,
+    /// The language code picked when subscribing.
+    pub locale: String,
+}
+
+/// A list, and whether a subscriber gets it.
+pub struct ListChoice {
+    pub list_id: Uuid,
+    pub slug: String,
+    pub name: String,
+    /// `None` when they never subscribed.
+    pub status: Option<String>,
+}
+
+impl ListChoice {
+    pub fn subscribed(&self) -> bool {
+        matches!(
+            self.status.as_deref(),
+            Some("confirmed" | "pending_confirmation")
+        )
+    }
+
+    /// Addresses that bounced or complained stay off the list whatever
+    /// they ask for.
+    pub fn blocked(&self) -> bool {
+        matches!(self.status.as_deref(), Some("bounced" | "complained"))
+    }
+}
+
+#[tracing::instrument(name = "Get subscriber", skip(pool))]
+pub async fn get_subscriber(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Option<Subscriber>, anyhow::Error> {
+    let subscriber = sqlx::query_as!(
+        Subscriber,
+        r#"SELECT id AS subscriber_id, email, name, locale FROM subscriptions WHERE id = $1"#,
+        subscriber_id,
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch subscriber")?;
+    Ok(subscriber)
+}
+
+#[tracing::instrument(name = "Get list choices", skip(pool))]
+pub async fn list_choices(pool: &PgPool, email: &str) -> Result<Vec<ListChoice>, anyhow::Error> {
+    let choices = sqlx::query_as!(
+        ListChoice,
+        r#"
+        SELECT l.list_id, l.slug, l.name, s.status AS "status?"
+        FROM lists l
+        LEFT JOIN subscriptions s ON s.list_id = l.list_id AND lower(s.email) = lower($1)
+        ORDER BY l.created_at, l.name
+        "#,
+        email,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch list choices")?;
+    Ok(choices)
+}
+
+/// When delivery resumes, if it is paused.
+#[tracing::instrument(name = "Get pause", skip(pool))]
+pub async fn paused_until(
+    pool: &PgPool,
+    email: &str,
+) -> Result<Option<DateTime<Utc>>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT MAX(paused_until) AS paused_until
+        FROM subscriptions
+        WHERE lower(email) = lower($1) AND paused_until > now()
+        "#,
+        email,
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to fetch pause")?;
+    Ok(row.paused_until)
+}
+
+#[tracing::instrument(name = "Rename subscriber", skip(pool))]
+pub async fn rename_subscriber(
+    pool: &PgPool,
+    email: &str,
+    name: &SubscriberName,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"UPDATE subscriptions SET name = $2 WHERE lower(email) = lower($1)"#,
+        email,
+        name.as_ref(),
+    )
+    .execute(pool)
+    .await
+    .context("Failed to rename subscriber")?;
+    Ok(())
+}
+
+/// Subscribe to the `wanted` lists and unsubscribe from the others.
+///
+/// The preference link reached this person at their address, so new
+/// subscriptions need no further confirmation. Subscriptions still waiting
+/// for theirs keep waiting: only their own link confirms them. The lists
+/// joined here enroll the subscriber in their sequences, as a confirmation
+/// does.
+#[tracing::instrument(name = "Choose lists", skip(pool, subscriber))]
+pub async fn choose_lists(
+    pool: &PgPool,
+    subscriber: &Subscriber,
+    wanted: &[Uuid],
+) -> Result<(), anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection")?;
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions SET status = 'unsubscribed'
+        WHERE lower(email) = lower($1)
+            AND status IN ('confirmed', 'pending_confirmation')
+            AND NOT list_id = ANY($2)
+        "#,
+        subscriber.email,
+        wanted,
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to unsubscribe")?;
+    let resubscribed = sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET status = 'confirmed', confirmed_at = COALESCE(confirmed_at, now())
+        WHERE lower(email) = lower($1)
+            AND status = 'unsubscribed'
+            AND list_id = ANY($2)
+        RETURNING id
+        "#,
+        subscriber.email,
+        wanted,
+    )
+    .fetch_all(&mut transaction)
+    .await
+    .context("Failed to resubscribe")?;
+    let subscribed = sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (
+            id, email, name, subscribed_at, status, confirmed_at, source, list_id, locale
+        )
+        SELECT
+            gen_random_uuid(), $1, $2, now(), 'confirmed', now(), 'preferences', l.list_id, $4
+        FROM lists l
+        WHERE l.list_id = ANY($3) AND NOT EXISTS (
+            SELECT 1 FROM subscriptions s
+            WHERE s.list_id = l.list_id AND lower(s.email) = lower($1)
+        )
+        RETURNING id
+        "#,
+        subscriber.email,
+        subscriber.name,
+        wanted,
+        subscriber.locale,
+    )
+    .fetch_all(&mut transaction)
+    .await
+    .context("Failed to subscribe")?;
+    let joined = resubscribed.iter().map(|row| row.id);
+    for id in joined.chain(subscribed.iter().map(|row| row.id)) {
+        enroll(&mut transaction, id).await?;
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction")?;
+    Ok(())
+}
+
+/// Hold back issues until `until`, or resume delivery when `None`.
+#[tracing::instrument(name = "Pause delivery", skip(pool))]
+pub async fn pause_delivery(
+    pool: &PgPool,
+    email: &str,
+    until: Option<DateTime<Utc>>,
+) -> Result<(), anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection")?;
+    sqlx::query!(
+        r#"UPDATE subscriptions SET paused_until = $2 WHERE lower(email) = lower($1)"#,
+        email,
+        until,
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to pause delivery")?;
+    reschedule_paused(&mut transaction, email, until).await?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction")?;
+    Ok(())
+}
+
+/// How long a link confirming a new address works, in hours.
+const EMAIL_CHANGE_VALIDITY_HOURS: i64 = 24;
+
+#[tracing::instrument(name = "Request email change", skip(pool, token))]
+pub async fn request_email_change(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+    new_email: &SubscriberEmail,
+    token: &str,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO email_change_requests (token, subscriber_id, new_email, requested_at)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        token,
+        subscriber_id,
+        new_email.as_ref(),
+        Utc::now(),
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store email change request")?;
+    Ok(())
+}
+
+/// The latest address waiting for confirmation, if any.
+#[tracing::instrument(name = "Get pending email change", skip(pool))]
+pub async fn pending_email_change(
+    pool: &PgPool,
+    email: &str,
+) -> Result<Option<String>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT r.new_email
+        FROM email_change_requests r
+        JOIN subscriptions s ON s.id = r.subscriber_id
+        WHERE lower(s.email) = lower($1) AND r.requested_at > $2
+        ORDER BY r.requested_at DESC
+        LIMIT 1
+        "#,
+        email,
+        Utc::now() - chrono::Duration::hours(EMAIL_CHANGE_VALIDITY_HOURS),
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch pending email change")?;
+    Ok(row.map(|row| row.new_email))
+}
+
+pub enum EmailChange {
+    Changed {
+        subscriber_id: Uuid,
+        email: String,
+    },
+    /// The link is unknown or expired.
+    Invalid,
+    /// Another subscription took the address since the change was asked for.
+    Taken {
+        email: String,
+    },
+}
+
+/// Move every subscription of the person who asked to their new address.
+#[tracing::instrument(name = "Confirm email change", skip(pool, token))]
+pub async fn confirm_email_change(
+    pool: &PgPool,
+    token: &str,
+) -> Result<EmailChange, anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection")?;
+    let Some(request) = sqlx::query!(
+        r#"
+        SELECT r.subscriber_id, r.new_email, s.email AS old_email
+        FROM email_change_requests r
+        JOIN subscriptions s ON s.id = r.subscriber_id
+        WHERE r.token = $1 AND r.requested_at > $2
+        "#,
+        token,
+        Utc::now() - chrono::Duration::hours(EMAIL_CHANGE_VALIDITY_HOURS),
+    )
+    .fetch_optional(&mut transaction)
+    .await
+    .context("Failed to fetch email change request")?
+    else {
+        return Ok(EmailChange::Invalid);
+    };
+    let updated = sqlx::query!(
+        r#"UPDATE subscriptions SET email = $2 WHERE lower(email) = lower($1)"#,
+        request.old_email,
+        request.new_email,
+    )
+    .execute(&mut transaction)
+    .await;
+    match updated {
+        Err(sqlx::Error::Database(e)) if e.code().as_deref() == Some("23505") => {
+            return Ok(EmailChange::Taken {
+                email: request.new_email,
+            });
+        }
+        updated => updated.context("Failed to change email")?,
+    };
+    sqlx::query!(
+        r#"
+        DELETE FROM email_change_requests
+        WHERE subscriber_id IN (SELECT id FROM subscriptions WHERE lower(email) = lower($1))
+        "#,
+        request.new_email,
+    )
+    .execute(&mut transaction)
+    .await
+    .context("Failed to delete email change requests")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction")?;
+    Ok(EmailChange::Changed {
+        subscriber_id: request.subscriber_id,
+        email: request.new_email,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+    use uuid::Uuid;
+
+    use super::PreferenceLinks;
+
+    fn links() -> PreferenceLinks {
+        PreferenceLinks::new(Secret::new("secret".into()), "https://news.example".into())
+    }
+
+    #[test]
+    fn tokens_lead_back_to_the_subscription() {
+        let subscriber_id = Uuid::new_v4();
+        assert_eq!(
+            links().verify(&links().token(subscriber_id)),
+            Some(subscriber_id)
+        );
+    }
+
+    #[test]
+    fn tampered_tokens_are_rejected() {
+        let token = links().token(Uuid::new_v4());
+        let (_, tag) = token.split_once('.').unwrap();
+        assert!(links()
+            .verify(&format!("{}.{tag}", Uuid::new_v4()))
+            .is_none());
+        assert!(links().verify("garbage").is_none());
+        let other_key = PreferenceLinks::new(Secret::new("other".into()), String::new());
+        assert!(other_key.verify(&token).is_none());
+    }
+
+    #[test]
+    fn the_footer_goes_at_the_end_of_the_body() {
+        let subscriber_id = Uuid::new_v4();
+        let (html, text) = links().add_footer("<html><body>Hi</body></html>", "Hi", subscriber_id);
+        assert!(html
+            .starts_with(r#"<html><body>Hi<p><a href="https://news.example/preferences?token="#));
+        assert!(html.ends_with("</body></html>"));
+        assert_eq!(
+            text,
+            format!(
+                "Hi\n\n--\nManage your subscription: {}",
+                links().link(subscriber_id)
+            )
+        );
+    }
+}
@@ -0,0 +1,146 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use chrono::{Days, TimeZone, Utc};
+use redis::{aio::ConnectionManager, Script};
+use sqlx::PgPool;
+
+use crate::configuration::{SubscribeRateLimitSettings, TokenBucketSettings};
+
+/// Take a token from every bucket in KEYS, or from none of them, so that a
+/// refused request does not use up the allowance of the other buckets.
+///
+/// ARGV holds the current time in milliseconds, then the capacity and the
+/// refill interval in milliseconds of each bucket. Returns 0 when the
+/// tokens were taken, otherwise the number of milliseconds until they can be.
+const TAKE_TOKENS: &str = r#"
+local now = tonumber(ARGV[1])
+local levels = {}
+local wait = 0
+for i, key in ipairs(KEYS) do
+    local capacity = tonumber(ARGV[i * 2])
+    local interval = tonumber(ARGV[i * 2 + 1])
+    local bucket = redis.call('HMGET', key, 'tokens', 'updated_at')
+    local tokens = tonumber(bucket[1]) or capacity
+    local elapsed = math.max(0, now - (tonumber(bucket[2]) or now))
+    tokens = math.min(capacity, tokens + elapsed / interval)
+    levels[i] = tokens
+    if tokens < 1 then
+        wait = math.max(wait, math.ceil((1 - tokens) * interval))
+    end
+end
+if wait > 0 then
+    return wait
+end
+for i, key in ipairs(KEYS) do
+    local capacity = tonumber(ARGV[i * 2])
+    local interval = tonumber(ARGV[i * 2 + 1])
+    redis.call('HSET', key, 'tokens', levels[i] - 1, 'updated_at', now)
+    redis.call('PEXPIRE', key, capacity * interval)
+end
+return 0
+"#;
+
+pub enum RateLimitDecision {
+    Allowed,
+    Limited { retry_after: Duration },
+}
+
+/// Token buckets per client IP and per target email address for new
+/// subscriptions, stored in Redis so that they are shared by every
+/// instance of the application, and the global daily cap on confirmation
+/// emails, kept in Postgres.
+#[derive(Clone)]
+pub struct SubscribeRateLimiter {
+    connection: ConnectionManager,
+    settings: SubscribeRateLimitSettings,
+}
+
+impl SubscribeRateLimiter {
+    pub fn new(connection: ConnectionManager, settings: SubscribeRateLimitSettings) -> Self {
+        Self {
+            connection,
+            settings,
+        }
+    }
+
+    #[tracing::instrument(name = "Check subscribe rate limits", skip(self))]
+    pub async fn check(&self, ip: &str, email: &str) -> Result<RateLimitDecision, anyhow::Error> {
+        let buckets = [
+            (format!("subscribe_bucket:ip:{ip}"), &self.settings.per_ip),
+            (
+                format!("subscribe_bucket:email:{}", email.to_lowercase()),
+                &self.settings.per_email,
+            ),
+        ];
+        let script = Script::new(TAKE_TOKENS);
+        let mut invocation = script.prepare_invoke();
+        invocation.arg(now_millis());
+        for (key, bucket) in &buckets {
+            invocation
+                .key(key)
+                .arg(bucket.capacity)
+                .arg(refill_interval_millis(bucket));
+        }
+        let wait_millis: u64 = invocation
+            .invoke_async(&mut self.connection.clone())
+            .await
+            .context("Failed to take rate limit tokens")?;
+        Ok(match wait_millis {
+            0 => RateLimitDecision::Allowed,
+            wait_millis => RateLimitDecision::Limited {
+                retry_after: Duration::from_millis(wait_millis),
+            },
+        })
+    }
+
+    /// Count a confirmation email against today's cap, unless it has been
+    /// reached already.
+    #[tracing::instrument(name = "Reserve a confirmation email", skip(self, pool))]
+    pub async fn reserve_confirmation_email(
+        &self,
+        pool: &PgPool,
+    ) -> Result<RateLimitDecision, anyhow::Error> {
+        let now = Utc::now();
+        let reserved = sqlx::query!(
+            r#"
+            INSERT INTO confirmation_email_quota (day, sent)
+            VALUES ($1, 1)
+            ON CONFLICT (day) DO UPDATE
+            SET sent = confirmation_email_quota.sent + 1
+            WHERE confirmation_email_quota.sent < $2
+            RETURNING sent
+            "#,
+            now.date_naive(),
+            self.settings.daily_confirmation_emails as i32,
+        )
+        .fetch_optional(pool)
+        .await
+        .context("Failed to reserve a confirmation email")?;
+        if reserved.is_some() && self.settings.daily_confirmation_emails > 0 {
+            return Ok(RateLimitDecision::Allowed);
+        }
+
+        tracing::warn!("The daily cap on confirmation emails has been reached");
+        let tomorrow = now
+            .date_naive()
+            .checked_add_days(Days::new(1))
+            .and_then(|day| day.and_hms_opt(0, 0, 0))
+            .context("Failed to compute the start of tomorrow")?;
+        let tomorrow = Utc.from_utc_datetime(&tomorrow);
+        Ok(RateLimitDecision::Limited {
+            retry_after: (tomorrow - now).to_std().unwrap_or_default(),
+        })
+    }
+}
+
+fn refill_interval_millis(bucket: &TokenBucketSettings) -> u64 {
+    bucket.refill_interval_seconds.saturating_mul(1000).max(1)
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
@@ -0,0 +1,107 @@
+use std::{
+    fmt,
+    future::{ready, Ready},
+};
+
+use actix_web::{
+    body::MessageBody,
+    dev::{Payload, ServiceRequest, ServiceResponse},
+    http::header::{HeaderName, HeaderValue},
+    FromRequest, HttpMessage, HttpRequest,
+};
+use actix_web_lab::middleware::Next;
+use uuid::Uuid;
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Longest `X-Request-Id` accepted from a client.
+const MAX_LENGTH: usize = 128;
+
+/// Identifies a request in logs, traces and error responses.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(String);
+
+impl RequestId {
+    pub fn generate() -> Self {
+        Self(Uuid::new_v4().to_string())
+    }
+
+    /// Accept a caller's id if it is short and made of characters that are
+    /// safe to log and echo back.
+    pub fn parse(value: &str) -> Option<Self> {
+        let valid = !value.is_empty()
+            && value.len() <= MAX_LENGTH
+            && value
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b':'));
+        valid.then(|| Self(value.to_string()))
+    }
+
+    /// The id assigned to `request` by [`assign_request_id`], if any.
+    pub fn of(request: &impl HttpMessage) -> Option<Self> {
+        request.extensions().get::<RequestId>().cloned()
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FromRequest for RequestId {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(Ok(Self::of(req).unwrap_or_else(Self::generate)))
+    }
+}
+
+/// Reuse the caller's `X-Request-Id`, or make one up, and echo it back on
+/// the response.
+pub async fn assign_request_id(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let request_id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(RequestId::parse)
+        .unwrap_or_else(RequestId::generate);
+    req.extensions_mut().insert(request_id.clone());
+
+    let mut response = next.call(req).await?;
+    if let Ok(value) = HeaderValue::from_str(request_id.as_str()) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RequestId;
+
+    #[test]
+    fn reasonable_ids_are_accepted() {
+        for id in [
+            "abc-123",
+            "4bf92f35-77b3-4da6-a3ce-929d0e0e4736",
+            "req_1.2:3",
+        ] {
+            assert_eq!(RequestId::parse(id).unwrap().as_str(), id);
+        }
+    }
+
+    #[test]
+    fn unreasonable_ids_are_rejected() {
+        for id in ["", "has space", "new\nline", "<script>", &"a".repeat(129)] {
+            assert!(RequestId::parse(id).is_none(), "{id:?} was accepted");
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use actix_web::{http::header::ContentDisposition, web, Error, HttpRequest, HttpResponse};
+use askama::Template;
+
+use crate::{
+    audit::{
+        audit_events_to_csv, record_audit_event, search_audit_events, AuditAction, AuditFilter,
+        AuditRecord,
+    },
+    authentication::UserId,
+    utils::{e500, render},
+};
+
+/// Only the most recent events are shown on the page, the export has them all.
+const PAGE_SIZE: i64 = 200;
+
+#[derive(Template)]
+#[template(path = "admin/audit.html")]
+struct AuditLogTemplate<'a> {
+    messages: Vec<String>,
+    actions: &'a [AuditAction],
+    filter: &'a AuditFilter,
+    records: Vec<AuditRecord>,
+    query_string: &'a str,
+}
+
+pub async fn audit_log_page(
+    request: HttpRequest,
+    filter: web::Query<AuditFilter>,
+    pool: web::Data<sqlx::PgPool>,
+    _user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, Error> {
+    let mut messages = Vec::new();
+    let records = match filter.validate() {
+        Ok(()) => search_audit_events(&pool, &filter, Some(PAGE_SIZE))
+            .await
+            .map_err(e500)?,
+        Err(e) => {
+            messages.push(e.to_string());
+            Vec::new()
+        }
+    };
+
+    render(&AuditLogTemplate {
+        messages,
+        actions: &AuditAction::ALL,
+        filter: &filter,
+        records,
+        query_string: request.query_string(),
+    })
+}
+
+pub async fn export_audit_log(
+    request: HttpRequest,
+    filter: web::Query<AuditFilter>,
+    pool: web::Data<sqlx::PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, Error> {
+    let user_id = user_id.into_inner();
+    filter
+        .validate()
+        .map_err(actix_web::error::ErrorBadRequest)?;
+    let records = search_audit_events(&pool, &filter, None)
+        .await
+        .map_err(e500)?;
+    record_audit_event(
+        &pool,
+        &request,
+        Some(*user_id),
+        AuditAction::AuditLogExported,
+        None,
+        serde_json::json!({ "query": request.query_string(), "records": records.len() }),
+    )
+    .await
+    .map_err(e500)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        .insert_header(ContentDisposition::attachment("audit_log.csv"))
+        .body(audit_events_to_csv(&records)))
+}
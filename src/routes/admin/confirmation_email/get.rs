@@ -0,0 +1,100 @@
+use actix_web::{error::ErrorNotFound, web, Error, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use askama::Template;
+use sqlx::PgPool;
+
+use crate::{
+    authentication::{CsrfToken, UserId},
+    configuration::LocalizationSettings,
+    confirmation_email::{
+        template_version, template_versions, ConfirmationEmail, RenderedEmail, TemplateVersion,
+        CONFIRMATION_LINK, LIST_NAME,
+    },
+    domain::Locale,
+    utils::{e500, render},
+};
+
+#[derive(Template)]
+#[template(path = "admin/confirmation_email.html")]
+pub(super) struct ConfirmationEmailTemplate {
+    pub(super) messages: Vec<String>,
+    csrf_token: CsrfToken,
+    locale: Locale,
+    locales: [Locale; 3],
+    /// What the form is filled with.
+    draft: ConfirmationEmail,
+    /// The version the draft started from, `None` for the built-in email.
+    editing: Option<i32>,
+    versions: Vec<TemplateVersion>,
+    pub(super) preview: Option<RenderedEmail>,
+    pub(super) test_recipient: String,
+    confirmation_link: &'static str,
+    list_name: &'static str,
+}
+
+impl ConfirmationEmailTemplate {
+    pub(super) fn new(
+        csrf_token: CsrfToken,
+        locale: Locale,
+        draft: ConfirmationEmail,
+        editing: Option<i32>,
+        versions: Vec<TemplateVersion>,
+    ) -> Self {
+        Self {
+            messages: Vec::new(),
+            csrf_token,
+            locale,
+            locales: Locale::ALL,
+            draft,
+            editing,
+            versions,
+            preview: None,
+            test_recipient: String::new(),
+            confirmation_link: CONFIRMATION_LINK,
+            list_name: LIST_NAME,
+        }
+    }
+
+    fn is_current(&self, version: &TemplateVersion) -> bool {
+        self.versions.first().map(|latest| latest.version) == Some(version.version)
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct ConfirmationEmailQuery {
+    locale: Option<String>,
+    /// An earlier version to start editing from.
+    version: Option<i32>,
+}
+
+pub async fn confirmation_email_page(
+    query: web::Query<ConfirmationEmailQuery>,
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+    localization: web::Data<LocalizationSettings>,
+    _user_id: web::ReqData<UserId>,
+    csrf_token: CsrfToken,
+) -> Result<HttpResponse, Error> {
+    let locale = query
+        .locale
+        .as_deref()
+        .and_then(Locale::parse)
+        .unwrap_or(localization.default_locale);
+    let versions = template_versions(&pool, locale).await.map_err(e500)?;
+    let editing = query
+        .version
+        .or_else(|| versions.first().map(|latest| latest.version));
+    let draft = match editing {
+        Some(version) => template_version(&pool, locale, version)
+            .await
+            .map_err(e500)?
+            .ok_or_else(|| ErrorNotFound("There is no such version."))?,
+        None => ConfirmationEmail::built_in(locale),
+    };
+    let mut page = ConfirmationEmailTemplate::new(csrf_token, locale, draft, editing, versions);
+    page.messages = flash_messages
+        .iter()
+        .map(|m| m.content().to_string())
+        .collect();
+    render(&page)
+}
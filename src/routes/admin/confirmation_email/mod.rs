@@ -0,0 +1,5 @@
+mod get;
+mod post;
+
+pub use get::confirmation_email_page;
+pub use post::edit_confirmation_email;
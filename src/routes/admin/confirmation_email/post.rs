@@ -0,0 +1,137 @@
+use actix_web::{error::ErrorBadRequest, web, Error, HttpRequest, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+
+use super::get::ConfirmationEmailTemplate;
+use crate::{
+    audit::{record_audit_event, AuditAction},
+    authentication::{CsrfToken, UserId},
+    confirmation_email::{save_template, template_versions, ConfirmationEmail, RenderedEmail},
+    domain::{Locale, SubscriberEmail},
+    email_client::EmailClient,
+    lists::all_lists,
+    utils::{e500, render, see_other},
+};
+
+#[derive(serde::Deserialize)]
+pub struct ConfirmationEmailForm {
+    locale: String,
+    subject: String,
+    html_content: String,
+    text_content: String,
+    /// The version the draft started from.
+    editing: Option<i32>,
+    /// `preview`, `test` or `save`.
+    action: String,
+    /// Where a test goes.
+    test_recipient: Option<String>,
+}
+
+/// Preview, send a test of, or save the draft of the confirmation email.
+/// Previews, tests and invalid drafts show the page again with the draft
+/// filled in, so that no edit is lost.
+#[tracing::instrument(
+    name = "Edit confirmation email",
+    skip(request, form, pool, email_client, base_url, csrf_token),
+    fields(locale = %form.locale, action = %form.action)
+)]
+pub async fn edit_confirmation_email(
+    request: HttpRequest,
+    form: web::Form<ConfirmationEmailForm>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<String>,
+    user_id: web::ReqData<UserId>,
+    csrf_token: CsrfToken,
+) -> Result<HttpResponse, Error> {
+    let form = form.into_inner();
+    let user_id = user_id.into_inner();
+    let locale = Locale::parse(&form.locale)
+        .ok_or_else(|| ErrorBadRequest("There are no translations for this locale."))?;
+    let parsed = ConfirmationEmail::parse(&form.subject, &form.html_content, &form.text_content);
+    let draft = ConfirmationEmail {
+        subject: form.subject,
+        html_content: form.html_content,
+        text_content: form.text_content,
+    };
+    let versions = template_versions(&pool, locale).await.map_err(e500)?;
+    let mut page =
+        ConfirmationEmailTemplate::new(csrf_token, locale, draft, form.editing, versions);
+    page.test_recipient = form.test_recipient.unwrap_or_default();
+    let template = match parsed {
+        Ok(template) => template,
+        Err(e) => {
+            page.messages.push(e);
+            return render(&page);
+        }
+    };
+
+    match form.action.as_str() {
+        "save" => {
+            let version = save_template(&pool, locale, &template, *user_id)
+                .await
+                .map_err(e500)?;
+            record_audit_event(
+                &pool,
+                &request,
+                Some(*user_id),
+                AuditAction::ConfirmationEmailChanged,
+                Some(locale.code()),
+                serde_json::json!({ "version": version }),
+            )
+            .await
+            .map_err(e500)?;
+            FlashMessage::info(format!(
+                "Saved version {version} of the {locale} confirmation email."
+            ))
+            .send();
+            Ok(see_other(&format!(
+                "/admin/confirmation-email?locale={locale}"
+            )))
+        }
+        "preview" => {
+            page.preview = Some(sample(&pool, &template, &base_url).await?);
+            render(&page)
+        }
+        "test" => {
+            let email = sample(&pool, &template, &base_url).await?;
+            match SubscriberEmail::parse(page.test_recipient.trim().to_string()) {
+                Ok(recipient) => {
+                    email_client
+                        .send_email(
+                            &recipient,
+                            &format!("[Test] {}", email.subject),
+                            &email.html_content,
+                            &email.text_content,
+                        )
+                        .await
+                        .map_err(e500)?;
+                    page.messages.push(format!("Sent a test to {recipient}."));
+                }
+                Err(e) => page.messages.push(e),
+            }
+            page.preview = Some(email);
+            render(&page)
+        }
+        _ => Err(ErrorBadRequest("Unknown action.")),
+    }
+}
+
+/// The draft filled in as it would be for the first list. Its link confirms
+/// nothing.
+async fn sample(
+    pool: &PgPool,
+    template: &ConfirmationEmail,
+    base_url: &str,
+) -> Result<RenderedEmail, Error> {
+    let list_name = all_lists(pool)
+        .await
+        .map_err(e500)?
+        .into_iter()
+        .next()
+        .map_or_else(|| "Newsletter".to_string(), |list| list.name);
+    Ok(template.render(
+        &list_name,
+        &format!("{base_url}/subscriptions/confirm?subscription_token=test"),
+    ))
+}
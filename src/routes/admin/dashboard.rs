@@ -1,43 +1,58 @@
 use actix_web::{web, HttpResponse};
 use actix_web_flash_messages::IncomingFlashMessages;
 use anyhow::Context;
+use askama::Template;
 use sqlx::PgPool;
 use uuid::Uuid;
 
-use crate::{authentication::UserId, utils::e500};
+use super::stats::{dashboard_stats, DashboardStats, PERIODS};
+use crate::{
+    authentication::{CsrfToken, UserId},
+    utils::{e500, render},
+};
+
+#[derive(Template)]
+#[template(path = "admin/dashboard.html")]
+struct DashboardTemplate {
+    username: String,
+    messages: Vec<String>,
+    csrf_token: CsrfToken,
+    stats: DashboardStats,
+    periods: [u64; 2],
+}
+
+#[derive(serde::Deserialize)]
+pub struct DashboardQuery {
+    days: Option<u64>,
+}
 
 pub async fn admin_dashboard(
     pool: web::Data<PgPool>,
     flash_messages: IncomingFlashMessages,
     user_id: web::ReqData<UserId>,
+    csrf_token: CsrfToken,
+    query: web::Query<DashboardQuery>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let user_id = user_id.into_inner();
     let username = get_username(*user_id, &pool).await.map_err(e500)?;
-    let mut message_html = String::new();
-    for message in flash_messages.iter() {
-        message_html.push_str(&format!("<p>{}</p>", message.content()));
-    }
-
-    let html = format!(
-        r#"
-        <!DOCTYPE html>
-        <html>
-        <head>
-            <title>Dashboard</title>
-        </head>
-        <body>
-            <h1>Welcome, {}!</h1>
-            {}
-            <a href="logout">Logout</a>
-            <br />
-            <a href="newsletter">Create new newsletter</a>
-        </body>
-        </html>
-        "#,
-        username, message_html
-    );
+    // Unsupported periods fall back to the shortest one.
+    let period_days = query
+        .days
+        .filter(|days| PERIODS.contains(days))
+        .unwrap_or(PERIODS[0]);
+    let stats = dashboard_stats(&pool, period_days).await.map_err(e500)?;
+    let messages = flash_messages
+        .iter()
+        .map(|m| m.content().to_string())
+        .collect();
 
-    Ok(HttpResponse::Ok().body(html))
+    render(&DashboardTemplate {
+        username,
+        messages,
+        csrf_token,
+        stats,
+        periods: PERIODS,
+    })
 }
 
 #[tracing::instrument(name = "fetching username from database", skip(pool))]
@@ -0,0 +1,66 @@
+use actix_web::{web, Error, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use askama::Template;
+use sqlx::PgPool;
+
+use crate::{
+    authentication::{CsrfToken, UserId},
+    utils::{e500, render},
+};
+
+struct ListRow {
+    slug: String,
+    name: String,
+    confirmed: i64,
+    pending: i64,
+}
+
+#[derive(Template)]
+#[template(path = "admin/lists.html")]
+struct ListsTemplate {
+    messages: Vec<String>,
+    csrf_token: CsrfToken,
+    lists: Vec<ListRow>,
+}
+
+pub async fn lists_page(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+    _user_id: web::ReqData<UserId>,
+    csrf_token: CsrfToken,
+) -> Result<HttpResponse, Error> {
+    let messages = flash_messages
+        .iter()
+        .map(|m| m.content().to_string())
+        .collect();
+    let lists = lists_with_counts(&pool).await.map_err(e500)?;
+
+    render(&ListsTemplate {
+        messages,
+        csrf_token,
+        lists,
+    })
+}
+
+#[tracing::instrument(name = "List lists with subscription counts", skip(pool))]
+async fn lists_with_counts(pool: &PgPool) -> Result<Vec<ListRow>, anyhow::Error> {
+    let lists = sqlx::query_as!(
+        ListRow,
+        r#"
+        SELECT
+            l.slug,
+            l.name,
+            COUNT(s.id) FILTER (WHERE s.status = 'confirmed') AS "confirmed!",
+            COUNT(s.id) FILTER (WHERE s.status = 'pending_confirmation') AS "pending!"
+        FROM lists l
+        LEFT JOIN subscriptions s USING (list_id)
+        GROUP BY l.list_id
+        ORDER BY l.created_at, l.name
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to list lists")?;
+    Ok(lists)
+}
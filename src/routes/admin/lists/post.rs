@@ -0,0 +1,56 @@
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+
+use crate::{
+    audit::{record_audit_event, AuditAction},
+    authentication::UserId,
+    domain::Label,
+    lists,
+    utils::{e500, see_other},
+};
+
+#[derive(serde::Deserialize)]
+pub struct ListForm {
+    slug: String,
+    name: String,
+}
+
+pub async fn create_list(
+    request: HttpRequest,
+    form: web::Form<ListForm>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, Error> {
+    let user_id = user_id.into_inner();
+    let name = form.name.trim();
+    if name.is_empty() {
+        FlashMessage::error("A list needs a name.").send();
+        return Ok(see_other("/admin/lists"));
+    }
+    let slug = match Label::parse(&form.slug) {
+        Ok(slug) => slug,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/lists"));
+        }
+    };
+
+    let created = lists::create_list(&pool, &slug, name).await.map_err(e500)?;
+    if created.is_none() {
+        FlashMessage::error(format!("There is a list called {slug} already.")).send();
+        return Ok(see_other("/admin/lists"));
+    }
+    record_audit_event(
+        &pool,
+        &request,
+        Some(*user_id),
+        AuditAction::ListCreated,
+        Some(slug.as_ref()),
+        serde_json::json!({ "name": name }),
+    )
+    .await
+    .map_err(e500)?;
+    FlashMessage::info(format!("List {name} created")).send();
+    Ok(see_other("/admin/lists"))
+}
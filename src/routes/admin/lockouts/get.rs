@@ -0,0 +1,35 @@
+use actix_web::{web, Error, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use askama::Template;
+
+use crate::{
+    authentication::{CsrfToken, Lockout, LoginThrottle, UserId},
+    utils::{e500, render},
+};
+
+#[derive(Template)]
+#[template(path = "admin/lockouts.html")]
+struct LockoutsTemplate {
+    messages: Vec<String>,
+    lockouts: Vec<Lockout>,
+    csrf_token: CsrfToken,
+}
+
+pub async fn lockouts_page(
+    flash_messages: IncomingFlashMessages,
+    throttle: web::Data<LoginThrottle>,
+    _user_id: web::ReqData<UserId>,
+    csrf_token: CsrfToken,
+) -> Result<HttpResponse, Error> {
+    let messages = flash_messages
+        .iter()
+        .map(|m| m.content().to_string())
+        .collect();
+    let lockouts = throttle.lockouts().await.map_err(e500)?;
+
+    render(&LockoutsTemplate {
+        messages,
+        lockouts,
+        csrf_token,
+    })
+}
@@ -0,0 +1,5 @@
+mod get;
+mod post;
+
+pub use get::lockouts_page;
+pub use post::clear_lockout;
@@ -0,0 +1,47 @@
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+
+use crate::{
+    audit::{record_audit_event, AuditAction},
+    authentication::{LoginThrottle, ThrottleTarget, UserId},
+    utils::{e500, see_other},
+};
+
+#[derive(serde::Deserialize)]
+pub struct ClearLockoutData {
+    target: String,
+}
+
+pub async fn clear_lockout(
+    request: HttpRequest,
+    form: web::Form<ClearLockoutData>,
+    throttle: web::Data<LoginThrottle>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, Error> {
+    let user_id = user_id.into_inner();
+    let target = match ThrottleTarget::parse(&form.target) {
+        Some(target) => target,
+        None => {
+            FlashMessage::error("Unknown lockout").send();
+            return Ok(see_other("/admin/lockouts"));
+        }
+    };
+
+    throttle.clear(&target).await.map_err(e500)?;
+    tracing::info!(%user_id, %target, "Login lockout cleared");
+    record_audit_event(
+        &pool,
+        &request,
+        Some(*user_id),
+        AuditAction::LockoutCleared,
+        Some(&target.to_string()),
+        serde_json::json!({}),
+    )
+    .await
+    .map_err(e500)?;
+
+    FlashMessage::info(format!("Lockout cleared for {}", target)).send();
+    Ok(see_other("/admin/lockouts"))
+}
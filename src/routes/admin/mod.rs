@@ -1,7 +1,26 @@
+mod audit;
+mod confirmation_email;
 mod dashboard;
+mod lists;
+mod lockouts;
 mod newsletter;
 mod password;
+mod segments;
+mod sequences;
+mod sessions;
+mod stats;
+mod subscribers;
+mod totp;
 
+pub use audit::*;
+pub use confirmation_email::*;
 pub use dashboard::*;
+pub use lists::*;
+pub use lockouts::*;
 pub use newsletter::*;
 pub use password::*;
+pub use segments::*;
+pub use sequences::*;
+pub use sessions::*;
+pub use subscribers::*;
+pub use totp::*;
@@ -1,29 +1,76 @@
 use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use askama::Template;
+use sqlx::PgPool;
 
-use crate::authentication::UserId;
+use crate::{
+    authentication::{CsrfToken, UserId},
+    lists::{all_lists, List},
+    segments::{count_recipients, list_segments, Segment, SegmentDefinition},
+    tracking::Tracker,
+    utils::{e500, render},
+};
+
+/// How many people an issue addressed to `audience` would reach, in the
+/// order of the lists on the page.
+struct RecipientCounts {
+    audience: String,
+    counts: Vec<i64>,
+}
+
+#[derive(Template)]
+#[template(path = "admin/newsletter.html")]
+struct NewsletterTemplate {
+    messages: Vec<String>,
+    csrf_token: CsrfToken,
+    tracking_enabled: bool,
+    lists: Vec<List>,
+    segments: Vec<Segment>,
+    /// Everyone first, then each segment.
+    recipients: Vec<RecipientCounts>,
+}
 
 pub async fn get_newsletter_page(
     user_id: web::ReqData<UserId>,
+    csrf_token: CsrfToken,
+    tracker: web::Data<Tracker>,
+    pool: web::Data<PgPool>,
+    flash_messages: IncomingFlashMessages,
 ) -> Result<HttpResponse, actix_web::Error> {
     let _user_id = user_id.into_inner();
-    let html = r#"
-    <html>
-        <head>
-            <title>Admin Newsletter</title>
-        </head>
-        <body>
-            <h1>Send Newsletter</h1>
-            <form action="newsletter" method="post">
-                <input type="text" name="title" placeholder="Title" required>
-                <br />
-                <textarea name="html_content" placeholder="html content" required></textarea>
-                <textarea name="content" placeholder="content" required></textarea>
-                <br /> 
-                <button type="submit">Send Issue</button>
-            </form>
-        </body>
-    </html>
-    "#;
-
-    Ok(HttpResponse::Ok().body(html))
+    let messages = flash_messages
+        .iter()
+        .map(|m| m.content().to_string())
+        .collect();
+    let lists = all_lists(&pool).await.map_err(e500)?;
+    let segments = list_segments(&pool).await.map_err(e500)?;
+    let everyone = SegmentDefinition::default();
+    let audiences = std::iter::once(("All confirmed subscribers", &everyone)).chain(
+        segments
+            .iter()
+            .map(|segment| (segment.name.as_str(), &segment.definition)),
+    );
+    let mut recipients = Vec::new();
+    for (audience, definition) in audiences {
+        let mut counts = Vec::with_capacity(lists.len());
+        for list in &lists {
+            counts.push(
+                count_recipients(&pool, Some(list.list_id), definition)
+                    .await
+                    .map_err(e500)?,
+            );
+        }
+        recipients.push(RecipientCounts {
+            audience: audience.to_string(),
+            counts,
+        });
+    }
+    render(&NewsletterTemplate {
+        messages,
+        csrf_token,
+        tracking_enabled: tracker.enabled(),
+        lists,
+        segments,
+        recipients,
+    })
 }
@@ -1,11 +1,22 @@
-use actix_web::{http::header, web, HttpResponse, ResponseError};
+use actix_web::{http::header, web, HttpRequest, HttpResponse, ResponseError};
+use actix_web_flash_messages::FlashMessage;
 use anyhow::Context;
+use chrono::Utc;
 use reqwest::{header::HeaderValue, StatusCode};
 use sqlx::PgPool;
+use uuid::Uuid;
 
 use crate::{
-    authentication::UserId, domain::SubscriberEmail, email_client::EmailClient,
-    routes::error_chain_fmt, utils::see_other,
+    audit::{record_audit_event, AuditAction},
+    authentication::UserId,
+    domain::SubscriberEmail,
+    email_client::EmailClient,
+    lists::{all_lists, get_list},
+    preferences::PreferenceLinks,
+    routes::error_chain_fmt,
+    segments::{get_segment, segment_recipients, SegmentDefinition},
+    tracking::{Tracker, TrackingOptions},
+    utils::see_other,
 };
 
 #[derive(serde::Deserialize)]
@@ -13,9 +24,19 @@ pub struct NewsletterForm {
     pub title: String,
     pub html_content: String,
     pub content: String,
+    /// Checkboxes, only sent when ticked.
+    pub track_opens: Option<String>,
+    pub track_clicks: Option<String>,
+    /// Everyone when left empty.
+    #[serde(default)]
+    pub segment_id: String,
+    /// Optional when there is a single list.
+    #[serde(default)]
+    pub list_id: String,
 }
 
 struct ConfirmedSubscriber {
+    id: Uuid,
     email: SubscriberEmail,
 }
 
@@ -51,25 +72,90 @@ impl ResponseError for PublishError {
 }
 
 pub async fn send_newsletter(
+    request: HttpRequest,
     user_id: web::ReqData<UserId>,
     form: web::Form<NewsletterForm>,
     email_client: web::Data<EmailClient>,
     pool: web::Data<PgPool>,
+    tracker: web::Data<Tracker>,
+    preference_links: web::Data<PreferenceLinks>,
 ) -> Result<HttpResponse, PublishError> {
     let user_id = user_id.into_inner();
     let form = form.into_inner();
-    tracing::Span::current().record("user_id", &tracing::field::display(user_id));
+    tracing::Span::current().record("user_id", tracing::field::display(user_id));
 
-    let confirmed_subscribers = gets_confirmed_subscriber(&pool).await?;
+    let tracking = TrackingOptions {
+        opens: tracker.enabled() && form.track_opens.is_some(),
+        clicks: tracker.enabled() && form.track_clicks.is_some(),
+    };
+    let list = match form.list_id.trim() {
+        "" => {
+            let mut lists = all_lists(&pool).await?;
+            if lists.len() == 1 {
+                lists.pop()
+            } else {
+                None
+            }
+        }
+        list_id => match Uuid::parse_str(list_id) {
+            Ok(list_id) => get_list(&pool, list_id).await?,
+            Err(_) => None,
+        },
+    };
+    let Some(list) = list else {
+        FlashMessage::error("Choose one of the lists to send this issue to.").send();
+        return Ok(see_other("/admin/newsletter"));
+    };
+    let segment = match form.segment_id.trim() {
+        "" => None,
+        segment_id => {
+            let segment = match Uuid::parse_str(segment_id) {
+                Ok(segment_id) => get_segment(&pool, segment_id).await?,
+                Err(_) => None,
+            };
+            let Some(segment) = segment else {
+                FlashMessage::error("The segment this issue was addressed to does not exist.")
+                    .send();
+                return Ok(see_other("/admin/newsletter"));
+            };
+            Some(segment)
+        }
+    };
+    let issue_id = insert_newsletter_issue(
+        &pool,
+        *user_id,
+        &form,
+        list.list_id,
+        tracking,
+        segment.as_ref().map(|segment| segment.segment_id),
+    )
+    .await?;
+    let definition = segment
+        .map(|segment| segment.definition)
+        .unwrap_or_default();
+    let confirmed_subscribers = gets_confirmed_subscriber(&pool, list.list_id, &definition).await?;
+    let mut delivered = 0;
+    let mut failed = 0;
     for confirmed_subscriber in confirmed_subscribers {
         match confirmed_subscriber {
             Ok(confirmed_subscriber) => {
-                let _ = email_client
+                let html_content = tracker.instrument_html(
+                    &form.html_content,
+                    issue_id,
+                    confirmed_subscriber.id,
+                    tracking,
+                );
+                let (html_content, text_content) = preference_links.add_footer(
+                    &html_content,
+                    &form.content,
+                    confirmed_subscriber.id,
+                );
+                let outcome = email_client
                     .send_email(
                         &confirmed_subscriber.email,
                         &form.title,
-                        &form.html_content,
-                        &form.content,
+                        &html_content,
+                        &text_content,
                     )
                     .await
                     .with_context(|| {
@@ -78,34 +164,123 @@ pub async fn send_newsletter(
                             confirmed_subscriber.email
                         )
                     });
+                match &outcome {
+                    Ok(()) => delivered += 1,
+                    Err(e) => {
+                        tracing::warn!(error.cause_chain = ?e, "Failed to deliver newsletter issue");
+                        failed += 1;
+                    }
+                }
+                // The email is out either way: losing its statistics must
+                // not abort the rest of the send.
+                if let Err(e) = record_delivery(
+                    &pool,
+                    issue_id,
+                    confirmed_subscriber.email.as_ref(),
+                    outcome.is_ok(),
+                )
+                .await
+                {
+                    tracing::error!(error.cause_chain = ?e, "Failed to record newsletter delivery");
+                }
             }
             Err(error) => {
                 tracing::warn!("Failed to retrieve confirmed subscriber: {:?}", error);
             }
         }
     }
+    record_audit_event(
+        &pool,
+        &request,
+        Some(*user_id),
+        AuditAction::NewsletterSent,
+        Some(&form.title),
+        serde_json::json!({
+            "newsletter_issue_id": issue_id,
+            "list_id": list.list_id,
+            "delivered": delivered,
+            "failed": failed,
+        }),
+    )
+    .await?;
     Ok(see_other("/admin/dashboard"))
 }
 
-#[tracing::instrument(name = "Get confirmed subscribers", skip(pool))]
-async fn gets_confirmed_subscriber(
+#[tracing::instrument(name = "Store newsletter issue", skip(pool, form))]
+async fn insert_newsletter_issue(
     pool: &PgPool,
-) -> Result<Vec<Result<ConfirmedSubscriber, anyhow::Error>>, anyhow::Error> {
-    let rows = sqlx::query!(
+    published_by: Uuid,
+    form: &NewsletterForm,
+    list_id: Uuid,
+    tracking: TrackingOptions,
+    segment_id: Option<Uuid>,
+) -> Result<Uuid, anyhow::Error> {
+    let newsletter_issue_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issues (
+            newsletter_issue_id, title, text_content, html_content, published_at, published_by,
+            track_opens, track_clicks, segment_id, list_id
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        "#,
+        newsletter_issue_id,
+        form.title,
+        form.content,
+        form.html_content,
+        Utc::now(),
+        published_by,
+        tracking.opens,
+        tracking.clicks,
+        segment_id,
+        list_id,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store newsletter issue")?;
+    Ok(newsletter_issue_id)
+}
+
+#[tracing::instrument(name = "Record newsletter delivery", skip(pool, subscriber_email))]
+async fn record_delivery(
+    pool: &PgPool,
+    newsletter_issue_id: Uuid,
+    subscriber_email: &str,
+    delivered: bool,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
         r#"
-        SELECT email
-        FROM subscriptions
-        WHERE status = 'confirmed'
+        INSERT INTO issue_deliveries (newsletter_issue_id, subscriber_email, delivered, attempted_at)
+        VALUES ($1, $2, $3, $4)
         "#,
+        newsletter_issue_id,
+        subscriber_email,
+        delivered,
+        Utc::now(),
     )
-    .fetch_all(pool)
-    .await?
-    .into_iter()
-    .map(|row| match SubscriberEmail::parse(row.email) {
-        Ok(email) => Ok(ConfirmedSubscriber { email }),
-        Err(error) => Err(anyhow::anyhow!(error)),
-    })
-    .collect();
+    .execute(pool)
+    .await
+    .context("Failed to record newsletter delivery")?;
+    Ok(())
+}
+
+#[tracing::instrument(name = "Get confirmed subscribers", skip(pool))]
+async fn gets_confirmed_subscriber(
+    pool: &PgPool,
+    list_id: Uuid,
+    segment: &SegmentDefinition,
+) -> Result<Vec<Result<ConfirmedSubscriber, anyhow::Error>>, anyhow::Error> {
+    let rows = segment_recipients(pool, list_id, segment)
+        .await?
+        .into_iter()
+        .map(|row| match SubscriberEmail::parse(row.email) {
+            Ok(email) => Ok(ConfirmedSubscriber {
+                id: row.subscriber_id,
+                email,
+            }),
+            Err(error) => Err(anyhow::anyhow!(error)),
+        })
+        .collect();
 
     Ok(rows)
 }
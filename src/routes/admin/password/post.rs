@@ -1,12 +1,18 @@
-use actix_web::{web, Error, HttpResponse};
+use actix_web::{web, Error, HttpRequest, HttpResponse};
 use actix_web_flash_messages::FlashMessage;
 use secrecy::{ExposeSecret, Secret};
 use sqlx::PgPool;
 
 use crate::{
+    audit::{record_audit_event, AuditAction},
     authentication::UserId,
-    authentication::{self, validate_credentials, AuthError, Credentials},
+    authentication::{
+        self, validate_credentials, AuthError, Credentials, PasswordPolicy, SessionRegistry,
+    },
+    configuration::PasswordHashingSettings,
     routes::get_username,
+    session_state::TypedSession,
+    shutdown::BackgroundTasks,
     utils::{e500, see_other},
 };
 
@@ -17,10 +23,17 @@ pub struct ChangePasswordData {
     confirm_password: Secret<String>,
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn change_password(
+    request: HttpRequest,
     form: web::Form<ChangePasswordData>,
     pool: web::Data<PgPool>,
     user_id: web::ReqData<UserId>,
+    password_policy: web::Data<PasswordPolicy>,
+    hashing: web::Data<PasswordHashingSettings>,
+    registry: web::Data<SessionRegistry>,
+    session: TypedSession,
+    background_tasks: web::Data<BackgroundTasks>,
 ) -> Result<HttpResponse, Error> {
     let user_id = user_id.into_inner();
 
@@ -31,11 +44,11 @@ pub async fn change_password(
 
     let username = get_username(*user_id, &pool).await.map_err(e500)?;
     let credentials = Credentials {
-        username,
-        password: form.0.password,
+        username: username.clone(),
+        password: form.password.clone(),
     };
 
-    if let Err(e) = validate_credentials(&pool, credentials).await {
+    if let Err(e) = validate_credentials(&pool, credentials, &hashing, &background_tasks).await {
         println!("error: {:?}", e);
         return match e {
             AuthError::InvalidCredentials(_) => {
@@ -46,9 +59,32 @@ pub async fn change_password(
         };
     }
 
-    authentication::change_password(*user_id, form.0.new_password, &pool)
+    if let Err(violation) =
+        password_policy.check(&form.new_password, &username, Some(&form.password))
+    {
+        FlashMessage::error(violation.to_string()).send();
+        return Ok(see_other("/admin/password"));
+    }
+
+    authentication::change_password(*user_id, form.0.new_password, &pool, &hashing)
+        .await
+        .map_err(e500)?;
+    // Anyone else holding a session for this account is logged out.
+    let current_session_id = session.get_session_id().map_err(e500)?;
+    registry
+        .revoke_all(*user_id, current_session_id)
         .await
         .map_err(e500)?;
+    record_audit_event(
+        &pool,
+        &request,
+        Some(*user_id),
+        AuditAction::PasswordChanged,
+        Some(&username),
+        serde_json::json!({}),
+    )
+    .await
+    .map_err(e500)?;
 
     FlashMessage::error("You have successfully changed your password").send();
     Ok(see_other("/admin/dashboard"))
@@ -0,0 +1,39 @@
+use actix_web::{web, Error, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use askama::Template;
+use sqlx::PgPool;
+
+use crate::{
+    authentication::{CsrfToken, UserId},
+    segments::{segments_with_counts, Segment},
+    utils::{e500, render},
+};
+
+#[derive(Template)]
+#[template(path = "admin/segments.html")]
+struct SegmentsTemplate {
+    messages: Vec<String>,
+    csrf_token: CsrfToken,
+    /// With the number of subscribers each would reach right now, across
+    /// all lists.
+    segments: Vec<(Segment, i64)>,
+}
+
+pub async fn segments_page(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+    _user_id: web::ReqData<UserId>,
+    csrf_token: CsrfToken,
+) -> Result<HttpResponse, Error> {
+    let messages = flash_messages
+        .iter()
+        .map(|m| m.content().to_string())
+        .collect();
+    let segments = segments_with_counts(&pool, None).await.map_err(e500)?;
+
+    render(&SegmentsTemplate {
+        messages,
+        csrf_token,
+        segments,
+    })
+}
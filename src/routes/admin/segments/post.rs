@@ -0,0 +1,105 @@
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use chrono::NaiveDate;
+use sqlx::PgPool;
+
+use crate::{
+    audit::{record_audit_event, AuditAction},
+    authentication::UserId,
+    domain::Label,
+    segments::{self, SegmentDefinition},
+    utils::{e500, see_other},
+};
+
+/// Empty fields leave the condition out.
+#[derive(serde::Deserialize)]
+pub struct SegmentForm {
+    name: String,
+    #[serde(default)]
+    tag: String,
+    #[serde(default)]
+    subscribed_from: String,
+    #[serde(default)]
+    subscribed_until: String,
+    #[serde(default)]
+    source: String,
+}
+
+impl SegmentForm {
+    fn definition(&self) -> Result<SegmentDefinition, String> {
+        let label = |value: &str| {
+            Some(value)
+                .filter(|value| !value.trim().is_empty())
+                .map(Label::parse)
+                .transpose()
+        };
+        let date = |value: &str| {
+            Some(value.trim())
+                .filter(|value| !value.is_empty())
+                .map(|value| {
+                    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                        .map_err(|_| format!("{value:?} is not a date: use YYYY-MM-DD."))
+                })
+                .transpose()
+        };
+        let definition = SegmentDefinition {
+            tag: label(&self.tag)?,
+            subscribed_from: date(&self.subscribed_from)?,
+            subscribed_until: date(&self.subscribed_until)?,
+            source: label(&self.source)?,
+        };
+        if let (Some(from), Some(until)) = (definition.subscribed_from, definition.subscribed_until)
+        {
+            if from > until {
+                return Err("The sign up period ends before it starts.".to_string());
+            }
+        }
+        Ok(definition)
+    }
+}
+
+pub async fn create_segment(
+    request: HttpRequest,
+    form: web::Form<SegmentForm>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, Error> {
+    let user_id = user_id.into_inner();
+    let name = form.name.trim();
+    if name.is_empty() {
+        FlashMessage::error("A segment needs a name.").send();
+        return Ok(see_other("/admin/segments"));
+    }
+    let definition = match form.definition() {
+        Ok(definition) => definition,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/segments"));
+        }
+    };
+    if segments::list_segments(&pool)
+        .await
+        .map_err(e500)?
+        .iter()
+        .any(|segment| segment.name == name)
+    {
+        FlashMessage::error(format!("There is a segment named {name} already.")).send();
+        return Ok(see_other("/admin/segments"));
+    }
+
+    segments::create_segment(&pool, name, &definition)
+        .await
+        .map_err(e500)?;
+    record_audit_event(
+        &pool,
+        &request,
+        Some(*user_id),
+        AuditAction::SegmentCreated,
+        Some(name),
+        serde_json::json!({}),
+    )
+    .await
+    .map_err(e500)?;
+    FlashMessage::info(format!("Segment {name} created")).send();
+    Ok(see_other("/admin/segments"))
+}
@@ -0,0 +1,41 @@
+use actix_web::{web, Error, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use askama::Template;
+use sqlx::PgPool;
+
+use crate::{
+    authentication::{CsrfToken, UserId},
+    lists::{all_lists, List},
+    sequences::{list_sequences, Sequence},
+    utils::{e500, render},
+};
+
+#[derive(Template)]
+#[template(path = "admin/sequences.html")]
+struct SequencesTemplate {
+    messages: Vec<String>,
+    csrf_token: CsrfToken,
+    sequences: Vec<Sequence>,
+    lists: Vec<List>,
+}
+
+pub async fn sequences_page(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+    _user_id: web::ReqData<UserId>,
+    csrf_token: CsrfToken,
+) -> Result<HttpResponse, Error> {
+    let messages = flash_messages
+        .iter()
+        .map(|m| m.content().to_string())
+        .collect();
+    let sequences = list_sequences(&pool).await.map_err(e500)?;
+    let lists = all_lists(&pool).await.map_err(e500)?;
+
+    render(&SequencesTemplate {
+        messages,
+        csrf_token,
+        sequences,
+        lists,
+    })
+}
@@ -0,0 +1,118 @@
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    audit::{record_audit_event, AuditAction},
+    authentication::UserId,
+    lists::get_list,
+    sequences::{self, NewStep},
+    utils::{e500, see_other},
+};
+
+/// Longest delay of a step after confirmation, a year.
+const MAX_DELAY_HOURS: i32 = 24 * 366;
+
+#[derive(serde::Deserialize)]
+pub struct SequenceForm {
+    name: String,
+    list_id: Uuid,
+}
+
+pub async fn create_sequence(
+    request: HttpRequest,
+    form: web::Form<SequenceForm>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, Error> {
+    let user_id = user_id.into_inner();
+    let name = form.name.trim();
+    if name.is_empty() {
+        FlashMessage::error("A sequence needs a name.").send();
+        return Ok(see_other("/admin/sequences"));
+    }
+    if get_list(&pool, form.list_id).await.map_err(e500)?.is_none() {
+        FlashMessage::error("The list of this sequence does not exist.").send();
+        return Ok(see_other("/admin/sequences"));
+    }
+
+    let created = sequences::create_sequence(&pool, form.list_id, name)
+        .await
+        .map_err(e500)?;
+    if created.is_none() {
+        FlashMessage::error(format!("There is a sequence named {name} already.")).send();
+        return Ok(see_other("/admin/sequences"));
+    }
+    record_audit_event(
+        &pool,
+        &request,
+        Some(*user_id),
+        AuditAction::SequenceCreated,
+        Some(name),
+        serde_json::json!({ "list_id": form.list_id }),
+    )
+    .await
+    .map_err(e500)?;
+    FlashMessage::info(format!("Sequence {name} created")).send();
+    Ok(see_other("/admin/sequences"))
+}
+
+#[derive(serde::Deserialize)]
+pub struct StepForm {
+    sequence_id: Uuid,
+    delay_hours: i32,
+    subject: String,
+    html_content: String,
+    text_content: String,
+}
+
+pub async fn add_sequence_step(
+    request: HttpRequest,
+    form: web::Form<StepForm>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, Error> {
+    let user_id = user_id.into_inner();
+    if !(0..=MAX_DELAY_HOURS).contains(&form.delay_hours) {
+        FlashMessage::error(format!(
+            "Steps go out between 0 and {MAX_DELAY_HOURS} hours after confirmation."
+        ))
+        .send();
+        return Ok(see_other("/admin/sequences"));
+    }
+    let subject = form.subject.trim();
+    if subject.is_empty()
+        || form.html_content.trim().is_empty()
+        || form.text_content.trim().is_empty()
+    {
+        FlashMessage::error("A step needs a subject and content.").send();
+        return Ok(see_other("/admin/sequences"));
+    }
+
+    let step = NewStep {
+        delay_hours: form.delay_hours,
+        subject,
+        html_content: &form.html_content,
+        text_content: &form.text_content,
+    };
+    if !sequences::add_step(&pool, form.sequence_id, &step)
+        .await
+        .map_err(e500)?
+    {
+        FlashMessage::error("The sequence of this step does not exist.").send();
+        return Ok(see_other("/admin/sequences"));
+    }
+    record_audit_event(
+        &pool,
+        &request,
+        Some(*user_id),
+        AuditAction::SequenceStepAdded,
+        Some(&form.sequence_id.to_string()),
+        serde_json::json!({ "subject": subject, "delay_hours": form.delay_hours }),
+    )
+    .await
+    .map_err(e500)?;
+    FlashMessage::info(format!("Step {subject} added")).send();
+    Ok(see_other("/admin/sequences"))
+}
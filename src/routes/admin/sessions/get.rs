@@ -0,0 +1,42 @@
+use actix_web::{web, Error, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use askama::Template;
+use uuid::Uuid;
+
+use crate::{
+    authentication::{ActiveSession, CsrfToken, SessionRegistry, UserId},
+    session_state::TypedSession,
+    utils::{e500, render},
+};
+
+#[derive(Template)]
+#[template(path = "admin/sessions.html")]
+struct SessionsTemplate {
+    messages: Vec<String>,
+    sessions: Vec<ActiveSession>,
+    current_session_id: Option<Uuid>,
+    csrf_token: CsrfToken,
+}
+
+pub async fn sessions_page(
+    flash_messages: IncomingFlashMessages,
+    registry: web::Data<SessionRegistry>,
+    session: TypedSession,
+    user_id: web::ReqData<UserId>,
+    csrf_token: CsrfToken,
+) -> Result<HttpResponse, Error> {
+    let user_id = user_id.into_inner();
+    let messages = flash_messages
+        .iter()
+        .map(|m| m.content().to_string())
+        .collect();
+    let current_session_id = session.get_session_id().map_err(e500)?;
+    let sessions = registry.list(*user_id).await.map_err(e500)?;
+
+    render(&SessionsTemplate {
+        messages,
+        sessions,
+        current_session_id,
+        csrf_token,
+    })
+}
@@ -0,0 +1,73 @@
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    audit::{record_audit_event, AuditAction},
+    authentication::{SessionRegistry, UserId},
+    session_state::TypedSession,
+    utils::{e500, see_other},
+};
+
+#[derive(serde::Deserialize)]
+pub struct RevokeSessionData {
+    session_id: Uuid,
+}
+
+pub async fn revoke_session(
+    request: HttpRequest,
+    form: web::Form<RevokeSessionData>,
+    registry: web::Data<SessionRegistry>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, Error> {
+    let user_id = user_id.into_inner();
+    if registry
+        .revoke(*user_id, form.session_id)
+        .await
+        .map_err(e500)?
+    {
+        tracing::info!(%user_id, session_id = %form.session_id, "Session revoked");
+        record_audit_event(
+            &pool,
+            &request,
+            Some(*user_id),
+            AuditAction::SessionRevoked,
+            Some(&form.session_id.to_string()),
+            serde_json::json!({}),
+        )
+        .await
+        .map_err(e500)?;
+        FlashMessage::info("The session has been revoked").send();
+    } else {
+        FlashMessage::error("Unknown session").send();
+    }
+    Ok(see_other("/admin/sessions"))
+}
+
+pub async fn revoke_all_sessions(
+    request: HttpRequest,
+    registry: web::Data<SessionRegistry>,
+    pool: web::Data<PgPool>,
+    session: TypedSession,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, Error> {
+    let user_id = user_id.into_inner();
+    registry.revoke_all(*user_id, None).await.map_err(e500)?;
+    tracing::info!(%user_id, "All sessions revoked");
+    record_audit_event(
+        &pool,
+        &request,
+        Some(*user_id),
+        AuditAction::AllSessionsRevoked,
+        None,
+        serde_json::json!({}),
+    )
+    .await
+    .map_err(e500)?;
+
+    session.log_out();
+    FlashMessage::info("You have been logged out everywhere").send();
+    Ok(see_other("/login"))
+}
@@ -0,0 +1,235 @@
+use anyhow::Context;
+use chrono::{DateTime, Days, NaiveDate, Utc};
+use sqlx::PgPool;
+
+/// Periods the dashboard can report on, in days.
+pub const PERIODS: [u64; 2] = [30, 90];
+
+pub struct StatusCount {
+    pub status: String,
+    pub count: i64,
+}
+
+pub struct DailyActivity {
+    pub day: NaiveDate,
+    pub new_subscriptions: i64,
+    pub confirmations: i64,
+}
+
+pub struct IssueSummary {
+    pub title: String,
+    pub published_at: DateTime<Utc>,
+    pub delivered: i64,
+    pub failed: i64,
+    pub track_opens: bool,
+    pub track_clicks: bool,
+    /// Subscribers who opened the issue at least once.
+    pub unique_opens: i64,
+    /// Subscribers who followed at least one of its links.
+    pub unique_clicks: i64,
+}
+
+impl IssueSummary {
+    pub fn formatted_opens(&self) -> String {
+        format_engagement(self.track_opens, self.unique_opens, self.delivered)
+    }
+
+    pub fn formatted_clicks(&self) -> String {
+        format_engagement(self.track_clicks, self.unique_clicks, self.delivered)
+    }
+}
+
+fn format_engagement(tracked: bool, count: i64, delivered: i64) -> String {
+    if tracked {
+        format!("{count} ({})", format_rate(&ratio(count, delivered)))
+    } else {
+        "not tracked".to_string()
+    }
+}
+
+pub struct DashboardStats {
+    pub period_days: u64,
+    pub status_counts: Vec<StatusCount>,
+    pub daily_activity: Vec<DailyActivity>,
+    /// Share of the subscriptions made during the period that were confirmed.
+    pub confirmation_rate: Option<f64>,
+    /// Share of the subscribers who ever confirmed that have since unsubscribed.
+    pub unsubscribe_rate: Option<f64>,
+    pub recent_issues: Vec<IssueSummary>,
+}
+
+impl DashboardStats {
+    pub fn new_subscriptions(&self) -> i64 {
+        self.daily_activity
+            .iter()
+            .map(|d| d.new_subscriptions)
+            .sum()
+    }
+
+    pub fn confirmations(&self) -> i64 {
+        self.daily_activity.iter().map(|d| d.confirmations).sum()
+    }
+
+    pub fn formatted_confirmation_rate(&self) -> String {
+        format_rate(&self.confirmation_rate)
+    }
+
+    pub fn formatted_unsubscribe_rate(&self) -> String {
+        format_rate(&self.unsubscribe_rate)
+    }
+}
+
+fn format_rate(rate: &Option<f64>) -> String {
+    match rate {
+        Some(rate) => format!("{:.1}%", rate * 100.0),
+        None => "n/a".to_string(),
+    }
+}
+
+fn ratio(numerator: i64, denominator: i64) -> Option<f64> {
+    (denominator > 0).then(|| numerator as f64 / denominator as f64)
+}
+
+#[tracing::instrument(name = "Compute dashboard statistics", skip(pool))]
+pub async fn dashboard_stats(
+    pool: &PgPool,
+    period_days: u64,
+) -> Result<DashboardStats, anyhow::Error> {
+    let status_counts = sqlx::query_as!(
+        StatusCount,
+        r#"
+        SELECT status, COUNT(*) AS "count!"
+        FROM subscriptions
+        GROUP BY status
+        ORDER BY status
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to count subscribers by status")?;
+
+    let today = Utc::now().date_naive();
+    let first_day = today
+        .checked_sub_days(Days::new(period_days - 1))
+        .context("Invalid statistics period")?;
+    let rows = sqlx::query!(
+        r#"
+        WITH days AS (
+            SELECT generate_series($1::date, $2::date, INTERVAL '1 day')::date AS day
+        ),
+        subscribed AS (
+            SELECT
+                subscribed_at::date AS day,
+                COUNT(*) AS subscriptions,
+                COUNT(*) FILTER (WHERE status <> 'pending_confirmation') AS confirmed
+            FROM subscriptions
+            WHERE subscribed_at >= $1::date
+            GROUP BY 1
+        ),
+        confirmed AS (
+            SELECT confirmed_at::date AS day, COUNT(*) AS confirmations
+            FROM subscriptions
+            WHERE confirmed_at >= $1::date
+            GROUP BY 1
+        )
+        SELECT
+            days.day AS "day!",
+            COALESCE(subscribed.subscriptions, 0) AS "new_subscriptions!",
+            COALESCE(subscribed.confirmed, 0) AS "confirmed_subscriptions!",
+            COALESCE(confirmed.confirmations, 0) AS "confirmations!"
+        FROM days
+        LEFT JOIN subscribed USING (day)
+        LEFT JOIN confirmed USING (day)
+        ORDER BY days.day
+        "#,
+        first_day,
+        today,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to compute daily subscription activity")?;
+
+    let confirmed_subscriptions = rows.iter().map(|r| r.confirmed_subscriptions).sum();
+    let daily_activity: Vec<DailyActivity> = rows
+        .into_iter()
+        .map(|r| DailyActivity {
+            day: r.day,
+            new_subscriptions: r.new_subscriptions,
+            confirmations: r.confirmations,
+        })
+        .collect();
+
+    let count = |status: &str| {
+        status_counts
+            .iter()
+            .find(|c| c.status == status)
+            .map_or(0, |c| c.count)
+    };
+    let unsubscribed = count("unsubscribed");
+    let unsubscribe_rate = ratio(unsubscribed, count("confirmed") + unsubscribed);
+
+    let recent_issues = sqlx::query_as!(
+        IssueSummary,
+        r#"
+        SELECT
+            i.title,
+            i.published_at,
+            COUNT(d.*) FILTER (WHERE d.delivered) AS "delivered!",
+            COUNT(d.*) FILTER (WHERE NOT d.delivered) AS "failed!",
+            i.track_opens,
+            i.track_clicks,
+            (
+                SELECT COUNT(DISTINCT o.subscriber_id)
+                FROM issue_opens o
+                WHERE o.newsletter_issue_id = i.newsletter_issue_id
+            ) AS "unique_opens!",
+            (
+                SELECT COUNT(DISTINCT c.subscriber_id)
+                FROM issue_clicks c
+                WHERE c.newsletter_issue_id = i.newsletter_issue_id
+            ) AS "unique_clicks!"
+        FROM newsletter_issues i
+        LEFT JOIN issue_deliveries d USING (newsletter_issue_id)
+        GROUP BY i.newsletter_issue_id
+        ORDER BY i.published_at DESC
+        LIMIT 5
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to summarise recent issues")?;
+
+    let mut stats = DashboardStats {
+        period_days,
+        status_counts,
+        daily_activity,
+        confirmation_rate: None,
+        unsubscribe_rate,
+        recent_issues,
+    };
+    stats.confirmation_rate = ratio(confirmed_subscriptions, stats.new_subscriptions());
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_engagement, format_rate, ratio};
+
+    #[test]
+    fn there_is_no_rate_without_a_denominator() {
+        assert_eq!(ratio(0, 0), None);
+        assert_eq!(format_rate(&ratio(0, 0)), "n/a");
+    }
+
+    #[test]
+    fn rates_are_shown_as_percentages() {
+        assert_eq!(format_rate(&ratio(1, 3)), "33.3%");
+        assert_eq!(format_rate(&ratio(4, 4)), "100.0%");
+    }
+
+    #[test]
+    fn engagement_is_only_shown_for_tracked_issues() {
+        assert_eq!(format_engagement(true, 1, 4), "1 (25.0%)");
+        assert_eq!(format_engagement(false, 0, 4), "not tracked");
+    }
+}
@@ -0,0 +1,110 @@
+use actix_web::{web, Error, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use askama::Template;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use crate::{
+    authentication::{CsrfToken, UserId},
+    domain::Label,
+    utils::{e500, render},
+};
+
+/// Most subscribers listed on the page.
+const PAGE_SIZE: i64 = 100;
+
+struct SubscriberRow {
+    email: String,
+    name: String,
+    list: String,
+    status: String,
+    source: Option<String>,
+    subscribed_at: DateTime<Utc>,
+    tags: Vec<String>,
+}
+
+#[derive(Template)]
+#[template(path = "admin/subscribers.html")]
+struct SubscribersTemplate {
+    messages: Vec<String>,
+    csrf_token: CsrfToken,
+    tag: Option<String>,
+    subscribers: Vec<SubscriberRow>,
+    page_size: i64,
+}
+
+#[derive(serde::Deserialize)]
+pub struct SubscribersQuery {
+    tag: Option<String>,
+}
+
+pub async fn subscribers_page(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+    _user_id: web::ReqData<UserId>,
+    csrf_token: CsrfToken,
+    query: web::Query<SubscribersQuery>,
+) -> Result<HttpResponse, Error> {
+    let mut messages: Vec<String> = flash_messages
+        .iter()
+        .map(|m| m.content().to_string())
+        .collect();
+    let tag = match query.tag.as_deref().filter(|tag| !tag.trim().is_empty()) {
+        Some(tag) => match Label::parse(tag) {
+            Ok(tag) => Some(tag),
+            Err(e) => {
+                messages.push(e);
+                None
+            }
+        },
+        None => None,
+    };
+    let subscribers = list_subscribers(&pool, tag.as_ref()).await.map_err(e500)?;
+
+    render(&SubscribersTemplate {
+        messages,
+        csrf_token,
+        tag: tag.map(|tag| tag.to_string()),
+        subscribers,
+        page_size: PAGE_SIZE,
+    })
+}
+
+#[tracing::instrument(name = "List subscribers", skip(pool))]
+async fn list_subscribers(
+    pool: &PgPool,
+    tag: Option<&Label>,
+) -> Result<Vec<SubscriberRow>, anyhow::Error> {
+    let subscribers = sqlx::query_as!(
+        SubscriberRow,
+        r#"
+        SELECT
+            s.email,
+            s.name,
+            l.name AS list,
+            s.status,
+            s.source,
+            s.subscribed_at,
+            ARRAY(
+                SELECT t.name FROM subscriber_tags st JOIN tags t USING (tag_id)
+                WHERE st.subscriber_id = s.id
+                ORDER BY t.name
+            ) AS "tags!"
+        FROM subscriptions s
+        JOIN lists l USING (list_id)
+        WHERE $1::text IS NULL OR EXISTS (
+            SELECT 1 FROM subscriber_tags st JOIN tags t USING (tag_id)
+            WHERE st.subscriber_id = s.id AND t.name = $1
+        )
+        ORDER BY s.subscribed_at DESC
+        LIMIT $2
+        "#,
+        tag.map(|tag| tag.as_ref()),
+        PAGE_SIZE,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to list subscribers")?;
+    Ok(subscribers)
+}
@@ -0,0 +1,5 @@
+mod get;
+mod post;
+
+pub use get::subscribers_page;
+pub use post::change_subscriber_tag;
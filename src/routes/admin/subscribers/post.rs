@@ -0,0 +1,78 @@
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+
+use crate::{
+    audit::{record_audit_event, AuditAction},
+    authentication::UserId,
+    domain::{Label, SubscriberEmail},
+    segments::{tag_subscriber, untag_subscriber},
+    utils::{e500, see_other},
+};
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TagAction {
+    Add,
+    Remove,
+}
+
+#[derive(serde::Deserialize)]
+pub struct SubscriberTagForm {
+    email: String,
+    tag: String,
+    action: TagAction,
+}
+
+pub async fn change_subscriber_tag(
+    request: HttpRequest,
+    form: web::Form<SubscriberTagForm>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, Error> {
+    let user_id = user_id.into_inner();
+    let form = form.into_inner();
+    let parsed = SubscriberEmail::parse(form.email).and_then(|email| {
+        let tag = Label::parse(&form.tag)?;
+        Ok((email, tag))
+    });
+    let (email, tag) = match parsed {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/subscribers"));
+        }
+    };
+
+    let changed = match form.action {
+        TagAction::Add => tag_subscriber(&pool, &email, &tag).await,
+        TagAction::Remove => untag_subscriber(&pool, &email, &tag).await,
+    }
+    .map_err(e500)?;
+    if changed {
+        let action = match form.action {
+            TagAction::Add => "add",
+            TagAction::Remove => "remove",
+        };
+        record_audit_event(
+            &pool,
+            &request,
+            Some(*user_id),
+            AuditAction::SubscriberTagsChanged,
+            Some(email.as_ref()),
+            serde_json::json!({ "tag": tag.as_ref(), "action": action }),
+        )
+        .await
+        .map_err(e500)?;
+    }
+    let message = match (form.action, changed) {
+        (TagAction::Add, true) => format!("Tagged {email} with {tag}"),
+        (TagAction::Remove, true) => format!("Removed {tag} from {email}"),
+        (TagAction::Add, false) => {
+            format!("{email} is not a subscriber, or is tagged {tag} already")
+        }
+        (TagAction::Remove, false) => format!("{email} is not tagged {tag}"),
+    };
+    FlashMessage::info(message).send();
+    Ok(see_other("/admin/subscribers"))
+}
@@ -0,0 +1,75 @@
+use actix_web::{web, Error, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use askama::Template;
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+
+use crate::{
+    authentication::{
+        generate_totp_secret, get_totp_secret, totp_qr_code_svg, totp_url, CsrfToken, UserId,
+    },
+    routes::get_username,
+    session_state::TypedSession,
+    utils::{e500, render},
+};
+
+#[derive(Template)]
+#[template(path = "admin/totp_enabled.html")]
+struct TotpEnabledTemplate {
+    messages: Vec<String>,
+}
+
+#[derive(Template)]
+#[template(path = "admin/totp_enrollment.html")]
+struct TotpEnrollmentTemplate<'a> {
+    messages: Vec<String>,
+    /// Inline SVG, rendered without escaping.
+    qr_code: String,
+    secret: &'a str,
+    url: String,
+    csrf_token: CsrfToken,
+}
+
+pub async fn totp_enrollment_form(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+    session: TypedSession,
+    user_id: web::ReqData<UserId>,
+    csrf_token: CsrfToken,
+) -> Result<HttpResponse, Error> {
+    let user_id = user_id.into_inner();
+    let messages = flash_messages
+        .iter()
+        .map(|m| m.content().to_string())
+        .collect();
+
+    if get_totp_secret(*user_id, &pool)
+        .await
+        .map_err(e500)?
+        .is_some()
+    {
+        return render(&TotpEnabledTemplate { messages });
+    }
+
+    let secret = match session.get_totp_enrollment_secret().map_err(e500)? {
+        Some(secret) => secret,
+        None => {
+            let secret = generate_totp_secret();
+            session
+                .insert_totp_enrollment_secret(&secret)
+                .map_err(e500)?;
+            secret
+        }
+    };
+    let username = get_username(*user_id, &pool).await.map_err(e500)?;
+    let url = totp_url(&secret, &username).map_err(e500)?;
+    let qr_code = totp_qr_code_svg(&secret, &username).map_err(e500)?;
+
+    render(&TotpEnrollmentTemplate {
+        messages,
+        qr_code,
+        secret: secret.expose_secret(),
+        url,
+        csrf_token,
+    })
+}
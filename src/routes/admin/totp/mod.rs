@@ -0,0 +1,5 @@
+mod get;
+mod post;
+
+pub use get::totp_enrollment_form;
+pub use post::enable_totp;
@@ -0,0 +1,62 @@
+use actix_web::{web, Error, HttpRequest, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use askama::Template;
+use sqlx::PgPool;
+
+use crate::{
+    audit::{record_audit_event, AuditAction},
+    authentication::{self, verify_totp_code, UserId},
+    session_state::TypedSession,
+    utils::{e500, render, see_other},
+};
+
+#[derive(Template)]
+#[template(path = "admin/recovery_codes.html")]
+struct RecoveryCodesTemplate {
+    recovery_codes: Vec<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct EnableTotpData {
+    code: String,
+}
+
+pub async fn enable_totp(
+    request: HttpRequest,
+    form: web::Form<EnableTotpData>,
+    pool: web::Data<PgPool>,
+    session: TypedSession,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, Error> {
+    let user_id = user_id.into_inner();
+
+    let secret = match session.get_totp_enrollment_secret().map_err(e500)? {
+        Some(secret) => secret,
+        None => {
+            FlashMessage::error("Your enrollment has expired, please try again").send();
+            return Ok(see_other("/admin/totp"));
+        }
+    };
+
+    if !verify_totp_code(&secret, &form.code).map_err(e500)? {
+        FlashMessage::error("Invalid authentication code").send();
+        return Ok(see_other("/admin/totp"));
+    }
+
+    let recovery_codes = authentication::enable_totp(*user_id, secret, &pool)
+        .await
+        .map_err(e500)?;
+    session.remove_totp_enrollment_secret();
+    record_audit_event(
+        &pool,
+        &request,
+        Some(*user_id),
+        AuditAction::TotpEnabled,
+        None,
+        serde_json::json!({}),
+    )
+    .await
+    .map_err(e500)?;
+
+    render(&RecoveryCodesTemplate { recovery_codes })
+}
@@ -0,0 +1,128 @@
+use actix_web::{
+    http::{
+        header::{HeaderName, AUTHORIZATION, WWW_AUTHENTICATE},
+        StatusCode,
+    },
+    web, HttpRequest, HttpResponse, ResponseError,
+};
+use base64::Engine;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::PgPool;
+use subtle::ConstantTimeEq;
+
+use crate::{
+    configuration::EmailWebhookSettings,
+    email_events::{apply_email_event, EmailEvent},
+    routes::error_chain_fmt,
+};
+
+/// Alternative to basic auth for providers that can send custom headers.
+const WEBHOOK_SECRET_HEADER: HeaderName = HeaderName::from_static("x-webhook-secret");
+
+#[derive(thiserror::Error)]
+pub enum WebhookError {
+    #[error("Not found")]
+    NotConfigured,
+    #[error("Invalid webhook credentials")]
+    Unauthorized,
+    #[error("Invalid event payload: {0}")]
+    InvalidPayload(serde_json::Error),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for WebhookError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for WebhookError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            WebhookError::NotConfigured => StatusCode::NOT_FOUND,
+            WebhookError::Unauthorized => StatusCode::UNAUTHORIZED,
+            WebhookError::InvalidPayload(_) => StatusCode::BAD_REQUEST,
+            WebhookError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code());
+        if let WebhookError::Unauthorized = self {
+            response.insert_header((WWW_AUTHENTICATE, r#"Basic realm="webhooks""#));
+        }
+        // `render_errors` fills in the body.
+        response.finish()
+    }
+}
+
+/// Bounce, spam complaint and delivery events from the email provider.
+#[tracing::instrument(name = "Receive email event", skip_all)]
+pub async fn email_webhook(
+    request: HttpRequest,
+    body: web::Bytes,
+    pool: web::Data<PgPool>,
+    settings: web::Data<EmailWebhookSettings>,
+) -> Result<HttpResponse, WebhookError> {
+    if settings.basic_auth.is_none() && settings.shared_secret.is_none() {
+        return Err(WebhookError::NotConfigured);
+    }
+    if !is_authorized(&request, &settings) {
+        return Err(WebhookError::Unauthorized);
+    }
+    let Some(event) = EmailEvent::from_postmark(&body).map_err(WebhookError::InvalidPayload)?
+    else {
+        return Ok(HttpResponse::Ok().finish());
+    };
+    let payload = serde_json::from_slice(&body).map_err(WebhookError::InvalidPayload)?;
+    let applied = apply_email_event(&pool, &event, payload, settings.soft_bounce_threshold).await?;
+    if !applied {
+        tracing::info!(event.key, "Ignoring an email event received before");
+    }
+    Ok(HttpResponse::Ok().finish())
+}
+
+fn is_authorized(request: &HttpRequest, settings: &EmailWebhookSettings) -> bool {
+    let header = |name| {
+        request
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    };
+    let secret_matches = settings.shared_secret.as_ref().is_some_and(|expected| {
+        header(WEBHOOK_SECRET_HEADER).is_some_and(|secret| constant_time_eq(secret, expected))
+    });
+    let credentials_match = settings.basic_auth.as_ref().is_some_and(|expected| {
+        header(AUTHORIZATION)
+            .and_then(basic_credentials)
+            .is_some_and(|(username, password)| {
+                // Both are compared, so that the time taken does not tell
+                // which one was wrong.
+                let username_matches = username.as_bytes().ct_eq(expected.username.as_bytes());
+                let password_matches = password
+                    .as_bytes()
+                    .ct_eq(expected.password.expose_secret().as_bytes());
+                (username_matches & password_matches).into()
+            })
+    });
+    secret_matches || credentials_match
+}
+
+fn constant_time_eq(given: &str, expected: &Secret<String>) -> bool {
+    given
+        .as_bytes()
+        .ct_eq(expected.expose_secret().as_bytes())
+        .into()
+}
+
+/// The username and password of a `Basic` authorization header.
+fn basic_credentials(header: &str) -> Option<(String, String)> {
+    let encoded = header.strip_prefix("Basic ")?;
+    let decoded = base64::engine::general_purpose::STANDARD
+        .decode(encoded.trim())
+        .ok()?;
+    let decoded = String::from_utf8(decoded).ok()?;
+    let (username, password) = decoded.split_once(':')?;
+    Some((username.to_string(), password.to_string()))
+}
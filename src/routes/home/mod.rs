@@ -1,7 +1,62 @@
-use actix_web::{HttpResponse, Responder};
+use actix_web::{web, HttpRequest, HttpResponse};
+use askama::Template;
+use sqlx::PgPool;
 
-pub async fn home() -> impl Responder {
-    HttpResponse::Ok()
-        .content_type("text/html; charset=utf-8")
-        .body(include_str!("home.html"))
+use crate::{
+    bot_protection::BotProtection,
+    configuration::LocalizationSettings,
+    domain::{Label, Locale},
+    lists::{all_lists, List},
+    utils::{accept_language, e500, render},
+};
+
+#[derive(Template)]
+#[template(path = "home.html")]
+struct HomeTemplate {
+    locale: Locale,
+    form_token: String,
+    source: Option<Label>,
+    lists: Vec<List>,
+    /// The slug of the list picked by the link to the form.
+    selected: Option<Label>,
+}
+
+impl HomeTemplate {
+    fn is_selected(&self, list: &List) -> bool {
+        self.selected.as_ref() == Some(&list.slug)
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct HomeQuery {
+    /// Set by links to the form, e.g. from a campaign, and recorded on the
+    /// subscriptions made from it.
+    source: Option<String>,
+    /// Which list to preselect.
+    list: Option<String>,
+    /// Overrides the language the browser asks for.
+    locale: Option<String>,
+}
+
+pub async fn home(
+    request: HttpRequest,
+    bot_protection: web::Data<BotProtection>,
+    localization: web::Data<LocalizationSettings>,
+    query: web::Query<HomeQuery>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let lists = all_lists(&pool).await.map_err(e500)?;
+    render(&HomeTemplate {
+        locale: localization.negotiate(query.locale.as_deref(), accept_language(&request)),
+        form_token: bot_protection.form_token(),
+        source: query
+            .source
+            .as_deref()
+            .and_then(|source| Label::parse(source).ok()),
+        lists,
+        selected: query
+            .list
+            .as_deref()
+            .and_then(|list| Label::parse(list).ok()),
+    })
 }
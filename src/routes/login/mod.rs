@@ -1,49 +1,36 @@
 use actix_web::{
     cookie::{time, Cookie},
-    HttpResponse, Responder,
+    HttpResponse,
 };
-use std::fmt::Write;
+use actix_web_flash_messages::{IncomingFlashMessages, Level};
+use askama::Template;
 
 mod post;
-use actix_web_flash_messages::{IncomingFlashMessages, Level};
+mod totp;
 pub use post::*;
+pub use totp::*;
+
+use crate::utils::render;
+
+#[derive(Template)]
+#[template(path = "login/login.html")]
+struct LoginTemplate {
+    messages: Vec<String>,
+}
 
-pub async fn login_form(flash_message: IncomingFlashMessages) -> impl Responder {
-    let mut error_message = String::new();
-    for m in flash_message.iter().filter(|m| m.level() == Level::Error) {
-        writeln!(error_message, "<p><i>{}</i></p>", m.content()).unwrap();
-    }
-    HttpResponse::Ok()
-        .content_type("text/html; charset=utf-8")
-        .cookie(
-            Cookie::build("_flash", "")
-                .max_age(time::Duration::ZERO)
-                .finish(),
-        )
-        .body(format!(
-            r##"
-            <!doctype html>
-            <html lang="en">
-                <head>
-                    <meta charset="UTF-8" />
-                    <title>Login</title>
-                    <meta http-equiv="content-type" content="text/html; charset=utf-8" />
-                </head>
-                <body>
-                    {error_message}
-                    <form action="/login" method="post">
-                        <label>
-                            Username
-                            <input type="text" name="username" />
-                        </label>
-                        <label>
-                            Password
-                            <input type="password" name="password" />
-                        </label>
-                        <input type="submit" value="Login" />
-                    </form>
-                </body>
-            </html
-            "##
-        ))
+pub async fn login_form(
+    flash_message: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let messages = flash_message
+        .iter()
+        .filter(|m| m.level() == Level::Error)
+        .map(|m| m.content().to_string())
+        .collect();
+    let mut response = render(&LoginTemplate { messages })?;
+    response.add_cookie(
+        &Cookie::build("_flash", "")
+            .max_age(time::Duration::ZERO)
+            .finish(),
+    )?;
+    Ok(response)
 }
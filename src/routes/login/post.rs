@@ -2,16 +2,23 @@ use actix_web::{
     error::InternalError,
     http::header::LOCATION,
     web::{self, Data, Form},
-    HttpResponse,
+    HttpRequest, HttpResponse,
 };
 use actix_web_flash_messages::FlashMessage;
 use secrecy::Secret;
 use sqlx::PgPool;
 
 use crate::{
-    authentication::{validate_credentials, AuthError, Credentials, UserId},
+    audit::{record_audit_event, AuditAction},
+    authentication::{
+        get_totp_secret, validate_credentials, AuthError, Credentials, LoginThrottle,
+        SessionRegistry, ThrottleStatus, UserId,
+    },
+    configuration::PasswordHashingSettings,
     routes::error_chain_fmt,
     session_state::TypedSession,
+    shutdown::BackgroundTasks,
+    utils::{client_ip, e500},
 };
 
 #[derive(serde::Deserialize)]
@@ -34,29 +41,109 @@ impl std::fmt::Debug for LoginError {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn login(
+    request: HttpRequest,
     Form(input): Form<LoginFormData>,
     pool: Data<PgPool>,
     session: TypedSession,
+    throttle: Data<LoginThrottle>,
+    hashing: Data<PasswordHashingSettings>,
+    registry: Data<SessionRegistry>,
+    background_tasks: Data<BackgroundTasks>,
 ) -> Result<HttpResponse, InternalError<LoginError>> {
+    let ip = client_ip(&request);
+    let username = input.username;
+
+    // A locked out user gets the same generic error as a wrong password.
+    match throttle
+        .check(&username, &ip)
+        .await
+        .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?
+    {
+        ThrottleStatus::Locked => {
+            record_audit_event(
+                &pool,
+                &request,
+                None,
+                AuditAction::LoginFailed,
+                Some(&username),
+                serde_json::json!({ "reason": "locked_out" }),
+            )
+            .await
+            .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+            let e = anyhow::anyhow!("Too many failed login attempts");
+            return Err(login_redirect(LoginError::AuthError(e)));
+        }
+        ThrottleStatus::Allowed { delay } => tokio::time::sleep(delay).await,
+    }
+
     let creds = Credentials {
-        username: input.username,
+        username: username.clone(),
         password: input.password,
     };
-    match validate_credentials(&pool, creds).await {
+    match validate_credentials(&pool, creds, &hashing, &background_tasks).await {
         Ok(user_id) => {
-            tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
+            tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+            let totp_secret = get_totp_secret(user_id, &pool)
+                .await
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
             session.renew();
+            if totp_secret.is_some() {
+                session
+                    .insert_pending_totp(user_id, username)
+                    .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+                return Ok(HttpResponse::SeeOther()
+                    .insert_header((LOCATION, "/login/totp"))
+                    .finish());
+            }
+            let session_id = registry
+                .register(user_id, &request)
+                .await
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
             session
                 .insert_user_id(user_id)
                 .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+            session
+                .insert_session_id(session_id)
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+            throttle
+                .record_success(&username)
+                .await
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+            record_audit_event(
+                &pool,
+                &request,
+                Some(user_id),
+                AuditAction::LoginSucceeded,
+                Some(&username),
+                serde_json::json!({}),
+            )
+            .await
+            .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
             Ok(HttpResponse::SeeOther()
                 .insert_header((LOCATION, "/admin/dashboard"))
                 .finish())
         }
         Err(e) => {
             let e = match e {
-                AuthError::InvalidCredentials(_) => LoginError::AuthError(e.into()),
+                AuthError::InvalidCredentials(_) => {
+                    throttle
+                        .record_failure(&username, &ip)
+                        .await
+                        .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+                    record_audit_event(
+                        &pool,
+                        &request,
+                        None,
+                        AuditAction::LoginFailed,
+                        Some(&username),
+                        serde_json::json!({ "reason": "invalid_credentials" }),
+                    )
+                    .await
+                    .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+                    LoginError::AuthError(e.into())
+                }
                 AuthError::UnexpectedError(_) => LoginError::UnexpectedError(e.into()),
             };
             Err(login_redirect(e))
@@ -73,10 +160,26 @@ fn login_redirect(e: LoginError) -> InternalError<LoginError> {
 }
 
 pub async fn logout(
+    request: HttpRequest,
     session: TypedSession,
     user_id: web::ReqData<UserId>,
+    registry: Data<SessionRegistry>,
+    pool: Data<PgPool>,
 ) -> Result<HttpResponse, actix_web::Error> {
-    let _user_id = user_id.into_inner();
+    let user_id = user_id.into_inner();
+    if let Some(session_id) = session.get_session_id().map_err(e500)? {
+        registry.revoke(*user_id, session_id).await.map_err(e500)?;
+    }
+    record_audit_event(
+        &pool,
+        &request,
+        Some(*user_id),
+        AuditAction::Logout,
+        None,
+        serde_json::json!({}),
+    )
+    .await
+    .map_err(e500)?;
     session.log_out();
     FlashMessage::error("You have successfully logged out").send();
     Ok(HttpResponse::SeeOther()
@@ -0,0 +1,149 @@
+use actix_web::{
+    cookie::{time, Cookie},
+    web::{Data, Form},
+    HttpRequest, HttpResponse,
+};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages, Level};
+use askama::Template;
+use sqlx::PgPool;
+
+use crate::{
+    audit::{record_audit_event, AuditAction},
+    authentication::{
+        get_totp_secret, use_recovery_code, use_totp_code, LoginThrottle, SessionRegistry,
+        ThrottleStatus,
+    },
+    session_state::TypedSession,
+    utils::{client_ip, e500, render, see_other},
+};
+
+#[derive(Template)]
+#[template(path = "login/totp.html")]
+struct LoginTotpTemplate {
+    messages: Vec<String>,
+}
+
+pub async fn login_totp_form(
+    flash_message: IncomingFlashMessages,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    if session.get_pending_totp().map_err(e500)?.is_none() {
+        return Ok(see_other("/login"));
+    }
+
+    let messages = flash_message
+        .iter()
+        .filter(|m| m.level() == Level::Error)
+        .map(|m| m.content().to_string())
+        .collect();
+    let mut response = render(&LoginTotpTemplate { messages })?;
+    response.add_cookie(
+        &Cookie::build("_flash", "")
+            .max_age(time::Duration::ZERO)
+            .finish(),
+    )?;
+    Ok(response)
+}
+
+#[derive(serde::Deserialize)]
+pub struct TotpFormData {
+    code: String,
+}
+
+#[tracing::instrument(
+    name = "Verify second factor",
+    skip(request, form, pool, session, registry, throttle)
+)]
+pub async fn login_totp(
+    request: HttpRequest,
+    Form(form): Form<TotpFormData>,
+    pool: Data<PgPool>,
+    session: TypedSession,
+    registry: Data<SessionRegistry>,
+    throttle: Data<LoginThrottle>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(pending) = session.get_pending_totp().map_err(e500)? else {
+        return Ok(see_other("/login"));
+    };
+    let user_id = pending.user_id;
+    let ip = client_ip(&request);
+
+    // Codes are throttled like passwords. Once locked out, the password has
+    // to be entered again.
+    match throttle.check(&pending.username, &ip).await.map_err(e500)? {
+        ThrottleStatus::Locked => {
+            session.remove_pending_totp();
+            record_audit_event(
+                &pool,
+                &request,
+                Some(user_id),
+                AuditAction::LoginFailed,
+                Some(&pending.username),
+                serde_json::json!({ "reason": "locked_out" }),
+            )
+            .await
+            .map_err(e500)?;
+            FlashMessage::error("Authentication failed").send();
+            return Ok(see_other("/login"));
+        }
+        ThrottleStatus::Allowed { delay } => tokio::time::sleep(delay).await,
+    }
+
+    let secret = get_totp_secret(user_id, &pool)
+        .await
+        .map_err(e500)?
+        .ok_or_else(|| e500("Two-factor authentication is not enabled for this user"))?;
+
+    let second_factor = if use_totp_code(user_id, &secret, &form.code, &pool)
+        .await
+        .map_err(e500)?
+    {
+        Some("totp")
+    } else if use_recovery_code(user_id, &form.code, &pool)
+        .await
+        .map_err(e500)?
+    {
+        Some("recovery_code")
+    } else {
+        None
+    };
+    let Some(second_factor) = second_factor else {
+        throttle
+            .record_failure(&pending.username, &ip)
+            .await
+            .map_err(e500)?;
+        record_audit_event(
+            &pool,
+            &request,
+            Some(user_id),
+            AuditAction::LoginFailed,
+            Some(&pending.username),
+            serde_json::json!({ "reason": "invalid_second_factor" }),
+        )
+        .await
+        .map_err(e500)?;
+        FlashMessage::error("Invalid authentication code").send();
+        return Ok(see_other("/login/totp"));
+    };
+
+    let session_id = registry.register(user_id, &request).await.map_err(e500)?;
+    session.renew();
+    session.remove_pending_totp();
+    session.insert_user_id(user_id).map_err(e500)?;
+    session.insert_session_id(session_id).map_err(e500)?;
+    throttle
+        .record_success(&pending.username)
+        .await
+        .map_err(e500)?;
+    record_audit_event(
+        &pool,
+        &request,
+        Some(user_id),
+        AuditAction::LoginSucceeded,
+        Some(&pending.username),
+        serde_json::json!({ "second_factor": second_factor }),
+    )
+    .await
+    .map_err(e500)?;
+    Ok(see_other("/admin/dashboard"))
+}
@@ -0,0 +1,41 @@
+use actix_web::{
+    http::header::{AUTHORIZATION, WWW_AUTHENTICATE},
+    web, HttpRequest, HttpResponse,
+};
+use secrecy::ExposeSecret;
+use sqlx::PgPool;
+use subtle::ConstantTimeEq;
+
+use crate::{configuration::MetricsSettings, metrics::metrics};
+
+/// Prometheus scrape endpoint.
+pub async fn metrics_endpoint(
+    request: HttpRequest,
+    pool: web::Data<PgPool>,
+    settings: web::Data<MetricsSettings>,
+) -> HttpResponse {
+    if !settings.enabled {
+        return HttpResponse::NotFound().finish();
+    }
+    if let Some(expected) = &settings.bearer_token {
+        let authorized = request
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .is_some_and(|token| {
+                token
+                    .as_bytes()
+                    .ct_eq(expected.expose_secret().as_bytes())
+                    .into()
+            });
+        if !authorized {
+            return HttpResponse::Unauthorized()
+                .insert_header((WWW_AUTHENTICATE, "Bearer"))
+                .finish();
+        }
+    }
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(metrics().encode(&pool))
+}
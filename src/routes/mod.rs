@@ -1,13 +1,23 @@
 mod admin;
+mod email_webhook;
 mod health_check;
 mod home;
 mod login;
+mod metrics;
+mod preferences;
+mod ready;
 mod subscriptions;
 mod subscriptions_confirm;
+mod tracking;
 
 pub use admin::*;
+pub use email_webhook::*;
 pub use health_check::*;
 pub use home::*;
 pub use login::*;
+pub use metrics::*;
+pub use preferences::*;
+pub use ready::*;
 pub use subscriptions::*;
 pub use subscriptions_confirm::*;
+pub use tracking::*;
@@ -0,0 +1,48 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+
+use crate::{
+    configuration::LocalizationSettings,
+    errors::AppError,
+    preferences::{confirm_email_change as confirm, EmailChange, PreferenceLinks},
+    utils::{accept_language, see_other},
+};
+
+#[derive(serde::Deserialize)]
+pub struct ConfirmEmailQuery {
+    token: String,
+}
+
+/// Where the link sent to a new address leads.
+#[tracing::instrument(
+    name = "Confirm email change",
+    skip(request, query, links, pool, localization)
+)]
+pub async fn confirm_email_change(
+    request: HttpRequest,
+    query: web::Query<ConfirmEmailQuery>,
+    links: web::Data<PreferenceLinks>,
+    pool: web::Data<PgPool>,
+    localization: web::Data<LocalizationSettings>,
+) -> Result<HttpResponse, AppError> {
+    let locale = || localization.negotiate(None, accept_language(&request));
+    match confirm(&pool, &query.token).await? {
+        EmailChange::Changed {
+            subscriber_id,
+            email,
+        } => {
+            FlashMessage::info(format!("Your email address is now {email}.")).send();
+            Ok(see_other(&format!(
+                "/preferences?token={}",
+                links.token(subscriber_id)
+            )))
+        }
+        EmailChange::Invalid => Err(AppError::Unauthorized(
+            locale().invalid_confirmation_link().into(),
+        )),
+        EmailChange::Taken { email } => {
+            Err(AppError::BadRequest(locale().already_subscribed(&email)))
+        }
+    }
+}
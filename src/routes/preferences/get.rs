@@ -0,0 +1,85 @@
+use actix_web::{web, HttpResponse};
+use actix_web_flash_messages::IncomingFlashMessages;
+use askama::Template;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use crate::{
+    configuration::LocalizationSettings,
+    domain::Locale,
+    errors::AppError,
+    preferences::{
+        get_subscriber, list_choices, paused_until, pending_email_change, ListChoice,
+        PreferenceLinks, Subscriber,
+    },
+    utils::{e500, render},
+};
+
+#[derive(Template)]
+#[template(path = "preferences.html")]
+struct PreferencesTemplate {
+    locale: Locale,
+    messages: Vec<String>,
+    token: String,
+    subscriber: Subscriber,
+    pending_email: Option<String>,
+    lists: Vec<ListChoice>,
+    paused_until: Option<DateTime<Utc>>,
+}
+
+impl PreferencesTemplate {
+    /// Pause lengths on offer, in weeks, with their labels.
+    fn pause_choices(&self) -> Vec<(u32, String)> {
+        [1, 2, 4, 12, 26]
+            .into_iter()
+            .map(|weeks| (weeks, self.locale.weeks(weeks)))
+            .collect()
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct PreferencesQuery {
+    token: String,
+}
+
+pub async fn preferences_page(
+    query: web::Query<PreferencesQuery>,
+    links: web::Data<PreferenceLinks>,
+    pool: web::Data<PgPool>,
+    localization: web::Data<LocalizationSettings>,
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    let token = query.into_inner().token;
+    let subscriber = subscriber_for(&pool, &links, &token).await?;
+    let messages = flash_messages
+        .iter()
+        .map(|m| m.content().to_string())
+        .collect();
+    let pending_email = pending_email_change(&pool, &subscriber.email)
+        .await
+        .map_err(e500)?;
+    let lists = list_choices(&pool, &subscriber.email).await.map_err(e500)?;
+    let paused_until = paused_until(&pool, &subscriber.email).await.map_err(e500)?;
+    render(&PreferencesTemplate {
+        locale: localization.stored(&subscriber.locale),
+        messages,
+        token,
+        subscriber,
+        pending_email,
+        lists,
+        paused_until,
+    })
+}
+
+/// Who a preference link was sent to.
+pub(super) async fn subscriber_for(
+    pool: &PgPool,
+    links: &PreferenceLinks,
+    token: &str,
+) -> Result<Subscriber, AppError> {
+    let invalid = || AppError::NotFound("This link is invalid.".into());
+    let subscriber_id = links.verify(token).ok_or_else(invalid)?;
+    get_subscriber(pool, subscriber_id)
+        .await?
+        .ok_or_else(invalid)
+}
@@ -0,0 +1,7 @@
+mod confirm_email;
+mod get;
+mod post;
+
+pub use confirm_email::confirm_email_change;
+pub use get::preferences_page;
+pub use post::*;
@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+
+use actix_web::{web, HttpRequest, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+
+use super::get::subscriber_for;
+use crate::{
+    configuration::LocalizationSettings,
+    domain::{EmailDomainPolicy, SubscriberEmail, SubscriberName},
+    email_client::EmailClient,
+    errors::AppError,
+    lists::all_lists,
+    preferences::{
+        choose_lists, pause_delivery, rename_subscriber, request_email_change, PreferenceLinks,
+    },
+    rate_limit::{RateLimitDecision, SubscribeRateLimiter},
+    routes::subscriptions::generate_subscription_token,
+    utils::{client_ip, see_other},
+};
+
+/// Longest pause on offer, in weeks.
+const MAX_PAUSE_WEEKS: i64 = 26;
+
+fn back_to(token: &str) -> HttpResponse {
+    see_other(&format!("/preferences?token={token}"))
+}
+
+#[derive(serde::Deserialize)]
+pub struct NameForm {
+    token: String,
+    name: String,
+}
+
+pub async fn change_subscriber_name(
+    form: web::Form<NameForm>,
+    links: web::Data<PreferenceLinks>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let NameForm { token, name } = form.into_inner();
+    let subscriber = subscriber_for(&pool, &links, &token).await?;
+    match SubscriberName::parse(name) {
+        Ok(name) => {
+            rename_subscriber(&pool, &subscriber.email, &name).await?;
+            FlashMessage::info("Your name was updated.").send();
+        }
+        Err(e) => FlashMessage::error(e).send(),
+    }
+    Ok(back_to(&token))
+}
+
+#[derive(serde::Deserialize)]
+pub struct EmailForm {
+    token: String,
+    email: String,
+}
+
+/// Send a confirmation link to the new address. It counts against the same
+/// rate limits and daily cap as the confirmation emails of new subscribers.
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(
+    name = "Change subscriber email",
+    skip(
+        request,
+        form,
+        links,
+        pool,
+        email_client,
+        base_url,
+        email_domain_policy,
+        rate_limiter,
+        localization
+    )
+)]
+pub async fn change_subscriber_email(
+    request: HttpRequest,
+    form: web::Form<EmailForm>,
+    links: web::Data<PreferenceLinks>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<String>,
+    email_domain_policy: web::Data<EmailDomainPolicy>,
+    rate_limiter: web::Data<SubscribeRateLimiter>,
+    localization: web::Data<LocalizationSettings>,
+) -> Result<HttpResponse, AppError> {
+    let EmailForm { token, email } = form.into_inner();
+    let subscriber = subscriber_for(&pool, &links, &token).await?;
+    let new_email = match SubscriberEmail::parse(email)
+        .and_then(|email| email_domain_policy.check(&email).map(|()| email))
+    {
+        Ok(email) => email,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(back_to(&token));
+        }
+    };
+    if new_email.as_ref().eq_ignore_ascii_case(&subscriber.email) {
+        FlashMessage::error("That is your current address.").send();
+        return Ok(back_to(&token));
+    }
+    // Whether the address is taken is only told to whoever follows the
+    // link, so that this form cannot be used to probe for subscribers.
+    if let RateLimitDecision::Limited { .. } = rate_limiter
+        .check(&client_ip(&request), new_email.as_ref())
+        .await?
+    {
+        FlashMessage::error("Too many attempts. Please try again later.").send();
+        return Ok(back_to(&token));
+    }
+    if let RateLimitDecision::Limited { .. } =
+        rate_limiter.reserve_confirmation_email(&pool).await?
+    {
+        FlashMessage::error("We cannot send more emails today. Please try again tomorrow.").send();
+        return Ok(back_to(&token));
+    }
+
+    let locale = localization.stored(&subscriber.locale);
+    let change_token = generate_subscription_token();
+    request_email_change(&pool, subscriber.subscriber_id, &new_email, &change_token).await?;
+    let confirmation_link = format!(
+        "{}/preferences/confirm-email?token={}",
+        base_url.get_ref(),
+        change_token
+    );
+    email_client
+        .send_email(
+            &new_email,
+            locale.email_change_subject(),
+            &locale.email_change_html(&confirmation_link),
+            &locale.email_change_text(&confirmation_link),
+        )
+        .await
+        .context("Failed to send email change confirmation")?;
+    FlashMessage::info(format!(
+        "We sent a confirmation link to {new_email}. Your address changes once you follow it."
+    ))
+    .send();
+    Ok(back_to(&token))
+}
+
+/// The token, and a `list.<slug>` entry for every ticked list.
+pub async fn change_subscribed_lists(
+    form: web::Form<HashMap<String, String>>,
+    links: web::Data<PreferenceLinks>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let form = form.into_inner();
+    let token = form
+        .get("token")
+        .ok_or_else(|| AppError::BadRequest("This link is invalid.".into()))?;
+    let subscriber = subscriber_for(&pool, &links, token).await?;
+    let wanted: Vec<_> = all_lists(&pool)
+        .await?
+        .into_iter()
+        .filter(|list| form.contains_key(&format!("list.{}", list.slug)))
+        .map(|list| list.list_id)
+        .collect();
+    choose_lists(&pool, &subscriber, &wanted).await?;
+    FlashMessage::info("Your lists were updated.").send();
+    Ok(back_to(token))
+}
+
+#[derive(serde::Deserialize)]
+pub struct PauseForm {
+    token: String,
+    /// Resume delivery when zero.
+    weeks: i64,
+}
+
+pub async fn pause_subscription(
+    form: web::Form<PauseForm>,
+    links: web::Data<PreferenceLinks>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let PauseForm { token, weeks } = form.into_inner();
+    let subscriber = subscriber_for(&pool, &links, &token).await?;
+    if !(0..=MAX_PAUSE_WEEKS).contains(&weeks) {
+        FlashMessage::error(format!(
+            "Delivery can be paused for up to {MAX_PAUSE_WEEKS} weeks."
+        ))
+        .send();
+        return Ok(back_to(&token));
+    }
+    if weeks == 0 {
+        pause_delivery(&pool, &subscriber.email, None).await?;
+        FlashMessage::info("Delivery resumed.").send();
+    } else {
+        let until = Utc::now() + Duration::weeks(weeks);
+        pause_delivery(&pool, &subscriber.email, Some(until)).await?;
+        FlashMessage::info(format!(
+            "Delivery is paused until {}.",
+            until.format("%Y-%m-%d")
+        ))
+        .send();
+    }
+    Ok(back_to(&token))
+}
@@ -0,0 +1,162 @@
+use std::{
+    future::Future,
+    time::{Duration, Instant},
+};
+
+use actix_web::{web, HttpResponse};
+use redis::aio::ConnectionManager;
+use sqlx::PgPool;
+
+use crate::{configuration::ReadinessSettings, email_client::EmailClient};
+
+/// The dependencies `/ready` checks, on top of the database pool.
+pub struct ReadinessProbe {
+    redis: ConnectionManager,
+    settings: ReadinessSettings,
+}
+
+impl ReadinessProbe {
+    pub fn new(redis: ConnectionManager, settings: ReadinessSettings) -> Self {
+        Self { redis, settings }
+    }
+}
+
+#[derive(serde::Serialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Up,
+    Down,
+}
+
+/// The probe is public: why a check failed is logged, not returned.
+#[derive(serde::Serialize, Debug)]
+pub struct DependencyCheck {
+    name: &'static str,
+    status: CheckStatus,
+    latency_ms: u128,
+    /// Whether the instance can serve traffic without this dependency.
+    #[serde(skip)]
+    critical: bool,
+}
+
+#[derive(serde::Serialize, Debug)]
+pub struct Readiness {
+    ready: bool,
+    checks: Vec<DependencyCheck>,
+}
+
+impl Readiness {
+    fn new(checks: Vec<DependencyCheck>) -> Self {
+        let ready = checks
+            .iter()
+            .all(|check| !check.critical || check.status == CheckStatus::Up);
+        Self { ready, checks }
+    }
+}
+
+async fn check<F>(
+    name: &'static str,
+    critical: bool,
+    timeout: Duration,
+    probe: F,
+) -> DependencyCheck
+where
+    F: Future<Output = Result<(), anyhow::Error>>,
+{
+    let started_at = Instant::now();
+    let outcome = match tokio::time::timeout(timeout, probe).await {
+        Ok(outcome) => outcome,
+        Err(_) => Err(anyhow::anyhow!("Timed out after {}ms", timeout.as_millis())),
+    };
+    let latency_ms = started_at.elapsed().as_millis();
+    let status = match outcome {
+        Ok(()) => CheckStatus::Up,
+        Err(e) => {
+            tracing::warn!(
+                error.cause_chain = ?e,
+                dependency = name,
+                latency_ms,
+                "Readiness check failed"
+            );
+            CheckStatus::Down
+        }
+    };
+    DependencyCheck {
+        name,
+        status,
+        latency_ms,
+        critical,
+    }
+}
+
+/// Readiness probe: 503 unless every critical dependency answers in time.
+/// `/health_check` remains the liveness probe.
+pub async fn ready(
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    probe: web::Data<ReadinessProbe>,
+) -> HttpResponse {
+    let timeout = probe.settings.timeout();
+    let postgres = check("postgres", true, timeout, async {
+        sqlx::query("SELECT 1").execute(pool.get_ref()).await?;
+        Ok(())
+    });
+    let redis = check("redis", true, timeout, async {
+        let mut connection = probe.redis.clone();
+        redis::cmd("PING")
+            .query_async::<_, String>(&mut connection)
+            .await?;
+        Ok(())
+    });
+    let (postgres, redis) = tokio::join!(postgres, redis);
+
+    let mut checks = vec![postgres, redis];
+    if probe.settings.check_email_provider {
+        checks.push(
+            check("email_provider", false, timeout, async {
+                email_client.ping().await?;
+                Ok(())
+            })
+            .await,
+        );
+    }
+
+    let readiness = Readiness::new(checks);
+    if readiness.ready {
+        HttpResponse::Ok().json(readiness)
+    } else {
+        HttpResponse::ServiceUnavailable().json(readiness)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CheckStatus, DependencyCheck, Readiness};
+
+    fn dependency(critical: bool, status: CheckStatus) -> DependencyCheck {
+        DependencyCheck {
+            name: "test",
+            status,
+            latency_ms: 1,
+            critical,
+        }
+    }
+
+    #[test]
+    fn a_failing_critical_dependency_is_not_ready() {
+        let readiness = Readiness::new(vec![
+            dependency(true, CheckStatus::Up),
+            dependency(true, CheckStatus::Down),
+        ]);
+        assert!(!readiness.ready);
+    }
+
+    #[test]
+    fn a_failing_optional_dependency_is_still_ready() {
+        let readiness = Readiness::new(vec![
+            dependency(true, CheckStatus::Up),
+            dependency(false, CheckStatus::Down),
+        ]);
+        assert!(readiness.ready);
+    }
+}
@@ -1,6 +1,9 @@
+use std::time::Duration;
+
 use actix_web::{
+    http::{header::RETRY_AFTER, StatusCode},
     web::{Data, Form},
-    HttpResponse, ResponseError,
+    HttpRequest, HttpResponse, ResponseError,
 };
 use anyhow::Context;
 use chrono::Utc;
@@ -10,15 +13,35 @@ use sqlx::Transaction;
 use sqlx::{PgPool, Postgres};
 use uuid::Uuid;
 
+use crate::bot_protection::{BotProtection, SubscriptionAttempt, Verdict};
+use crate::configuration::LocalizationSettings;
+use crate::confirmation_email::{template_for, ConfirmationEmail};
+use crate::domain::EmailDomainPolicy;
+use crate::domain::Label;
+use crate::domain::Locale;
 use crate::domain::NewSubscriber;
 use crate::domain::SubscriberEmail;
 use crate::domain::SubscriberName;
 use crate::email_client::EmailClient;
+use crate::lists::{resolve_list, List};
+use crate::metrics::metrics;
+use crate::rate_limit::{RateLimitDecision, SubscribeRateLimiter};
+use crate::utils::{accept_language, client_ip};
 
 #[derive(serde::Deserialize)]
 pub struct FormData {
     name: String,
     email: String,
+    /// Honeypot, hidden from people.
+    website: Option<String>,
+    /// When the form was rendered, signed.
+    form_token: Option<String>,
+    /// Where the form was linked from.
+    source: Option<String>,
+    /// The slug of the list to subscribe to, optional when there is only one.
+    list: Option<String>,
+    /// The language the form was shown in.
+    locale: Option<String>,
 }
 
 impl TryFrom<FormData> for NewSubscriber {
@@ -26,7 +49,17 @@ impl TryFrom<FormData> for NewSubscriber {
     fn try_from(value: FormData) -> Result<Self, Self::Error> {
         let name = SubscriberName::parse(value.name)?;
         let email = SubscriberEmail::parse(value.email)?;
-        Ok(NewSubscriber { email, name })
+        let source = value
+            .source
+            .as_deref()
+            .filter(|source| !source.trim().is_empty())
+            .map(Label::parse)
+            .transpose()?;
+        Ok(NewSubscriber {
+            email,
+            name,
+            source,
+        })
     }
 }
 
@@ -34,6 +67,10 @@ impl TryFrom<FormData> for NewSubscriber {
 pub enum SubscribeError {
     #[error("{0}")]
     ValidationError(String),
+    #[error("Too many subscription requests. Please try again later.")]
+    TooManyRequests { retry_after: Duration },
+    #[error("We cannot take new subscriptions right now. Please try again later.")]
+    ConfirmationEmailQuotaExhausted { retry_after: Duration },
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -51,65 +88,157 @@ impl From<String> for SubscribeError {
 }
 
 impl ResponseError for SubscribeError {
-    fn status_code(&self) -> actix_web::http::StatusCode {
+    fn status_code(&self) -> StatusCode {
         match self {
-            Self::ValidationError(_) => actix_web::http::StatusCode::BAD_REQUEST,
-            Self::UnexpectedError(_) => actix_web::http::StatusCode::INTERNAL_SERVER_ERROR,
+            Self::ValidationError(_) => StatusCode::BAD_REQUEST,
+            Self::TooManyRequests { .. } => StatusCode::TOO_MANY_REQUESTS,
+            // The cap is global: it is not this client's fault.
+            Self::ConfirmationEmailQuotaExhausted { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            Self::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status_code());
+        match self {
+            Self::TooManyRequests { retry_after }
+            | Self::ConfirmationEmailQuotaExhausted { retry_after } => {
+                // Round up, so that a client retrying right on time is let through.
+                let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+                response.insert_header((RETRY_AFTER, seconds.to_string()));
+            }
+            Self::ValidationError(_) | Self::UnexpectedError(_) => {}
+        }
+        // `render_errors` fills in the body.
+        response.finish()
+    }
 }
 
+#[allow(clippy::too_many_arguments)]
 #[tracing::instrument(
     name = "Adding a new subscriber",
-    skip(form, pool, email_client, base_url),
+    skip(
+        request,
+        form,
+        pool,
+        email_client,
+        base_url,
+        rate_limiter,
+        bot_protection,
+        email_domain_policy,
+        localization
+    ),
     fields(
         subscriber_email = %form.email,
         subscriber_name = %form.name,
     )
 )]
 pub async fn subscribe(
+    request: HttpRequest,
     Form(form): Form<FormData>,
     pool: Data<PgPool>,
     email_client: Data<EmailClient>,
     base_url: Data<String>,
+    rate_limiter: Data<SubscribeRateLimiter>,
+    bot_protection: Data<BotProtection>,
+    email_domain_policy: Data<EmailDomainPolicy>,
+    localization: Data<LocalizationSettings>,
 ) -> Result<HttpResponse, SubscribeError> {
     println!("Adding a new subscriber");
-    let new_subscriber = form.try_into().map_err(SubscribeError::ValidationError)?;
+    let ip = client_ip(&request);
+    let attempt = SubscriptionAttempt {
+        ip: &ip,
+        honeypot: form.website.as_deref(),
+        form_token: form.form_token.as_deref(),
+    };
+    // Bots get the same response as everyone else, so that they cannot
+    // tell that they have been caught.
+    if let Verdict::Bot { reason } = bot_protection.verify(&attempt).await? {
+        tracing::warn!(reason, "Dropping a subscription that looks automated");
+        return Ok(HttpResponse::Ok().finish());
+    }
+    let list_slug = form
+        .list
+        .as_deref()
+        .filter(|list| !list.trim().is_empty())
+        .map(Label::parse)
+        .transpose()
+        .map_err(SubscribeError::ValidationError)?;
+    let locale = localization.negotiate(form.locale.as_deref(), accept_language(&request));
+    let new_subscriber: NewSubscriber = form.try_into().map_err(SubscribeError::ValidationError)?;
+    email_domain_policy
+        .check(&new_subscriber.email)
+        .map_err(SubscribeError::ValidationError)?;
+    if let RateLimitDecision::Limited { retry_after } = rate_limiter
+        .check(&ip, new_subscriber.email.as_ref())
+        .await?
+    {
+        return Err(SubscribeError::TooManyRequests { retry_after });
+    }
+    let list = resolve_list(&pool, list_slug.as_ref())
+        .await?
+        .ok_or_else(|| match &list_slug {
+            Some(slug) => {
+                SubscribeError::ValidationError(format!("There is no newsletter called {slug}."))
+            }
+            None => SubscribeError::ValidationError(
+                "Please choose a newsletter to subscribe to.".into(),
+            ),
+        })?;
+    let confirmation_email = template_for(&pool, locale).await?;
     let mut transaction = pool
         .begin()
         .await
         .context("Failed to acquire a Postgres connection")?;
-    let subscriber_id = insert_subscriber(&new_subscriber, &mut transaction)
+    let subscriber_id = insert_subscriber(&new_subscriber, list.list_id, locale, &mut transaction)
         .await
         .context("Failed to insert a new subscriber")?;
     let token = generate_subscription_token();
     store_token(&mut transaction, &subscriber_id, &token)
         .await
         .context("Failed to store subscription token")?;
-    send_confirmation_email(new_subscriber, &email_client, &base_url, &token)
-        .await
-        .context("Failed to send confirmation email")?;
+    if let RateLimitDecision::Limited { retry_after } =
+        rate_limiter.reserve_confirmation_email(&pool).await?
+    {
+        return Err(SubscribeError::ConfirmationEmailQuotaExhausted { retry_after });
+    }
+    send_confirmation_email(
+        new_subscriber,
+        &list,
+        &confirmation_email,
+        &email_client,
+        &base_url,
+        &token,
+    )
+    .await
+    .context("Failed to send confirmation email")?;
     transaction
         .commit()
         .await
         .context("Failed to commit SQL transaction")?;
+    metrics().increment_subscriptions();
     Ok(HttpResponse::Ok().finish())
 }
 
 pub async fn insert_subscriber(
     new_subscriber: &NewSubscriber,
+    list_id: Uuid,
+    locale: Locale,
     pool: &mut Transaction<'_, Postgres>,
 ) -> Result<Uuid, sqlx::Error> {
     let subscriber_id = Uuid::new_v4();
     sqlx::query!(
         r#"
-    INSERT INTO subscriptions (id, email, name, subscribed_at, status)
-    VALUES ($1, $2, $3, $4, 'pending_confirmation')
+    INSERT INTO subscriptions (id, email, name, subscribed_at, status, source, list_id, locale)
+    VALUES ($1, $2, $3, $4, 'pending_confirmation', $5, $6, $7)
     "#,
         subscriber_id,
         new_subscriber.email.as_ref(),
         new_subscriber.name.as_ref(),
-        Utc::now()
+        Utc::now(),
+        new_subscriber.source.as_ref().map(|source| source.as_ref()),
+        list_id,
+        locale.code(),
     )
     .execute(pool)
     .await?;
@@ -173,6 +302,8 @@ async fn store_token(
 
 async fn send_confirmation_email(
     new_subscriber: NewSubscriber,
+    list: &List,
+    confirmation_email: &ConfirmationEmail,
     email_client: &EmailClient,
     base_url: &str,
     token: &str,
@@ -182,26 +313,20 @@ async fn send_confirmation_email(
         base_url, token
     );
     println!("Confirmation link: {}", confirmation_link);
+    let email = confirmation_email.render(&list.name, &confirmation_link);
     email_client
         .send_email(
             &new_subscriber.email,
-            "Welcome!",
-            &format!(
-                "<html>Welcome to my new Newsletter!<br/>
-                Visit <a href=\"{}\">here</a> to confirm your subscription</html>",
-                confirmation_link
-            ),
-            &format!(
-                "Welcome to my new Newsletter!\nVisit {} to confirm your subscription",
-                confirmation_link
-            ),
+            &email.subject,
+            &email.html_content,
+            &email.text_content,
         )
         .await?;
 
     Ok(())
 }
 
-fn generate_subscription_token() -> String {
+pub(crate) fn generate_subscription_token() -> String {
     let mut rng = thread_rng();
     std::iter::repeat(())
         .map(|()| rng.sample(Alphanumeric))
@@ -1,66 +1,116 @@
 use actix_web::{
     web::{Data, Query},
-    HttpResponse,
+    HttpRequest, HttpResponse,
 };
+use anyhow::Context;
+use chrono::Utc;
 use serde::Deserialize;
-use sqlx::PgPool;
+use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
+use crate::{
+    configuration::LocalizationSettings, errors::AppError, metrics::metrics, sequences::enroll,
+    utils::accept_language,
+};
+
 #[derive(Deserialize, Debug)]
 pub struct Parameter {
     subscription_token: String,
 }
 
-#[tracing::instrument(name = "Confirm a pending subscriber", skip(parameters, pool))]
-pub async fn confirm(Query(parameters): Query<Parameter>, pool: Data<PgPool>) -> HttpResponse {
-    let id = match get_subscriber_id(&pool, &parameters.subscription_token).await {
-        Ok(id) => id,
-        Err(_) => return HttpResponse::InternalServerError().finish(),
-    };
+#[tracing::instrument(
+    name = "Confirm a pending subscriber",
+    skip(request, parameters, pool, localization)
+)]
+pub async fn confirm(
+    request: HttpRequest,
+    Query(parameters): Query<Parameter>,
+    pool: Data<PgPool>,
+    localization: Data<LocalizationSettings>,
+) -> Result<HttpResponse, AppError> {
+    let subscription = get_subscription(&pool, &parameters.subscription_token)
+        .await
+        .context("Failed to look up the subscription token")?
+        .ok_or_else(|| {
+            // There is no subscription to take the language from.
+            let locale = localization.negotiate(None, accept_language(&request));
+            AppError::Unauthorized(locale.invalid_confirmation_link().into())
+        })?;
 
-    let id = match id {
-        Some(id) => id,
-        None => return HttpResponse::Unauthorized().finish(),
-    };
+    confirm_subscriber(&pool, subscription.id)
+        .await
+        .context("Failed to confirm the subscriber")?;
+    metrics().increment_confirmations();
 
-    if confirm_subscriber(&pool, id).await.is_err() {
-        return HttpResponse::InternalServerError().finish();
-    }
+    Ok(HttpResponse::Ok()
+        .content_type("text/plain; charset=utf-8")
+        .body(
+            localization
+                .stored(&subscription.locale)
+                .subscription_confirmed(&subscription.list_name),
+        ))
+}
 
-    HttpResponse::Ok().finish()
+struct Subscription {
+    id: Uuid,
+    list_name: String,
+    locale: String,
 }
 
-async fn get_subscriber_id(
+/// The subscription a token confirms.
+async fn get_subscription(
     pool: &PgPool,
     subscription_token: &str,
-) -> Result<Option<Uuid>, sqlx::Error> {
-    let rec = sqlx::query!(
-        r#"SELECT subscription_id FROM subscription_tokens WHERE subscription_token = $1"#,
+) -> Result<Option<Subscription>, sqlx::Error> {
+    sqlx::query_as!(
+        Subscription,
+        r#"
+        SELECT t.subscription_id AS id, l.name AS list_name, s.locale
+        FROM subscription_tokens t
+        JOIN subscriptions s ON s.id = t.subscription_id
+        JOIN lists l USING (list_id)
+        WHERE t.subscription_token = $1
+        "#,
         subscription_token
     )
     .fetch_optional(pool)
-    .await?;
-
-    Ok(rec.map(|r| r.subscription_id))
+    .await
 }
 
-async fn confirm_subscriber(pool: &PgPool, id: Uuid) -> Result<(), sqlx::Error> {
+/// Confirm the subscription and start the sequences of its list.
+async fn confirm_subscriber(pool: &PgPool, id: Uuid) -> Result<(), anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection")?;
     sqlx::query!(
-        r#"UPDATE subscriptions SET status = 'confirmed' WHERE id = $1"#,
-        id
+        r#"UPDATE subscriptions SET status = 'confirmed', confirmed_at = $2 WHERE id = $1"#,
+        id,
+        Utc::now()
     )
-    .execute(pool)
-    .await?;
-
-    delete_token(pool, id).await
+    .execute(&mut transaction)
+    .await
+    .context("Failed to update the subscription status")?;
+    delete_token(&mut transaction, id)
+        .await
+        .context("Failed to delete the subscription token")?;
+    enroll(&mut transaction, id).await?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction")?;
+    Ok(())
 }
 
-async fn delete_token(pool: &sqlx::Pool<sqlx::Postgres>, id: Uuid) -> Result<(), sqlx::Error> {
+async fn delete_token(
+    transaction: &mut Transaction<'_, Postgres>,
+    id: Uuid,
+) -> Result<(), sqlx::Error> {
     sqlx::query!(
         r#"DELETE FROM subscription_tokens WHERE subscription_id = $1"#,
         id
     )
-    .execute(pool)
+    .execute(transaction)
     .await?;
 
     Ok(())
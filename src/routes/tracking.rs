@@ -0,0 +1,106 @@
+use actix_web::{
+    http::header::{CACHE_CONTROL, LOCATION},
+    web, HttpResponse,
+};
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::PgPool;
+
+use crate::{
+    errors::AppError,
+    tracking::{TrackedRecipient, Tracker},
+};
+
+/// A transparent 1x1 GIF.
+const PIXEL: &[u8] = &[
+    0x47, 0x49, 0x46, 0x38, 0x39, 0x61, 0x01, 0x00, 0x01, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0xff, 0xff, 0xff, 0x21, 0xf9, 0x04, 0x01, 0x00, 0x00, 0x00, 0x00, 0x2c, 0x00, 0x00, 0x00, 0x00,
+    0x01, 0x00, 0x01, 0x00, 0x00, 0x02, 0x02, 0x44, 0x01, 0x00, 0x3b,
+];
+
+/// The open pixel embedded in tracked issues.
+pub async fn track_open(
+    token: web::Path<String>,
+    tracker: web::Data<Tracker>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let recipient = verify(&tracker, &token)?;
+    if tracker.enabled() {
+        if let Err(e) = record_open(&pool, &recipient).await {
+            tracing::warn!(error.cause_chain = ?e, "Failed to record an issue open");
+        }
+    }
+    Ok(HttpResponse::Ok()
+        .content_type("image/gif")
+        .insert_header((CACHE_CONTROL, "no-store"))
+        .body(PIXEL))
+}
+
+/// Where the links of tracked issues lead: record the click, then send the
+/// reader on to the original link.
+pub async fn track_click(
+    token: web::Path<String>,
+    tracker: web::Data<Tracker>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, AppError> {
+    let recipient = verify(&tracker, &token)?;
+    let Some(url) = &recipient.url else {
+        return Err(AppError::NotFound("This link is invalid.".into()));
+    };
+    // Links in issues that were already sent keep working once tracking
+    // is turned off.
+    if tracker.enabled() {
+        if let Err(e) = record_click(&pool, &recipient, url).await {
+            tracing::warn!(error.cause_chain = ?e, "Failed to record an issue click");
+        }
+    }
+    Ok(HttpResponse::Found()
+        .insert_header((LOCATION, url.as_str()))
+        .insert_header((CACHE_CONTROL, "no-store"))
+        .finish())
+}
+
+fn verify(tracker: &Tracker, token: &str) -> Result<TrackedRecipient, AppError> {
+    tracker
+        .verify(token)
+        .ok_or_else(|| AppError::NotFound("This link is invalid.".into()))
+}
+
+#[tracing::instrument(name = "Record issue open", skip(pool))]
+async fn record_open(pool: &PgPool, recipient: &TrackedRecipient) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_opens (newsletter_issue_id, subscriber_id, opened_at)
+        VALUES ($1, $2, $3)
+        "#,
+        recipient.newsletter_issue_id,
+        recipient.subscriber_id,
+        Utc::now(),
+    )
+    .execute(pool)
+    .await
+    .context("Failed to record issue open")?;
+    Ok(())
+}
+
+#[tracing::instrument(name = "Record issue click", skip(pool))]
+async fn record_click(
+    pool: &PgPool,
+    recipient: &TrackedRecipient,
+    url: &str,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_clicks (newsletter_issue_id, subscriber_id, url, clicked_at)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        recipient.newsletter_issue_id,
+        recipient.subscriber_id,
+        url,
+        Utc::now(),
+    )
+    .execute(pool)
+    .await
+    .context("Failed to record issue click")?;
+    Ok(())
+}
@@ -0,0 +1,336 @@
+use anyhow::Context;
+use chrono::{NaiveDate, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::domain::{Label, SubscriberEmail};
+
+/// Which confirmed subscribers an issue goes to. Every condition that is
+/// set has to match; the default matches everyone. Paused subscriptions
+/// never match.
+#[derive(Debug, Clone, Default)]
+pub struct SegmentDefinition {
+    pub tag: Option<Label>,
+    /// First day of sign ups included, in UTC.
+    pub subscribed_from: Option<NaiveDate>,
+    /// Last day of sign ups included, in UTC.
+    pub subscribed_until: Option<NaiveDate>,
+    pub source: Option<Label>,
+}
+
+pub struct Segment {
+    pub segment_id: Uuid,
+    pub name: String,
+    pub definition: SegmentDefinition,
+}
+
+impl Segment {
+    /// The conditions, for people.
+    pub fn description(&self) -> String {
+        let SegmentDefinition {
+            tag,
+            subscribed_from,
+            subscribed_until,
+            source,
+        } = &self.definition;
+        let mut conditions = Vec::new();
+        if let Some(tag) = tag {
+            conditions.push(format!("tagged {tag}"));
+        }
+        if let Some(from) = subscribed_from {
+            conditions.push(format!("subscribed on or after {from}"));
+        }
+        if let Some(until) = subscribed_until {
+            conditions.push(format!("subscribed on or before {until}"));
+        }
+        if let Some(source) = source {
+            conditions.push(format!("from {source}"));
+        }
+        if conditions.is_empty() {
+            "all confirmed subscribers".to_string()
+        } else {
+            conditions.join(", ")
+        }
+    }
+}
+
+pub struct Recipient {
+    pub subscriber_id: Uuid,
+    pub email: String,
+}
+
+/// Tags apply to a person, so every subscription of `email` gets tagged.
+#[tracing::instrument(name = "Tag subscriber", skip(pool))]
+pub async fn tag_subscriber(
+    pool: &PgPool,
+    email: &SubscriberEmail,
+    tag: &Label,
+) -> Result<bool, anyhow::Error> {
+    let tag_id = upsert_tag(pool, tag).await?;
+    let tagged = sqlx::query!(
+        r#"
+        INSERT INTO subscriber_tags (subscriber_id, tag_id)
+        SELECT id, $2 FROM subscriptions WHERE lower(email) = lower($1)
+        ON CONFLICT DO NOTHING
+        "#,
+        email.as_ref(),
+        tag_id,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to tag subscriber")?;
+    Ok(tagged.rows_affected() > 0)
+}
+
+#[tracing::instrument(name = "Untag subscriber", skip(pool))]
+pub async fn untag_subscriber(
+    pool: &PgPool,
+    email: &SubscriberEmail,
+    tag: &Label,
+) -> Result<bool, anyhow::Error> {
+    let untagged = sqlx::query!(
+        r#"
+        DELETE FROM subscriber_tags st
+        USING subscriptions s, tags t
+        WHERE st.subscriber_id = s.id AND st.tag_id = t.tag_id
+            AND lower(s.email) = lower($1) AND t.name = $2
+        "#,
+        email.as_ref(),
+        tag.as_ref(),
+    )
+    .execute(pool)
+    .await
+    .context("Failed to untag subscriber")?;
+    Ok(untagged.rows_affected() > 0)
+}
+
+async fn upsert_tag(pool: &PgPool, tag: &Label) -> Result<Uuid, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        INSERT INTO tags (tag_id, name) VALUES ($1, $2)
+        ON CONFLICT (name) DO UPDATE SET name = EXCLUDED.name
+        RETURNING tag_id
+        "#,
+        Uuid::new_v4(),
+        tag.as_ref(),
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to store tag")?;
+    Ok(row.tag_id)
+}
+
+#[tracing::instrument(name = "Create segment", skip(pool))]
+pub async fn create_segment(
+    pool: &PgPool,
+    name: &str,
+    definition: &SegmentDefinition,
+) -> Result<Uuid, anyhow::Error> {
+    let tag_id = match &definition.tag {
+        Some(tag) => Some(upsert_tag(pool, tag).await?),
+        None => None,
+    };
+    let segment_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO segments (
+            segment_id, name, tag_id, subscribed_from, subscribed_until, source, created_at
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#,
+        segment_id,
+        name,
+        tag_id,
+        definition.subscribed_from,
+        definition.subscribed_until,
+        definition.source.as_ref().map(|source| source.as_ref()),
+        Utc::now(),
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store segment")?;
+    Ok(segment_id)
+}
+
+struct SegmentRow {
+    segment_id: Uuid,
+    name: String,
+    tag: Option<String>,
+    subscribed_from: Option<NaiveDate>,
+    subscribed_until: Option<NaiveDate>,
+    source: Option<String>,
+}
+
+impl TryFrom<SegmentRow> for Segment {
+    type Error = anyhow::Error;
+
+    fn try_from(row: SegmentRow) -> Result<Self, Self::Error> {
+        let label = |value: Option<String>| {
+            value
+                .as_deref()
+                .map(Label::parse)
+                .transpose()
+                .map_err(anyhow::Error::msg)
+        };
+        Ok(Segment {
+            segment_id: row.segment_id,
+            name: row.name,
+            definition: SegmentDefinition {
+                tag: label(row.tag)?,
+                subscribed_from: row.subscribed_from,
+                subscribed_until: row.subscribed_until,
+                source: label(row.source)?,
+            },
+        })
+    }
+}
+
+#[tracing::instrument(name = "List segments", skip(pool))]
+pub async fn list_segments(pool: &PgPool) -> Result<Vec<Segment>, anyhow::Error> {
+    sqlx::query_as!(
+        SegmentRow,
+        r#"
+        SELECT
+            s.segment_id, s.name, t.name AS "tag?", s.subscribed_from, s.subscribed_until, s.source
+        FROM segments s
+        LEFT JOIN tags t USING (tag_id)
+        ORDER BY s.name
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to list segments")?
+    .into_iter()
+    .map(Segment::try_from)
+    .collect()
+}
+
+#[tracing::instrument(name = "Get segment", skip(pool))]
+pub async fn get_segment(
+    pool: &PgPool,
+    segment_id: Uuid,
+) -> Result<Option<Segment>, anyhow::Error> {
+    sqlx::query_as!(
+        SegmentRow,
+        r#"
+        SELECT
+            s.segment_id, s.name, t.name AS "tag?", s.subscribed_from, s.subscribed_until, s.source
+        FROM segments s
+        LEFT JOIN tags t USING (tag_id)
+        WHERE s.segment_id = $1
+        "#,
+        segment_id,
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch segment")?
+    .map(Segment::try_from)
+    .transpose()
+}
+
+/// The confirmed subscribers of a list matching `definition`.
+///
+/// Who a segment reaches is defined once, by the `segment_members` SQL
+/// function, so that the count shown before sending matches who the issue
+/// goes to.
+#[tracing::instrument(name = "Get segment recipients", skip(pool))]
+pub async fn segment_recipients(
+    pool: &PgPool,
+    list_id: Uuid,
+    definition: &SegmentDefinition,
+) -> Result<Vec<Recipient>, anyhow::Error> {
+    let recipients = sqlx::query_as!(
+        Recipient,
+        r#"
+        SELECT id AS "subscriber_id!", email AS "email!"
+        FROM segment_members($1, $2, $3, $4, $5)
+        "#,
+        definition.tag.as_ref().map(|tag| tag.as_ref()),
+        definition.subscribed_from,
+        definition.subscribed_until,
+        definition.source.as_ref().map(|source| source.as_ref()),
+        list_id,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch segment recipients")?;
+    Ok(recipients)
+}
+
+/// How many confirmed subscribers an issue sent to `definition` would reach
+/// right now, in one list or across all of them.
+#[tracing::instrument(name = "Count segment recipients", skip(pool))]
+pub async fn count_recipients(
+    pool: &PgPool,
+    list_id: Option<Uuid>,
+    definition: &SegmentDefinition,
+) -> Result<i64, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT COUNT(*) AS "count!"
+        FROM segment_members($1, $2, $3, $4, $5)
+        "#,
+        definition.tag.as_ref().map(|tag| tag.as_ref()),
+        definition.subscribed_from,
+        definition.subscribed_until,
+        definition.source.as_ref().map(|source| source.as_ref()),
+        list_id,
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to count segment recipients")?;
+    Ok(row.count)
+}
+
+/// Every segment, with the number of subscribers it would reach right now
+/// in one list or across all of them.
+pub async fn segments_with_counts(
+    pool: &PgPool,
+    list_id: Option<Uuid>,
+) -> Result<Vec<(Segment, i64)>, anyhow::Error> {
+    let mut segments = Vec::new();
+    for segment in list_segments(pool).await? {
+        let count = count_recipients(pool, list_id, &segment.definition).await?;
+        segments.push((segment, count));
+    }
+    Ok(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use uuid::Uuid;
+
+    use super::{Segment, SegmentDefinition};
+    use crate::domain::Label;
+
+    fn segment(definition: SegmentDefinition) -> Segment {
+        Segment {
+            segment_id: Uuid::new_v4(),
+            name: "Segment".into(),
+            definition,
+        }
+    }
+
+    #[test]
+    fn an_empty_segment_is_everyone() {
+        assert_eq!(
+            segment(SegmentDefinition::default()).description(),
+            "all confirmed subscribers"
+        );
+    }
+
+    #[test]
+    fn conditions_are_described() {
+        let definition = SegmentDefinition {
+            tag: Some(Label::parse("vip").unwrap()),
+            subscribed_from: NaiveDate::from_ymd_opt(2026, 1, 1),
+            subscribed_until: None,
+            source: Some(Label::parse("podcast").unwrap()),
+        };
+        assert_eq!(
+            segment(definition).description(),
+            "tagged vip, subscribed on or after 2026-01-01, from podcast"
+        );
+    }
+}
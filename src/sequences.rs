@@ -0,0 +1,432 @@
+use std::{future::Future, pin::Pin, sync::Arc, task::Poll, time::Duration};
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    domain::SubscriberEmail, email_client::EmailClient, metrics::metrics,
+    preferences::PreferenceLinks,
+};
+
+/// Attempts at sending a step before giving up on it.
+const MAX_ATTEMPTS: i32 = 3;
+/// Wait before retrying a step that failed to send, times the attempts so far.
+const RETRY_BACKOFF_MINUTES: i64 = 5;
+/// Most steps sent per round, so that newly due steps are queued regularly.
+const BATCH_SIZE: usize = 100;
+
+pub struct Sequence {
+    pub sequence_id: Uuid,
+    pub name: String,
+    pub list_name: String,
+    pub steps: Vec<SequenceStep>,
+}
+
+pub struct SequenceStep {
+    pub step_id: Uuid,
+    pub delay_hours: i32,
+    pub subject: String,
+    pub sent: i64,
+    pub queued: i64,
+}
+
+/// `None` when there is a sequence with that name already.
+#[tracing::instrument(name = "Create sequence", skip(pool))]
+pub async fn create_sequence(
+    pool: &PgPool,
+    list_id: Uuid,
+    name: &str,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        INSERT INTO sequences (sequence_id, list_id, name, created_at)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (name) DO NOTHING
+        RETURNING sequence_id
+        "#,
+        Uuid::new_v4(),
+        list_id,
+        name,
+        Utc::now(),
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to store sequence")?;
+    Ok(row.map(|row| row.sequence_id))
+}
+
+pub struct NewStep<'a> {
+    pub delay_hours: i32,
+    pub subject: &'a str,
+    pub html_content: &'a str,
+    pub text_content: &'a str,
+}
+
+/// `false` when there is no such sequence.
+///
+/// Subscribers enrolled before the step was added only get it if it is not
+/// due for them yet.
+#[tracing::instrument(name = "Add sequence step", skip(pool, step))]
+pub async fn add_step(
+    pool: &PgPool,
+    sequence_id: Uuid,
+    step: &NewStep<'_>,
+) -> Result<bool, anyhow::Error> {
+    let added = sqlx::query!(
+        r#"
+        INSERT INTO sequence_steps (
+            step_id, sequence_id, delay_hours, subject, html_content, text_content, created_at
+        )
+        SELECT $1, sequence_id, $3, $4, $5, $6, $7
+        FROM sequences WHERE sequence_id = $2
+        "#,
+        Uuid::new_v4(),
+        sequence_id,
+        step.delay_hours,
+        step.subject,
+        step.html_content,
+        step.text_content,
+        Utc::now(),
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store sequence step")?;
+    Ok(added.rows_affected() > 0)
+}
+
+/// Every sequence with its steps, in the order they go out.
+#[tracing::instrument(name = "List sequences", skip(pool))]
+pub async fn list_sequences(pool: &PgPool) -> Result<Vec<Sequence>, anyhow::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT
+            q.sequence_id,
+            q.name,
+            l.name AS list_name,
+            st.step_id AS "step_id?",
+            st.delay_hours AS "delay_hours?",
+            st.subject AS "subject?",
+            (SELECT COUNT(*) FROM sequence_deliveries d
+                WHERE d.step_id = st.step_id AND d.status = 'sent') AS "sent!",
+            (SELECT COUNT(*) FROM sequence_deliveries d
+                WHERE d.step_id = st.step_id AND d.status = 'queued') AS "queued!"
+        FROM sequences q
+        JOIN lists l USING (list_id)
+        LEFT JOIN sequence_steps st USING (sequence_id)
+        ORDER BY q.name, st.delay_hours, st.created_at
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to list sequences")?;
+
+    let mut sequences: Vec<Sequence> = Vec::new();
+    for row in rows {
+        if sequences.last().map(|s| s.sequence_id) != Some(row.sequence_id) {
+            sequences.push(Sequence {
+                sequence_id: row.sequence_id,
+                name: row.name,
+                list_name: row.list_name,
+                steps: Vec::new(),
+            });
+        }
+        if let (Some(step_id), Some(delay_hours), Some(subject)) =
+            (row.step_id, row.delay_hours, row.subject)
+        {
+            let sequence = sequences.last_mut().expect("A sequence was just pushed");
+            sequence.steps.push(SequenceStep {
+                step_id,
+                delay_hours,
+                subject,
+                sent: row.sent,
+                queued: row.queued,
+            });
+        }
+    }
+    Ok(sequences)
+}
+
+/// Enroll a subscription that was just confirmed in every sequence of its
+/// list. Their steps are timed from now.
+#[tracing::instrument(name = "Enroll subscriber in sequences", skip(transaction))]
+pub async fn enroll(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO sequence_enrollments (sequence_id, subscriber_id, enrolled_at)
+        SELECT q.sequence_id, s.id, $2
+        FROM subscriptions s
+        JOIN sequences q USING (list_id)
+        WHERE s.id = $1
+        ON CONFLICT DO NOTHING
+        "#,
+        subscriber_id,
+        Utc::now(),
+    )
+    .execute(transaction)
+    .await
+    .context("Failed to enroll subscriber in sequences")?;
+    Ok(())
+}
+
+/// Bring forward the queued steps of a subscriber whose pause was cleared
+/// or shortened: the scheduler put them off until the old end of the pause.
+/// `resume_at` is when delivery resumes, `None` for right away.
+#[tracing::instrument(name = "Reschedule paused steps", skip(transaction))]
+pub async fn reschedule_paused(
+    transaction: &mut Transaction<'_, Postgres>,
+    email: &str,
+    resume_at: Option<DateTime<Utc>>,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE sequence_deliveries d
+        SET next_attempt_at = $2
+        FROM subscriptions s
+        WHERE d.subscriber_id = s.id AND lower(s.email) = lower($1)
+            AND d.status = 'queued' AND d.next_attempt_at > $2
+        "#,
+        email,
+        resume_at.unwrap_or_else(Utc::now),
+    )
+    .execute(transaction)
+    .await
+    .context("Failed to reschedule paused sequence steps")?;
+    Ok(())
+}
+
+/// What one round of the scheduler did.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct RoundSummary {
+    pub enqueued: u64,
+    pub sent: usize,
+    /// Attempts that failed, whether the step is retried later or not.
+    pub failed: usize,
+    /// Steps dropped because the subscriber left.
+    pub cancelled: usize,
+    /// Whether shutdown was requested before the batch was through.
+    pub interrupted: bool,
+}
+
+enum Outcome {
+    Sent,
+    Failed,
+    Cancelled,
+    Deferred,
+}
+
+/// Queues the steps that came due and sends them.
+///
+/// Several instances can run side by side: steps are claimed with row locks,
+/// so each goes out once.
+#[derive(Clone)]
+pub struct SequenceScheduler {
+    pool: PgPool,
+    email_client: Arc<EmailClient>,
+    preference_links: Arc<PreferenceLinks>,
+}
+
+impl SequenceScheduler {
+    pub fn new(
+        pool: PgPool,
+        email_client: Arc<EmailClient>,
+        preference_links: Arc<PreferenceLinks>,
+    ) -> Self {
+        Self {
+            pool,
+            email_client,
+            preference_links,
+        }
+    }
+
+    /// Run a round every `interval` until `shutdown` resolves. Shutdown is
+    /// checked between steps too, so that a long batch does not outlast the
+    /// drain timeout and get aborted halfway through a send.
+    pub async fn run_until(self, interval: Duration, shutdown: impl Future<Output = ()>) {
+        tokio::pin!(shutdown);
+        loop {
+            match self.run_round_until(shutdown.as_mut()).await {
+                Ok(summary) if summary.interrupted => return,
+                Ok(_) => {}
+                Err(e) => {
+                    tracing::error!(error.cause_chain = ?e, "Failed to deliver sequence steps");
+                }
+            }
+            tokio::select! {
+                _ = &mut shutdown => return,
+                _ = tokio::time::sleep(interval) => {}
+            }
+        }
+    }
+
+    /// Queue the steps that came due and send a batch of them.
+    pub async fn run_round(&self) -> Result<RoundSummary, anyhow::Error> {
+        self.run_round_until(std::pin::pin!(std::future::pending()))
+            .await
+    }
+
+    #[tracing::instrument(name = "Deliver sequence steps", skip(self, shutdown))]
+    async fn run_round_until(
+        &self,
+        mut shutdown: Pin<&mut impl Future<Output = ()>>,
+    ) -> Result<RoundSummary, anyhow::Error> {
+        let mut summary = RoundSummary {
+            enqueued: self.enqueue_due_steps().await?,
+            ..Default::default()
+        };
+        for _ in 0..BATCH_SIZE {
+            if is_ready(shutdown.as_mut()).await {
+                summary.interrupted = true;
+                break;
+            }
+            match self.deliver_next_step().await? {
+                None => break,
+                Some(Outcome::Sent) => summary.sent += 1,
+                Some(Outcome::Failed) => summary.failed += 1,
+                Some(Outcome::Cancelled) => summary.cancelled += 1,
+                Some(Outcome::Deferred) => {}
+            }
+        }
+        self.record_queue_depth().await?;
+        Ok(summary)
+    }
+
+    async fn record_queue_depth(&self) -> Result<(), anyhow::Error> {
+        let row = sqlx::query!(
+            r#"SELECT COUNT(*) AS "count!" FROM sequence_deliveries WHERE status = 'queued'"#
+        )
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to count queued sequence steps")?;
+        metrics().set_queued_sequence_steps(row.count as u64);
+        Ok(())
+    }
+
+    /// Queue every step whose delay has passed for a confirmed subscriber.
+    /// Steps added to a sequence after they would have been due for someone
+    /// are skipped for them.
+    async fn enqueue_due_steps(&self) -> Result<u64, anyhow::Error> {
+        let now = Utc::now();
+        let enqueued = sqlx::query!(
+            r#"
+            INSERT INTO sequence_deliveries (
+                step_id, subscriber_id, status, attempts, queued_at, next_attempt_at
+            )
+            SELECT st.step_id, e.subscriber_id, 'queued', 0, $1, $1
+            FROM sequence_enrollments e
+            JOIN sequence_steps st USING (sequence_id)
+            JOIN subscriptions s ON s.id = e.subscriber_id
+            WHERE s.status = 'confirmed'
+                AND e.enrolled_at + make_interval(hours => st.delay_hours) <= $1
+                AND st.created_at <= e.enrolled_at + make_interval(hours => st.delay_hours)
+            ON CONFLICT DO NOTHING
+            "#,
+            now,
+        )
+        .execute(&self.pool)
+        .await
+        .context("Failed to enqueue due sequence steps")?;
+        Ok(enqueued.rows_affected())
+    }
+
+    /// Claim and send one queued step, if any is ready.
+    async fn deliver_next_step(&self) -> Result<Option<Outcome>, anyhow::Error> {
+        let mut transaction = self
+            .pool
+            .begin()
+            .await
+            .context("Failed to acquire a Postgres connection")?;
+        let Some(step) = sqlx::query!(
+            r#"
+            SELECT
+                d.step_id, d.subscriber_id, d.attempts, s.email, s.status, s.paused_until,
+                st.subject, st.html_content, st.text_content
+            FROM sequence_deliveries d
+            JOIN subscriptions s ON s.id = d.subscriber_id
+            JOIN sequence_steps st USING (step_id)
+            WHERE d.status = 'queued' AND d.next_attempt_at <= $1
+            ORDER BY d.next_attempt_at
+            LIMIT 1
+            FOR UPDATE OF d SKIP LOCKED
+            "#,
+            Utc::now(),
+        )
+        .fetch_optional(&mut transaction)
+        .await
+        .context("Failed to claim a sequence step")?
+        else {
+            return Ok(None);
+        };
+
+        let now = Utc::now();
+        let (outcome, status, next_attempt_at): (_, _, DateTime<Utc>) =
+            if step.status != "confirmed" {
+                (Outcome::Cancelled, "cancelled", now)
+            } else if let Some(paused_until) = step.paused_until.filter(|until| *until > now) {
+                (Outcome::Deferred, "queued", paused_until)
+            } else {
+                let (html, text) = self.preference_links.add_footer(
+                    &step.html_content,
+                    &step.text_content,
+                    step.subscriber_id,
+                );
+                let sent = match SubscriberEmail::parse(step.email) {
+                    Ok(email) => self
+                        .email_client
+                        .send_email(&email, &step.subject, &html, &text)
+                        .await
+                        .context("Failed to send sequence step"),
+                    Err(e) => Err(anyhow::anyhow!(e)),
+                };
+                match sent {
+                    Ok(()) => (Outcome::Sent, "sent", now),
+                    Err(e) if step.attempts + 1 >= MAX_ATTEMPTS => {
+                        tracing::error!(error.cause_chain = ?e, "Giving up on a sequence step");
+                        (Outcome::Failed, "failed", now)
+                    }
+                    Err(e) => {
+                        tracing::warn!(error.cause_chain = ?e, "Failed to send a sequence step");
+                        let backoff = chrono::Duration::minutes(
+                            RETRY_BACKOFF_MINUTES * i64::from(step.attempts + 1),
+                        );
+                        (Outcome::Failed, "queued", now + backoff)
+                    }
+                }
+            };
+        let attempted = matches!(outcome, Outcome::Sent | Outcome::Failed);
+        sqlx::query!(
+            r#"
+            UPDATE sequence_deliveries
+            SET status = $3,
+                attempts = attempts + $4,
+                next_attempt_at = $5,
+                finished_at = $6
+            WHERE step_id = $1 AND subscriber_id = $2
+            "#,
+            step.step_id,
+            step.subscriber_id,
+            status,
+            i32::from(attempted),
+            next_attempt_at,
+            (status != "queued").then_some(now),
+        )
+        .execute(&mut transaction)
+        .await
+        .context("Failed to record sequence step delivery")?;
+        transaction
+            .commit()
+            .await
+            .context("Failed to commit SQL transaction")?;
+        Ok(Some(outcome))
+    }
+}
+
+/// Whether `future` has resolved, without waiting for it. It must not be
+/// polled again once it has.
+async fn is_ready(mut future: Pin<&mut impl Future<Output = ()>>) -> bool {
+    std::future::poll_fn(|cx| Poll::Ready(future.as_mut().poll(cx).is_ready())).await
+}
@@ -1,12 +1,31 @@
 use actix_session::{Session, SessionExt};
 use actix_web::{dev::Payload, FromRequest, HttpRequest};
+use chrono::Utc;
+use secrecy::{ExposeSecret, Secret};
 use std::future::{ready, Ready};
 use uuid::Uuid;
 
+/// How long the second factor may be entered after the password was verified.
+const PENDING_TOTP_LIFETIME_SECONDS: i64 = 5 * 60;
+
 pub struct TypedSession(Session);
 
+/// Password verified, second factor still pending: the user is not logged in yet.
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct PendingTotp {
+    pub user_id: Uuid,
+    /// The username the password was verified for, used by the login throttle.
+    pub username: String,
+    /// Unix timestamp after which the password has to be entered again.
+    expires_at: i64,
+}
+
 impl TypedSession {
     const USER_ID: &'static str = "user_id";
+    const SESSION_ID: &'static str = "session_id";
+    const CSRF_TOKEN: &'static str = "csrf_token";
+    const PENDING_TOTP: &'static str = "pending_totp";
+    const TOTP_ENROLLMENT_SECRET: &'static str = "totp_enrollment_secret";
 
     pub fn renew(&self) {
         self.0.renew();
@@ -21,6 +40,78 @@ impl TypedSession {
         self.0.get(Self::USER_ID)
     }
 
+    /// Identifier of this session in the `SessionRegistry`.
+    pub fn insert_session_id(&self, value: Uuid) -> Result<(), actix_session::SessionInsertError> {
+        self.0.insert(Self::SESSION_ID, value)?;
+        Ok(())
+    }
+
+    pub fn get_session_id(&self) -> Result<Option<Uuid>, actix_session::SessionGetError> {
+        self.0.get(Self::SESSION_ID)
+    }
+
+    pub fn insert_csrf_token(&self, value: &str) -> Result<(), actix_session::SessionInsertError> {
+        self.0.insert(Self::CSRF_TOKEN, value)?;
+        Ok(())
+    }
+
+    pub fn get_csrf_token(&self) -> Result<Option<String>, actix_session::SessionGetError> {
+        self.0.get(Self::CSRF_TOKEN)
+    }
+
+    pub fn insert_pending_totp(
+        &self,
+        user_id: Uuid,
+        username: String,
+    ) -> Result<(), actix_session::SessionInsertError> {
+        self.0.remove(Self::USER_ID);
+        let pending = PendingTotp {
+            user_id,
+            username,
+            expires_at: Utc::now().timestamp() + PENDING_TOTP_LIFETIME_SECONDS,
+        };
+        self.0.insert(Self::PENDING_TOTP, pending)?;
+        Ok(())
+    }
+
+    /// `None` if there is no pending second factor or if it expired.
+    pub fn get_pending_totp(&self) -> Result<Option<PendingTotp>, actix_session::SessionGetError> {
+        let pending: Option<PendingTotp> = self.0.get(Self::PENDING_TOTP)?;
+        match pending {
+            Some(pending) if pending.expires_at <= Utc::now().timestamp() => {
+                self.remove_pending_totp();
+                Ok(None)
+            }
+            pending => Ok(pending),
+        }
+    }
+
+    pub fn remove_pending_totp(&self) {
+        self.0.remove(Self::PENDING_TOTP);
+    }
+
+    pub fn insert_totp_enrollment_secret(
+        &self,
+        value: &Secret<String>,
+    ) -> Result<(), actix_session::SessionInsertError> {
+        self.0
+            .insert(Self::TOTP_ENROLLMENT_SECRET, value.expose_secret())?;
+        Ok(())
+    }
+
+    pub fn get_totp_enrollment_secret(
+        &self,
+    ) -> Result<Option<Secret<String>>, actix_session::SessionGetError> {
+        Ok(self
+            .0
+            .get::<String>(Self::TOTP_ENROLLMENT_SECRET)?
+            .map(Secret::new))
+    }
+
+    pub fn remove_totp_enrollment_secret(&self) {
+        self.0.remove(Self::TOTP_ENROLLMENT_SECRET);
+    }
+
     pub fn log_out(&self) {
         self.0.purge();
     }
@@ -0,0 +1,249 @@
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    web,
+};
+use actix_web_lab::middleware::Next;
+use tokio::{
+    sync::{watch, Notify},
+    task::JoinHandle,
+};
+use tracing::Instrument;
+
+/// Number of requests currently being handled.
+#[derive(Clone, Default)]
+pub struct InFlightRequests {
+    inner: Arc<InFlightInner>,
+}
+
+#[derive(Default)]
+struct InFlightInner {
+    count: AtomicUsize,
+    idle: Notify,
+}
+
+struct InFlightGuard(InFlightRequests);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        let inner = &self.0.inner;
+        if inner.count.fetch_sub(1, Ordering::SeqCst) == 1 {
+            inner.idle.notify_waiters();
+        }
+    }
+}
+
+impl InFlightRequests {
+    fn start(&self) -> InFlightGuard {
+        self.inner.count.fetch_add(1, Ordering::SeqCst);
+        InFlightGuard(self.clone())
+    }
+
+    pub fn count(&self) -> usize {
+        self.inner.count.load(Ordering::SeqCst)
+    }
+
+    /// Resolves once no request is being handled.
+    pub async fn idle(&self) {
+        loop {
+            // Register before checking, so that a wakeup in between is not lost.
+            let idle = self.inner.idle.notified();
+            if self.count() == 0 {
+                return;
+            }
+            idle.await;
+        }
+    }
+}
+
+/// Keep count of the requests being handled, so that shutdown can wait for them.
+pub async fn track_in_flight_requests(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let _guard = req
+        .app_data::<web::Data<InFlightRequests>>()
+        .map(|requests| requests.start());
+    next.call(req).await
+}
+
+/// Work spawned outside of the request/response cycle, tracked so that
+/// shutdown can wait for it instead of cutting it off.
+#[derive(Clone)]
+pub struct BackgroundTasks {
+    shutdown: Arc<watch::Sender<bool>>,
+    tasks: Arc<Mutex<Vec<TrackedTask>>>,
+}
+
+struct TrackedTask {
+    name: &'static str,
+    handle: JoinHandle<()>,
+}
+
+/// What became of the background tasks once shutdown was requested.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct DrainSummary {
+    pub finished: usize,
+    pub abandoned: usize,
+}
+
+impl Default for BackgroundTasks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BackgroundTasks {
+    pub fn new() -> Self {
+        let (shutdown, _) = watch::channel(false);
+        Self {
+            shutdown: Arc::new(shutdown),
+            tasks: Arc::default(),
+        }
+    }
+
+    pub fn spawn<F>(&self, name: &'static str, task: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let handle = tokio::spawn(task.in_current_span());
+        let mut tasks = self.tasks.lock().unwrap_or_else(|e| e.into_inner());
+        tasks.retain(|task| !task.handle.is_finished());
+        tasks.push(TrackedTask { name, handle });
+    }
+
+    /// Resolves once shutdown has been requested. Long running workers
+    /// should check it between units of work.
+    pub fn shutdown_requested(&self) -> impl Future<Output = ()> + Send + 'static {
+        let mut receiver = self.shutdown.subscribe();
+        async move {
+            // An error means the sender is gone, which only happens on shutdown.
+            while !*receiver.borrow_and_update() {
+                if receiver.changed().await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Ask every task to stop and wait up to `timeout` for them to finish.
+    /// Tasks still running after that are aborted.
+    pub async fn drain(&self, timeout: Duration) -> DrainSummary {
+        self.shutdown.send_replace(true);
+        let tasks = std::mem::take(&mut *self.tasks.lock().unwrap_or_else(|e| e.into_inner()));
+
+        let mut summary = DrainSummary::default();
+        let deadline = tokio::time::Instant::now() + timeout;
+        for mut task in tasks {
+            match tokio::time::timeout_at(deadline, &mut task.handle).await {
+                Ok(_) => summary.finished += 1,
+                Err(_) => {
+                    tracing::warn!(task = task.name, "Aborting background task at shutdown");
+                    task.handle.abort();
+                    summary.abandoned += 1;
+                }
+            }
+        }
+        summary
+    }
+}
+
+/// Resolves on SIGINT or, on Unix, SIGTERM.
+pub async fn shutdown_signal() {
+    let interrupt = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!(error = %e, "Failed to listen for SIGINT");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::error!(error = %e, "Failed to listen for SIGTERM");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = interrupt => tracing::info!("Received SIGINT"),
+        _ = terminate => tracing::info!("Received SIGTERM"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{BackgroundTasks, DrainSummary, InFlightRequests};
+
+    #[tokio::test]
+    async fn idle_waits_for_requests_to_finish() {
+        let requests = InFlightRequests::default();
+        let guard = requests.start();
+        assert_eq!(requests.count(), 1);
+
+        let idle = tokio::spawn({
+            let requests = requests.clone();
+            async move { requests.idle().await }
+        });
+        tokio::task::yield_now().await;
+        assert!(!idle.is_finished());
+
+        drop(guard);
+        tokio::time::timeout(Duration::from_secs(1), idle)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn finished_tasks_are_counted() {
+        let tasks = BackgroundTasks::new();
+        tasks.spawn("quick", async {});
+
+        let summary = tasks.drain(Duration::from_secs(1)).await;
+        assert_eq!(
+            summary,
+            DrainSummary {
+                finished: 1,
+                abandoned: 0
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn workers_are_told_to_stop() {
+        let tasks = BackgroundTasks::new();
+        let shutdown = tasks.shutdown_requested();
+        tasks.spawn("worker", shutdown);
+
+        let summary = tasks.drain(Duration::from_secs(1)).await;
+        assert_eq!(summary.finished, 1);
+    }
+
+    #[tokio::test]
+    async fn tasks_running_past_the_timeout_are_abandoned() {
+        let tasks = BackgroundTasks::new();
+        tasks.spawn("stuck", std::future::pending());
+
+        let summary = tasks.drain(Duration::from_millis(10)).await;
+        assert_eq!(summary.abandoned, 1);
+    }
+}
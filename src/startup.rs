@@ -7,14 +7,35 @@ use actix_web::{
 };
 use actix_web_flash_messages::{storage::CookieMessageStore, FlashMessagesFramework};
 use actix_web_lab::middleware::from_fn;
+use redis::aio::ConnectionManager;
 use secrecy::{ExposeSecret, Secret};
 use sqlx::{postgres::PgPoolOptions, PgPool};
-use std::{net::TcpListener, time::Duration};
+use std::{
+    future::Future,
+    net::TcpListener,
+    time::{Duration, Instant},
+};
 use tracing_actix_web::TracingLogger;
 
 use crate::{
-    authentication::reject_anonymous_users, configuration::DatabaseSettings,
-    configuration::Settings, email_client::EmailClient, routes,
+    authentication::{
+        reject_anonymous_users, reject_invalid_csrf_tokens, LoginThrottle, SessionRegistry,
+    },
+    bot_protection::BotProtection,
+    configuration::{DatabaseSettings, Settings},
+    domain::EmailDomainPolicy,
+    email_client::EmailClient,
+    errors::render_errors,
+    metrics::record_http_metrics,
+    preferences::PreferenceLinks,
+    rate_limit::SubscribeRateLimiter,
+    request_id::assign_request_id,
+    routes,
+    sequences::SequenceScheduler,
+    shutdown::{shutdown_signal, track_in_flight_requests, BackgroundTasks, InFlightRequests},
+    telemetry::TraceContextRootSpanBuilder,
+    tracking::Tracker,
+    utils::TrustedProxies,
 };
 
 pub struct HmacSecret(pub Secret<String>);
@@ -23,20 +44,84 @@ pub async fn run(
     listener: TcpListener,
     connection: PgPool,
     email_client: EmailClient,
-    base_url: String,
-    hmac_secret: Secret<String>,
-    redis_url: Secret<String>,
+    configuration: Settings,
+    background_tasks: BackgroundTasks,
+    in_flight_requests: InFlightRequests,
 ) -> Result<Server, anyhow::Error> {
+    let Settings {
+        application_settings,
+        email_webhooks,
+        redis_url,
+        login_throttle,
+        subscribe_rate_limit,
+        bot_protection,
+        email_domains,
+        password_policy,
+        password_hashing,
+        metrics,
+        readiness,
+        tracking,
+        sequences,
+        localization,
+        ..
+    } = configuration;
+    let base_url = application_settings.base_url;
+    let hmac_secret = application_settings.hmac_secret;
+    let trusted_proxies = web::Data::new(TrustedProxies(application_settings.trusted_proxies));
+
     let db_connection = web::Data::new(connection);
     let email_client = web::Data::new(email_client);
+    let tracker = web::Data::new(Tracker::new(
+        hmac_secret.clone(),
+        base_url.clone(),
+        tracking.enabled,
+    ));
+    let preference_links =
+        web::Data::new(PreferenceLinks::new(hmac_secret.clone(), base_url.clone()));
     let base_url = web::Data::new(base_url);
     let hmac_secret_data = web::Data::new(HmacSecret(hmac_secret.clone()));
+    let bot_protection = web::Data::new(BotProtection::new(hmac_secret.clone(), &bot_protection));
+    let email_domain_policy = web::Data::new(EmailDomainPolicy::from_settings(&email_domains)?);
+    let email_webhooks = web::Data::new(email_webhooks);
+    let password_policy = web::Data::new(password_policy);
+    let password_hashing = web::Data::new(password_hashing);
+    let metrics_settings = web::Data::new(metrics);
+    let localization = web::Data::new(localization);
+    let background_tasks = web::Data::new(background_tasks);
+    let in_flight_requests = web::Data::new(in_flight_requests);
+
+    if sequences.enabled {
+        let scheduler = SequenceScheduler::new(
+            db_connection.get_ref().clone(),
+            email_client.clone().into_inner(),
+            preference_links.clone().into_inner(),
+        );
+        background_tasks.spawn(
+            "sequence_scheduler",
+            scheduler.run_until(
+                sequences.poll_interval(),
+                background_tasks.shutdown_requested(),
+            ),
+        );
+    }
 
     let cookie_storage =
         CookieMessageStore::builder(Key::from(hmac_secret.expose_secret().as_bytes())).build();
     let message_framework = FlashMessagesFramework::builder(cookie_storage).build();
 
     let redis_session_store = RedisSessionStore::new(redis_url.expose_secret()).await?;
+    let redis_connection =
+        ConnectionManager::new(redis::Client::open(redis_url.expose_secret().as_str())?).await?;
+    let session_registry = web::Data::new(SessionRegistry::new(redis_connection.clone()));
+    let readiness_probe = web::Data::new(routes::ReadinessProbe::new(
+        redis_connection.clone(),
+        readiness,
+    ));
+    let subscribe_rate_limiter = web::Data::new(SubscribeRateLimiter::new(
+        redis_connection.clone(),
+        subscribe_rate_limit,
+    ));
+    let login_throttle = web::Data::new(LoginThrottle::new(redis_connection, login_throttle));
 
     let server = HttpServer::new(move || {
         App::new()
@@ -45,28 +130,108 @@ pub async fn run(
                 redis_session_store.clone(),
                 Key::from(hmac_secret.expose_secret().as_bytes()),
             ))
-            .wrap(TracingLogger::default())
+            .wrap(from_fn(render_errors))
+            .wrap(from_fn(record_http_metrics))
+            .wrap(TracingLogger::<TraceContextRootSpanBuilder>::new())
+            .wrap(from_fn(assign_request_id))
+            .wrap(from_fn(track_in_flight_requests))
             .route("/", get().to(routes::home))
             .route("/login", get().to(routes::login_form))
             .route("/login", post().to(routes::login))
+            .route("/login/totp", get().to(routes::login_totp_form))
+            .route("/login/totp", post().to(routes::login_totp))
             .route("/health_check", get().to(routes::health_check))
+            .route("/ready", get().to(routes::ready))
+            .route("/metrics", get().to(routes::metrics_endpoint))
             .route("/subscriptions", post().to(routes::subscribe))
             .route("/subscriptions/confirm", get().to(routes::confirm))
+            .route("/webhooks/email", post().to(routes::email_webhook))
+            .route("/preferences", get().to(routes::preferences_page))
+            .route(
+                "/preferences/name",
+                post().to(routes::change_subscriber_name),
+            )
+            .route(
+                "/preferences/email",
+                post().to(routes::change_subscriber_email),
+            )
+            .route(
+                "/preferences/confirm-email",
+                get().to(routes::confirm_email_change),
+            )
+            .route(
+                "/preferences/lists",
+                post().to(routes::change_subscribed_lists),
+            )
+            .route("/preferences/pause", post().to(routes::pause_subscription))
+            .route("/o/{token}", get().to(routes::track_open))
+            .route("/r/{token}", get().to(routes::track_click))
             .service(
                 web::scope("/admin")
+                    .wrap(from_fn(reject_invalid_csrf_tokens))
                     .wrap(from_fn(reject_anonymous_users))
                     .route("/dashboard", get().to(routes::admin_dashboard))
+                    .route("/audit", get().to(routes::audit_log_page))
+                    .route("/audit/export", get().to(routes::export_audit_log))
                     .route("/password", get().to(routes::change_password_form))
                     .route("/password", post().to(routes::change_password))
+                    .route("/lockouts", get().to(routes::lockouts_page))
+                    .route("/lockouts/clear", post().to(routes::clear_lockout))
+                    .route("/sessions", get().to(routes::sessions_page))
+                    .route("/sessions/revoke", post().to(routes::revoke_session))
+                    .route(
+                        "/sessions/revoke_all",
+                        post().to(routes::revoke_all_sessions),
+                    )
+                    .route("/totp", get().to(routes::totp_enrollment_form))
+                    .route("/totp", post().to(routes::enable_totp))
                     .route("/newsletter", get().to(routes::get_newsletter_page))
                     .route("/newsletter", post().to(routes::send_newsletter))
+                    .route("/subscribers", get().to(routes::subscribers_page))
+                    .route(
+                        "/subscribers/tags",
+                        post().to(routes::change_subscriber_tag),
+                    )
+                    .route("/lists", get().to(routes::lists_page))
+                    .route("/lists", post().to(routes::create_list))
+                    .route("/sequences", get().to(routes::sequences_page))
+                    .route("/sequences", post().to(routes::create_sequence))
+                    .route("/sequences/steps", post().to(routes::add_sequence_step))
+                    .route(
+                        "/confirmation-email",
+                        get().to(routes::confirmation_email_page),
+                    )
+                    .route(
+                        "/confirmation-email",
+                        post().to(routes::edit_confirmation_email),
+                    )
+                    .route("/segments", get().to(routes::segments_page))
+                    .route("/segments", post().to(routes::create_segment))
                     .route("/logout", post().to(routes::logout)),
             )
             .app_data(db_connection.clone())
             .app_data(email_client.clone())
             .app_data(base_url.clone())
+            .app_data(preference_links.clone())
             .app_data(hmac_secret_data.clone())
+            .app_data(login_throttle.clone())
+            .app_data(subscribe_rate_limiter.clone())
+            .app_data(bot_protection.clone())
+            .app_data(email_domain_policy.clone())
+            .app_data(email_webhooks.clone())
+            .app_data(tracker.clone())
+            .app_data(session_registry.clone())
+            .app_data(password_policy.clone())
+            .app_data(password_hashing.clone())
+            .app_data(metrics_settings.clone())
+            .app_data(localization.clone())
+            .app_data(readiness_probe.clone())
+            .app_data(background_tasks.clone())
+            .app_data(in_flight_requests.clone())
+            .app_data(trusted_proxies.clone())
     })
+    // Signals are handled by `Application::run_until_stopped`.
+    .disable_signals()
     .listen(listener)?
     .run();
     Ok(server)
@@ -75,24 +240,19 @@ pub async fn run(
 pub struct Application {
     pub port: u16,
     pub server: Server,
+    background_tasks: BackgroundTasks,
+    in_flight_requests: InFlightRequests,
+    shutdown_timeout: Duration,
 }
 
 impl Application {
     pub async fn build(configuration: Settings) -> Result<Self, anyhow::Error> {
         let connection_pool = get_connection_pool(&configuration.database);
-        let sender_email = configuration
+        let email_client = configuration
             .email_configuration
-            .sender()
+            .client()
             .expect("Invalid sender email");
 
-        let timeout = configuration.email_configuration.timeout();
-        let email_client = EmailClient::new(
-            configuration.email_configuration.base_url,
-            sender_email,
-            configuration.email_configuration.authorization_token,
-            timeout,
-        );
-
         let address = format!(
             "{}:{}",
             configuration.application_settings.host, configuration.application_settings.port
@@ -100,24 +260,82 @@ impl Application {
 
         let listener = TcpListener::bind(address)?;
         let port = listener.local_addr().unwrap().port();
+        let shutdown_timeout = configuration.shutdown.timeout();
+        let background_tasks = BackgroundTasks::new();
+        let in_flight_requests = InFlightRequests::default();
         let server = run(
             listener,
             connection_pool,
             email_client,
-            configuration.application_settings.base_url,
-            configuration.application_settings.hmac_secret,
-            configuration.redis_url,
+            configuration,
+            background_tasks.clone(),
+            in_flight_requests.clone(),
         )
         .await?;
-        Ok(Self { port, server })
+        Ok(Self {
+            port,
+            server,
+            background_tasks,
+            in_flight_requests,
+            shutdown_timeout,
+        })
     }
 
     pub fn port(&self) -> u16 {
         self.port
     }
 
+    /// Serve until SIGINT or SIGTERM, then shut down gracefully.
     pub async fn run_until_stopped(self) -> Result<(), std::io::Error> {
-        self.server.await
+        self.run_until(shutdown_signal()).await
+    }
+
+    /// Serve until `shutdown` resolves. New connections are refused straight
+    /// away, in-flight requests and then background tasks get up to the
+    /// configured timeout to finish.
+    pub async fn run_until(self, shutdown: impl Future<Output = ()>) -> Result<(), std::io::Error> {
+        let Self {
+            mut server,
+            background_tasks,
+            in_flight_requests,
+            shutdown_timeout,
+            ..
+        } = self;
+        tokio::select! {
+            result = &mut server => return result,
+            _ = shutdown => {}
+        }
+
+        tracing::info!("Shutting down, no longer accepting connections");
+        let started_at = Instant::now();
+        let handle = server.handle();
+        // actix-server's own graceful stop can tear a worker down, in-flight
+        // requests included, before it gets to wait for them: pause the
+        // listener and wait for the requests here instead. The server has to
+        // be polled meanwhile for the commands to be carried out.
+        let drain_requests = async {
+            handle.pause().await;
+            let drained = tokio::time::timeout(shutdown_timeout, in_flight_requests.idle())
+                .await
+                .is_ok();
+            let abandoned_requests = in_flight_requests.count();
+            handle.stop(false).await;
+            if !drained {
+                tracing::warn!(abandoned_requests, "Gave up waiting for in-flight requests");
+            }
+            abandoned_requests
+        };
+        let (result, abandoned_requests) = tokio::join!(server, drain_requests);
+
+        let drained = background_tasks.drain(shutdown_timeout).await;
+        tracing::info!(
+            elapsed_ms = started_at.elapsed().as_millis() as u64,
+            abandoned_requests,
+            background_tasks_finished = drained.finished,
+            background_tasks_abandoned = drained.abandoned,
+            "Shutdown complete"
+        );
+        result
     }
 }
 
@@ -1,3 +1,11 @@
+mod otlp;
+mod trace_context;
+
+pub use otlp::{flush_tracer_provider, tracer_provider};
+pub use trace_context::{trace_context_headers, TraceContextRootSpanBuilder};
+
+use opentelemetry::{global, trace::TracerProvider as _};
+use opentelemetry_sdk::{propagation::TraceContextPropagator, trace::TracerProvider};
 use tokio::task::JoinHandle;
 use tracing::{dispatcher::set_global_default, Subscriber};
 use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
@@ -16,10 +24,14 @@ use tracing_subscriber::{
 /// We need to explicitly call out that the returned subscriber is
 /// `Send` and `SynC` to make it possible to pass it to `init_subscriber`
 /// later on
+///
+/// Spans are also handed to OpenTelemetry through `tracer_provider`, which
+/// exports them if it was built with an exporter.
 pub fn get_subscriber<Sink>(
     name: String,
     env_filter: String,
     sink: Sink,
+    tracer_provider: &TracerProvider,
 ) -> impl Subscriber + Send + Sync
 where
     Sink: for<'a> MakeWriter<'a> + Send + Sync + 'static,
@@ -27,20 +39,28 @@ where
     let env_filter =
         EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(env_filter));
 
-    let formatting_layer = BunyanFormattingLayer::new(name, sink);
+    let formatting_layer = BunyanFormattingLayer::new(name.clone(), sink);
+    let opentelemetry_layer =
+        tracing_opentelemetry::layer().with_tracer(tracer_provider.tracer(name));
 
     Registry::default()
         .with(env_filter)
         .with(JsonStorageLayer)
+        .with(opentelemetry_layer)
         .with(formatting_layer)
 }
 
 /// Register a subscriber as global default to process span data
 ///
 /// It should only be called once!
+/// It also installs the W3C trace context propagator and reports
+/// OpenTelemetry errors, such as failed exports, through `tracing`.
 pub fn init_subscriber(subscriber: impl Subscriber + Send + Sync) {
     LogTracer::init().expect("Failed to init log tracer");
     set_global_default(subscriber.into()).expect("Failed to init subscriber");
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    global::set_error_handler(|error| tracing::warn!(%error, "OpenTelemetry error"))
+        .expect("Failed to init OpenTelemetry error handler");
 }
 
 pub fn spawn_blocking_with_tracing<F, R>(f: F) -> JoinHandle<R>
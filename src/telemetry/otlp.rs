@@ -0,0 +1,50 @@
+use anyhow::Context;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{
+    runtime,
+    trace::{self, Sampler, TracerProvider},
+    Resource,
+};
+
+use crate::configuration::TelemetrySettings;
+
+/// The provider of the tracer that turns `tracing` spans into OpenTelemetry
+/// spans.
+///
+/// Without an OTLP endpoint nothing is exported, but spans still get trace
+/// and span ids so that the trace context is propagated to the services we
+/// call.
+pub fn tracer_provider(settings: &TelemetrySettings) -> Result<TracerProvider, anyhow::Error> {
+    // Traces started by a caller follow the caller's sampling decision.
+    let sampler = Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+        settings.sampling_ratio,
+    )));
+    let config = trace::config()
+        .with_sampler(sampler)
+        .with_resource(Resource::new([KeyValue::new(
+            "service.name",
+            settings.service_name.clone(),
+        )]));
+    let mut builder = TracerProvider::builder().with_config(config);
+    if let Some(endpoint) = &settings.otlp_endpoint {
+        let exporter = opentelemetry_otlp::new_exporter()
+            .http()
+            .with_endpoint(endpoint.trim_end_matches('/'))
+            .build_span_exporter()
+            .context("Failed to build the OTLP exporter")?;
+        builder = builder.with_batch_exporter(exporter, runtime::Tokio);
+    }
+    Ok(builder.build())
+}
+
+/// Export the spans still buffered. Called on shutdown.
+pub async fn flush_tracer_provider(provider: TracerProvider) {
+    // Flushing blocks until the exporter is done.
+    let results = tokio::task::spawn_blocking(move || provider.force_flush()).await;
+    for result in results.into_iter().flatten() {
+        if let Err(e) = result {
+            tracing::warn!(error = %e, "Failed to flush spans");
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    http::header::{HeaderMap, USER_AGENT},
+    Error,
+};
+use opentelemetry::{global, propagation::Extractor, trace::TraceContextExt};
+use opentelemetry_http::HeaderInjector;
+use tracing::{field::Empty, Span};
+use tracing_actix_web::{DefaultRootSpanBuilder, RootSpanBuilder};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use crate::{request_id::RequestId, utils::client_ip};
+
+/// Headers carrying the trace context of the current span, to send along
+/// with outgoing requests.
+pub fn trace_context_headers() -> reqwest::header::HeaderMap {
+    let mut headers = reqwest::header::HeaderMap::new();
+    let context = Span::current().context();
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&context, &mut HeaderInjector(&mut headers))
+    });
+    headers
+}
+
+struct RequestHeaders<'a>(&'a HeaderMap);
+
+impl Extractor for RequestHeaders<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|name| name.as_str()).collect()
+    }
+}
+
+/// Root span for incoming requests that continues the caller's trace when
+/// the request carries a `traceparent` header.
+///
+/// It has the fields of `tracing_actix_web::root_span!`, except that
+/// `request_id` is the one assigned by
+/// [`assign_request_id`](crate::request_id::assign_request_id), which may
+/// come from the caller.
+pub struct TraceContextRootSpanBuilder;
+
+impl RootSpanBuilder for TraceContextRootSpanBuilder {
+    fn on_request_start(request: &ServiceRequest) -> Span {
+        let request_id = RequestId::of(request).unwrap_or_else(RequestId::generate);
+        let method = request.method().as_str();
+        let route = request
+            .match_pattern()
+            .unwrap_or_else(|| "default".to_string());
+        let connection_info = request.connection_info();
+        let span = tracing::info_span!(
+            "HTTP request",
+            http.method = %method,
+            http.route = %route,
+            http.flavor = ?request.version(),
+            http.scheme = %connection_info.scheme(),
+            http.host = %connection_info.host(),
+            http.client_ip = %client_ip(request.request()),
+            http.user_agent = %request
+                .headers()
+                .get(USER_AGENT)
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or(""),
+            http.target = %request.uri().path_and_query().map_or("", |p| p.as_str()),
+            http.status_code = Empty,
+            otel.name = %format!("HTTP {method} {route}"),
+            otel.kind = "server",
+            otel.status_code = Empty,
+            trace_id = Empty,
+            request_id = %request_id,
+            exception.message = Empty,
+            exception.details = Empty,
+        );
+        drop(connection_info);
+
+        let parent = global::get_text_map_propagator(|propagator| {
+            propagator.extract(&RequestHeaders(request.headers()))
+        });
+        span.set_parent(parent);
+        let trace_id = span.context().span().span_context().trace_id();
+        span.record("trace_id", tracing::field::display(trace_id));
+        span
+    }
+
+    fn on_request_end<B: MessageBody>(span: Span, outcome: &Result<ServiceResponse<B>, Error>) {
+        DefaultRootSpanBuilder::on_request_end(span, outcome);
+    }
+}
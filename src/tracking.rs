@@ -0,0 +1,276 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+use uuid::Uuid;
+
+/// What a newsletter issue asked to be tracked.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TrackingOptions {
+    pub opens: bool,
+    pub clicks: bool,
+}
+
+/// Who a tracking link was sent to, and where it leads for clicks.
+#[derive(Debug, PartialEq, Eq)]
+pub struct TrackedRecipient {
+    pub newsletter_issue_id: Uuid,
+    pub subscriber_id: Uuid,
+    pub url: Option<String>,
+}
+
+/// Signs and checks the links embedded in newsletter issues to record
+/// opens and clicks.
+///
+/// Tokens are signed so that the redirect endpoint cannot be used to send
+/// people anywhere, and so that stats cannot be made up.
+pub struct Tracker {
+    key: Secret<String>,
+    base_url: String,
+    /// Off for privacy-sensitive lists: nothing gets recorded, whatever
+    /// issues ask for.
+    enabled: bool,
+}
+
+impl Tracker {
+    pub fn new(key: Secret<String>, base_url: String, enabled: bool) -> Self {
+        Self {
+            key,
+            base_url,
+            enabled,
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// The HTML body of an issue for one subscriber, with links going
+    /// through the click redirect and an open pixel at the end, as asked
+    /// by `options`.
+    pub fn instrument_html(
+        &self,
+        html: &str,
+        newsletter_issue_id: Uuid,
+        subscriber_id: Uuid,
+        options: TrackingOptions,
+    ) -> String {
+        if !self.enabled {
+            return html.to_string();
+        }
+        let mut html = if options.clicks {
+            rewrite_links(html, |url| {
+                let recipient = TrackedRecipient {
+                    newsletter_issue_id,
+                    subscriber_id,
+                    url: Some(url.to_string()),
+                };
+                format!("{}/r/{}", self.base_url, self.sign(&recipient))
+            })
+        } else {
+            html.to_string()
+        };
+        if options.opens {
+            let recipient = TrackedRecipient {
+                newsletter_issue_id,
+                subscriber_id,
+                url: None,
+            };
+            let pixel = format!(
+                r#"<img src="{}/o/{}" width="1" height="1" alt="" style="display:none">"#,
+                self.base_url,
+                self.sign(&recipient)
+            );
+            match html.rfind("</body>") {
+                Some(end) => html.insert_str(end, &pixel),
+                None => html.push_str(&pixel),
+            }
+        }
+        html
+    }
+
+    fn sign(&self, recipient: &TrackedRecipient) -> String {
+        let payload = format!(
+            "{}\n{}\n{}",
+            recipient.newsletter_issue_id,
+            recipient.subscriber_id,
+            recipient.url.as_deref().unwrap_or_default()
+        );
+        let payload = URL_SAFE_NO_PAD.encode(payload);
+        let tag = self.mac(&payload).finalize().into_bytes();
+        format!("{payload}.{}", hex::encode(tag))
+    }
+
+    fn mac(&self, payload: &str) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.key.expose_secret().as_bytes())
+            .expect("HMAC can take a key of any size");
+        mac.update(format!("tracking:{payload}").as_bytes());
+        mac
+    }
+
+    /// The recipient a token was signed for, unless it was tampered with.
+    pub fn verify(&self, token: &str) -> Option<TrackedRecipient> {
+        let (payload, tag) = token.split_once('.')?;
+        self.mac(payload)
+            .verify_slice(&hex::decode(tag).ok()?)
+            .ok()?;
+        let payload = String::from_utf8(URL_SAFE_NO_PAD.decode(payload).ok()?).ok()?;
+        let mut fields = payload.splitn(3, '\n');
+        let newsletter_issue_id = fields.next()?.parse().ok()?;
+        let subscriber_id = fields.next()?.parse().ok()?;
+        let url = Some(fields.next()?.to_string()).filter(|url| !url.is_empty());
+        Some(TrackedRecipient {
+            newsletter_issue_id,
+            subscriber_id,
+            url,
+        })
+    }
+}
+
+/// Replace the target of every `href` pointing to an http(s) URL. Other
+/// links, such as `mailto:` ones and anchors, are left alone.
+fn rewrite_links(html: &str, mut rewrite: impl FnMut(&str) -> String) -> String {
+    let mut output = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = find_ignore_case(rest, "href=") {
+        let (before, after) = rest.split_at(start + "href=".len());
+        output.push_str(before);
+        let Some(quote) = after.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+            rest = after;
+            continue;
+        };
+        let Some(end) = after[1..].find(quote) else {
+            rest = after;
+            continue;
+        };
+        let value = &after[1..=end];
+        let url = htmlescape::decode_html(value).unwrap_or_else(|_| value.to_string());
+        let lowercase = url.to_ascii_lowercase();
+        if lowercase.starts_with("http://") || lowercase.starts_with("https://") {
+            output.push(quote);
+            output.push_str(&rewrite(&url));
+            output.push(quote);
+        } else {
+            output.push_str(&after[..=end + 1]);
+        }
+        rest = &after[end + 2..];
+    }
+    output.push_str(rest);
+    output
+}
+
+fn find_ignore_case(haystack: &str, needle: &str) -> Option<usize> {
+    haystack
+        .as_bytes()
+        .windows(needle.len())
+        .position(|window| window.eq_ignore_ascii_case(needle.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+    use uuid::Uuid;
+
+    use super::{rewrite_links, TrackedRecipient, Tracker, TrackingOptions};
+
+    fn tracker(enabled: bool) -> Tracker {
+        Tracker::new(
+            Secret::new("secret".into()),
+            "https://news.example".into(),
+            enabled,
+        )
+    }
+
+    #[test]
+    fn only_http_links_are_rewritten() {
+        let html = r##"<a href="https://example.com/a?x=1&amp;y=2">A</a>
+<A HREF='http://example.com/b'>B</A>
+<a href="mailto:ursula@example.com">Mail</a> <a href="#top">Top</a> <a href=bare>C</a>"##;
+        let mut seen = Vec::new();
+        let rewritten = rewrite_links(html, |url| {
+            seen.push(url.to_string());
+            format!("/r/{}", seen.len())
+        });
+        assert_eq!(
+            seen,
+            ["https://example.com/a?x=1&y=2", "http://example.com/b"]
+        );
+        assert_eq!(
+            rewritten,
+            r##"<a href="/r/1">A</a>
+<A HREF='/r/2'>B</A>
+<a href="mailto:ursula@example.com">Mail</a> <a href="#top">Top</a> <a href=bare>C</a>"##
+        );
+    }
+
+    #[test]
+    fn tracked_links_lead_back_to_the_recipient_and_url() {
+        let tracker = tracker(true);
+        let (issue, subscriber) = (Uuid::new_v4(), Uuid::new_v4());
+        let html = tracker.instrument_html(
+            r#"<html><body><a href="https://example.com/">Read</a></body></html>"#,
+            issue,
+            subscriber,
+            TrackingOptions {
+                opens: true,
+                clicks: true,
+            },
+        );
+
+        let token = |prefix: &str| {
+            let start = html.find(prefix).unwrap() + prefix.len();
+            html[start..].split('"').next().unwrap().to_string()
+        };
+        assert_eq!(
+            tracker.verify(&token("https://news.example/r/")),
+            Some(TrackedRecipient {
+                newsletter_issue_id: issue,
+                subscriber_id: subscriber,
+                url: Some("https://example.com/".into()),
+            })
+        );
+        assert_eq!(
+            tracker.verify(&token("https://news.example/o/")),
+            Some(TrackedRecipient {
+                newsletter_issue_id: issue,
+                subscriber_id: subscriber,
+                url: None,
+            })
+        );
+        assert!(html.ends_with(r#"style="display:none"></body></html>"#));
+    }
+
+    #[test]
+    fn tampered_tokens_are_rejected() {
+        let tracker = tracker(true);
+        let token = tracker.sign(&TrackedRecipient {
+            newsletter_issue_id: Uuid::new_v4(),
+            subscriber_id: Uuid::new_v4(),
+            url: Some("https://example.com/".into()),
+        });
+        let (payload, tag) = token.split_once('.').unwrap();
+        let forged = tracker.sign(&TrackedRecipient {
+            newsletter_issue_id: Uuid::new_v4(),
+            subscriber_id: Uuid::new_v4(),
+            url: Some("https://evil.example/".into()),
+        });
+        let (forged_payload, _) = forged.split_once('.').unwrap();
+
+        assert!(tracker.verify(&format!("{forged_payload}.{tag}")).is_none());
+        assert!(tracker.verify(payload).is_none());
+        assert!(tracker.verify("garbage").is_none());
+    }
+
+    #[test]
+    fn nothing_is_tracked_when_disabled() {
+        let html = r#"<a href="https://example.com/">Read</a>"#;
+        let options = TrackingOptions {
+            opens: true,
+            clicks: true,
+        };
+        assert_eq!(
+            tracker(false).instrument_html(html, Uuid::new_v4(), Uuid::new_v4(), options),
+            html
+        );
+    }
+}
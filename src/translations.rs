@@ -0,0 +1,274 @@
+//! The copy of the emails and pages subscribers see, in every [`Locale`].
+
+use chrono::{DateTime, Utc};
+
+use crate::domain::Locale::{self, English, French, German};
+
+impl Locale {
+    pub fn home_title(self) -> &'static str {
+        match self {
+            English => "Home",
+            French => "Accueil",
+            German => "Startseite",
+        }
+    }
+
+    pub fn home_intro(self) -> &'static str {
+        match self {
+            English => "Welcome to my newsletter",
+            French => "Bienvenue sur ma lettre d'information",
+            German => "Willkommen bei meinem Newsletter",
+        }
+    }
+
+    pub fn name_label(self) -> &'static str {
+        match self {
+            English | German => "Name",
+            French => "Nom",
+        }
+    }
+
+    pub fn email_label(self) -> &'static str {
+        match self {
+            English => "Email",
+            French => "E-mail",
+            German => "E-Mail",
+        }
+    }
+
+    pub fn list_label(self) -> &'static str {
+        match self {
+            English | German => "Newsletter",
+            French => "Lettre d'information",
+        }
+    }
+
+    pub fn subscribe_button(self) -> &'static str {
+        match self {
+            English => "Subscribe",
+            French => "S'abonner",
+            German => "Abonnieren",
+        }
+    }
+
+    pub fn confirmation_subject(self, list: &str) -> String {
+        match self {
+            English => format!("Welcome to {list}!"),
+            French => format!("Bienvenue dans {list} !"),
+            German => format!("Willkommen bei {list}!"),
+        }
+    }
+
+    pub fn confirmation_html(self, list: &str, confirmation_link: &str) -> String {
+        let welcome = self.confirmation_subject(&htmlescape::encode_minimal(list));
+        let visit = match self {
+            English => {
+                format!("Visit <a href=\"{confirmation_link}\">here</a> to confirm your subscription")
+            }
+            French => format!(
+                "Cliquez <a href=\"{confirmation_link}\">ici</a> pour confirmer votre abonnement"
+            ),
+            German => format!(
+                "Klicken Sie <a href=\"{confirmation_link}\">hier</a>, um Ihr Abonnement zu bestätigen"
+            ),
+        };
+        format!("<html>{welcome}<br/>\n{visit}</html>")
+    }
+
+    pub fn confirmation_text(self, list: &str, confirmation_link: &str) -> String {
+        let welcome = self.confirmation_subject(list);
+        let visit = match self {
+            English => format!("Visit {confirmation_link} to confirm your subscription"),
+            French => {
+                format!("Rendez-vous sur {confirmation_link} pour confirmer votre abonnement")
+            }
+            German => format!("Besuchen Sie {confirmation_link}, um Ihr Abonnement zu bestätigen"),
+        };
+        format!("{welcome}\n{visit}")
+    }
+
+    pub fn subscription_confirmed(self, list: &str) -> String {
+        match self {
+            English => format!("Your subscription to {list} is confirmed."),
+            French => format!("Votre abonnement à {list} est confirmé."),
+            German => format!("Ihr Abonnement von {list} ist bestätigt."),
+        }
+    }
+
+    pub fn email_change_subject(self) -> &'static str {
+        match self {
+            English => "Confirm your new email address",
+            French => "Confirmez votre nouvelle adresse e-mail",
+            German => "Bestätigen Sie Ihre neue E-Mail-Adresse",
+        }
+    }
+
+    pub fn email_change_html(self, confirmation_link: &str) -> String {
+        let visit = match self {
+            English => format!(
+                "Visit <a href=\"{confirmation_link}\">here</a> to receive the newsletter at this address from now on."
+            ),
+            French => format!(
+                "Cliquez <a href=\"{confirmation_link}\">ici</a> pour recevoir désormais la lettre d'information à cette adresse."
+            ),
+            German => format!(
+                "Klicken Sie <a href=\"{confirmation_link}\">hier</a>, um den Newsletter künftig an diese Adresse zu erhalten."
+            ),
+        };
+        format!("<html>{visit}</html>")
+    }
+
+    pub fn email_change_text(self, confirmation_link: &str) -> String {
+        match self {
+            English => format!(
+                "Visit {confirmation_link} to receive the newsletter at this address from now on."
+            ),
+            French => format!(
+                "Rendez-vous sur {confirmation_link} pour recevoir désormais la lettre d'information à cette adresse."
+            ),
+            German => format!(
+                "Besuchen Sie {confirmation_link}, um den Newsletter künftig an diese Adresse zu erhalten."
+            ),
+        }
+    }
+
+    pub fn invalid_confirmation_link(self) -> &'static str {
+        match self {
+            English => "This confirmation link is invalid or has expired.",
+            French => "Ce lien de confirmation est invalide ou a expiré.",
+            German => "Dieser Bestätigungslink ist ungültig oder abgelaufen.",
+        }
+    }
+
+    pub fn already_subscribed(self, email: &str) -> String {
+        match self {
+            English => format!("{email} is subscribed already."),
+            French => format!("{email} est déjà abonné."),
+            German => format!("{email} ist bereits angemeldet."),
+        }
+    }
+
+    pub fn preferences_title(self) -> &'static str {
+        match self {
+            English => "Your subscription",
+            French => "Votre abonnement",
+            German => "Ihr Abonnement",
+        }
+    }
+
+    pub fn change_name_button(self) -> &'static str {
+        match self {
+            English => "Change name",
+            French => "Changer de nom",
+            German => "Namen ändern",
+        }
+    }
+
+    pub fn email_heading(self) -> &'static str {
+        match self {
+            English => "Email address",
+            French => "Adresse e-mail",
+            German => "E-Mail-Adresse",
+        }
+    }
+
+    pub fn writing_to(self, email: &str) -> String {
+        match self {
+            English => format!("We write to {email}."),
+            French => format!("Nous vous écrivons à {email}."),
+            German => format!("Wir schreiben an {email}."),
+        }
+    }
+
+    pub fn awaiting_confirmation(self, email: &str) -> String {
+        match self {
+            English => format!("Waiting for you to confirm {email}."),
+            French => format!("En attente de votre confirmation de {email}."),
+            German => format!("Wir warten auf Ihre Bestätigung von {email}."),
+        }
+    }
+
+    pub fn new_email_placeholder(self) -> &'static str {
+        match self {
+            English => "New email address",
+            French => "Nouvelle adresse e-mail",
+            German => "Neue E-Mail-Adresse",
+        }
+    }
+
+    pub fn change_email_button(self) -> &'static str {
+        match self {
+            English => "Change address",
+            French => "Changer d'adresse",
+            German => "Adresse ändern",
+        }
+    }
+
+    pub fn lists_heading(self) -> &'static str {
+        match self {
+            English => "Newsletters",
+            French => "Lettres d'information",
+            German => "Newsletter",
+        }
+    }
+
+    pub fn undeliverable(self, list: &str) -> String {
+        match self {
+            English => format!("{list}: we cannot deliver to this address."),
+            French => format!("{list} : nous ne pouvons pas écrire à cette adresse."),
+            German => format!("{list}: An diese Adresse können wir nicht zustellen."),
+        }
+    }
+
+    pub fn save_button(self) -> &'static str {
+        match self {
+            English => "Save",
+            French => "Enregistrer",
+            German => "Speichern",
+        }
+    }
+
+    pub fn pause_heading(self) -> &'static str {
+        match self {
+            English => "Take a break",
+            French => "Faire une pause",
+            German => "Eine Pause einlegen",
+        }
+    }
+
+    pub fn paused_until(self, until: &DateTime<Utc>) -> String {
+        let date = until.format("%Y-%m-%d");
+        match self {
+            English => format!("Delivery is paused until {date}."),
+            French => format!("L'envoi est suspendu jusqu'au {date}."),
+            German => format!("Die Zustellung ist bis {date} pausiert."),
+        }
+    }
+
+    pub fn weeks(self, weeks: u32) -> String {
+        match (self, weeks) {
+            (English, 1) => "1 week".into(),
+            (English, _) => format!("{weeks} weeks"),
+            (French, 1) => "1 semaine".into(),
+            (French, _) => format!("{weeks} semaines"),
+            (German, 1) => "1 Woche".into(),
+            (German, _) => format!("{weeks} Wochen"),
+        }
+    }
+
+    pub fn pause_button(self) -> &'static str {
+        match self {
+            English => "Pause delivery",
+            French => "Suspendre l'envoi",
+            German => "Zustellung pausieren",
+        }
+    }
+
+    pub fn resume_button(self) -> &'static str {
+        match self {
+            English => "Resume delivery",
+            French => "Reprendre l'envoi",
+            German => "Zustellung fortsetzen",
+        }
+    }
+}
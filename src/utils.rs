@@ -1,4 +1,10 @@
-use actix_web::{error::ErrorInternalServerError, http::header::LOCATION, Error, HttpResponse};
+use actix_web::{
+    error::ErrorInternalServerError,
+    http::header::{ACCEPT_LANGUAGE, LOCATION, USER_AGENT},
+    web, Error, HttpRequest, HttpResponse,
+};
+use askama::Template;
+use std::net::IpAddr;
 
 pub fn e500<T>(e: T) -> Error
 where
@@ -12,3 +18,47 @@ pub fn see_other(location: &str) -> HttpResponse {
         .insert_header((LOCATION, location))
         .finish()
 }
+
+/// Render a page template into a `200 OK` HTML response.
+pub fn render<T: Template>(template: &T) -> Result<HttpResponse, Error> {
+    let body = template.render().map_err(e500)?;
+    Ok(HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(body))
+}
+
+/// Reverse proxies whose forwarding headers are believed.
+#[derive(Clone, Default)]
+pub struct TrustedProxies(pub Vec<IpAddr>);
+
+/// The client address. `Forwarded`/`X-Forwarded-For` are only honoured on
+/// connections from a trusted proxy.
+pub fn client_ip(request: &HttpRequest) -> String {
+    let from_trusted_proxy = request
+        .app_data::<web::Data<TrustedProxies>>()
+        .zip(request.peer_addr())
+        .is_some_and(|(proxies, peer)| proxies.0.contains(&peer.ip()));
+    let connection_info = request.connection_info();
+    let address = if from_trusted_proxy {
+        connection_info.realip_remote_addr()
+    } else {
+        connection_info.peer_addr()
+    };
+    address.unwrap_or("unknown").to_string()
+}
+
+pub fn user_agent(request: &HttpRequest) -> String {
+    request
+        .headers()
+        .get(USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("unknown")
+        .to_string()
+}
+
+pub fn accept_language(request: &HttpRequest) -> Option<&str> {
+    request
+        .headers()
+        .get(ACCEPT_LANGUAGE)
+        .and_then(|value| value.to_str().ok())
+}
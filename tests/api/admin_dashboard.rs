@@ -1,4 +1,6 @@
 use crate::helpers::{assert_is_redirect_to, spawn_app};
+use wiremock::matchers::any;
+use wiremock::{Mock, ResponseTemplate};
 
 #[tokio::test]
 async fn you_must_be_logged_in_to_access_the_admin_dashboard() {
@@ -35,3 +37,85 @@ async fn logout_clears_session_state() {
     let response = app.get_admin_dashboard().await;
     assert_is_redirect_to(&response, "/login");
 }
+
+#[tokio::test]
+async fn the_dashboard_summarises_subscriber_growth() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com")
+        .await;
+    app.create_unconfirmed_subscriber("butler", "octavia_butler@gmail.com")
+        .await;
+    app.log_in().await;
+
+    let html_page = app.get_admin_dashboard_html().await;
+
+    assert!(html_page.contains("<td>confirmed</td><td>1</td>"));
+    assert!(html_page.contains("<td>pending_confirmation</td><td>1</td>"));
+    assert!(html_page.contains("Last 30 days"));
+    assert!(html_page.contains("New subscriptions: 2"));
+    assert!(html_page.contains("Confirmations: 1"));
+    assert!(html_page.contains("Confirmation rate: 50.0%"));
+    assert!(html_page.contains("Unsubscribe rate: 0.0%"));
+}
+
+#[tokio::test]
+async fn rates_are_not_available_without_subscribers() {
+    let app = spawn_app().await;
+    app.log_in().await;
+
+    let html_page = app.get_admin_dashboard_html().await;
+
+    assert!(html_page.contains("Confirmation rate: n/a"));
+    assert!(html_page.contains("Unsubscribe rate: n/a"));
+    assert!(html_page.contains("No issues have been published yet."));
+}
+
+#[tokio::test]
+async fn the_dashboard_reports_deliveries_of_recent_issues() {
+    let app = spawn_app().await;
+    for (name, email) in [
+        ("le guin", "ursula_le_guin@gmail.com"),
+        ("butler", "octavia_butler@gmail.com"),
+        ("jemisin", "nk_jemisin@gmail.com"),
+    ] {
+        app.create_confirmed_subscriber(name, email).await;
+    }
+    app.log_in().await;
+
+    // The last delivery fails.
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .up_to_n_times(2)
+        .mount(&app.email_server)
+        .await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&app.email_server)
+        .await;
+    let response = app
+        .post_newsletters(serde_json::json!({
+            "title": "Autumn issue",
+            "content": "Newsletter content",
+            "html_content": "<p>Newsletter content</p>",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(html_page.contains("<td>Autumn issue</td>"));
+    assert!(html_page.contains("<td>2</td><td>1</td><td>not tracked</td>"));
+}
+
+#[tokio::test]
+async fn the_reporting_period_can_be_extended_to_90_days() {
+    let app = spawn_app().await;
+    app.log_in().await;
+
+    let html_page = app.get_admin_dashboard_stats_html("days=90").await;
+    assert!(html_page.contains("Last 90 days"));
+    assert!(html_page.contains(r#"<a href="/admin/dashboard?days=30">30 days</a>"#));
+
+    // Unsupported periods fall back to 30 days.
+    let html_page = app.get_admin_dashboard_stats_html("days=7").await;
+    assert!(html_page.contains("Last 30 days"));
+}
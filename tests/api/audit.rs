@@ -0,0 +1,139 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_the_audit_log() {
+    let app = spawn_app().await;
+
+    let response = app.get_audit_log_export("").await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn logins_and_logouts_are_recorded() {
+    let app = spawn_app().await;
+    app.post_login_form(&serde_json::json!({
+        "username": &app.test_user.name,
+        "password": "wrong-password"
+    }))
+    .await;
+    app.log_in().await;
+    app.post_logout().await;
+    app.log_in().await;
+
+    let html_page = app.get_audit_log_html("").await;
+    assert_eq!(html_page.matches("<td>login_succeeded</td>").count(), 2);
+    assert_eq!(html_page.matches("<td>login_failed</td>").count(), 1);
+    assert_eq!(html_page.matches("<td>logout</td>").count(), 1);
+    assert!(html_page.contains("invalid_credentials"));
+}
+
+#[tokio::test]
+async fn a_password_change_is_recorded() {
+    let app = spawn_app().await;
+    app.log_in().await;
+
+    let new_password = "correct horse battery staple";
+    app.post_change_password(&serde_json::json!({
+        "password": &app.test_user.password,
+        "new_password": new_password,
+        "confirm_password": new_password,
+    }))
+    .await;
+
+    let html_page = app.get_audit_log_html("action=password_changed").await;
+    assert!(html_page.contains("<td>password_changed</td>"));
+    assert!(!html_page.contains("<td>login_succeeded</td>"));
+}
+
+#[tokio::test]
+async fn the_audit_log_can_be_filtered_by_actor_and_date() {
+    let app = spawn_app().await;
+    app.log_in().await;
+
+    let html_page = app
+        .get_audit_log_html(&format!("actor={}", app.test_user.name))
+        .await;
+    assert!(html_page.contains("<td>login_succeeded</td>"));
+
+    let html_page = app.get_audit_log_html("actor=somebody-else").await;
+    assert!(!html_page.contains("<td>login_succeeded</td>"));
+
+    let html_page = app.get_audit_log_html("until=2000-01-01").await;
+    assert!(!html_page.contains("<td>login_succeeded</td>"));
+
+    let html_page = app.get_audit_log_html("since=yesterday").await;
+    assert!(html_page.contains("Invalid date: yesterday"));
+}
+
+#[tokio::test]
+async fn the_audit_log_can_be_exported_as_csv() {
+    let app = spawn_app().await;
+    app.log_in().await;
+
+    let response = app.get_audit_log_export("action=login_succeeded").await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers().get("content-type").unwrap(),
+        "text/csv; charset=utf-8"
+    );
+    let csv = response.text().await.unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(
+        lines[0],
+        "occurred_at,actor,action,target,ip,user_agent,details"
+    );
+    assert_eq!(lines.len(), 2);
+    assert!(lines[1].contains(&format!(r#""{}","login_succeeded""#, app.test_user.name)));
+}
+
+#[tokio::test]
+async fn admin_changes_are_recorded() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    app.insert_confirmed_subscriber("ursula@example.com").await;
+    let list_id = app.list_id("newsletter").await;
+
+    for (path, body) in [
+        (
+            "/admin/subscribers/tags",
+            serde_json::json!({ "email": "ursula@example.com", "tag": "vip", "action": "add" }),
+        ),
+        (
+            "/admin/segments",
+            serde_json::json!({ "name": "VIPs", "tag": "vip" }),
+        ),
+        (
+            "/admin/lists",
+            serde_json::json!({ "slug": "weekly", "name": "Weekly digest" }),
+        ),
+        (
+            "/admin/sequences",
+            serde_json::json!({ "name": "Onboarding", "list_id": list_id.to_string() }),
+        ),
+    ] {
+        let response = app
+            .client
+            .post(format!("{}{path}", app.address))
+            .form(&app.with_csrf_token(&body).await)
+            .send()
+            .await
+            .expect("Failed to execute request");
+        assert_eq!(response.status().as_u16(), 303, "{path}");
+    }
+    app.get_audit_log_export("").await;
+
+    let html_page = app.get_audit_log_html("").await;
+    for action in [
+        "subscriber_tags_changed",
+        "segment_created",
+        "list_created",
+        "sequence_created",
+        "audit_log_exported",
+    ] {
+        assert!(
+            html_page.contains(&format!("<td>{action}</td>")),
+            "{action}"
+        );
+    }
+}
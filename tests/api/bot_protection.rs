@@ -0,0 +1,125 @@
+use std::time::Duration;
+
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{spawn_app, spawn_app_with, TestApp};
+
+async fn expect_emails(app: &TestApp, expected: u64) {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(expected)
+        .mount(&app.email_server)
+        .await;
+}
+
+async fn post_form(app: &TestApp, form: &[(&str, &str)]) -> reqwest::Response {
+    app.client
+        .post(format!("{}/subscriptions", app.address))
+        .form(form)
+        .send()
+        .await
+        .expect("Failed to execute request")
+}
+
+async fn subscriber_count(app: &TestApp) -> i64 {
+    sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM subscriptions"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count
+}
+
+#[tokio::test]
+async fn the_subscription_form_carries_a_honeypot_and_a_form_token() {
+    let app = spawn_app().await;
+
+    let html_page = app
+        .client
+        .get(format!("{}/", app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    assert!(html_page.contains(r#"action="/subscriptions""#));
+    assert!(html_page.contains(r#"name="website""#));
+    assert!(html_page.contains(r#"name="form_token""#));
+}
+
+#[tokio::test]
+async fn a_filled_in_honeypot_is_accepted_but_dropped() {
+    let app = spawn_app().await;
+    expect_emails(&app, 0).await;
+    let form_token = app.get_subscription_form_token().await;
+
+    let response = post_form(
+        &app,
+        &[
+            ("name", "le guin"),
+            ("email", "ursula_le_guin@gmail.com"),
+            ("website", "https://cheap-pills.example"),
+            ("form_token", &form_token),
+        ],
+    )
+    .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(subscriber_count(&app).await, 0);
+}
+
+#[tokio::test]
+async fn a_submission_without_a_valid_form_token_is_accepted_but_dropped() {
+    let app = spawn_app().await;
+    expect_emails(&app, 0).await;
+
+    for form_token in [None, Some("1700000000000.deadbeef")] {
+        let mut form = vec![("name", "le guin"), ("email", "ursula_le_guin@gmail.com")];
+        if let Some(form_token) = form_token {
+            form.push(("form_token", form_token));
+        }
+        let response = post_form(&app, &form).await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+    assert_eq!(subscriber_count(&app).await, 0);
+}
+
+#[tokio::test]
+async fn a_form_posted_too_quickly_is_accepted_but_dropped() {
+    let app = spawn_app_with(|c| c.bot_protection.min_submit_seconds = 60).await;
+    expect_emails(&app, 0).await;
+
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(subscriber_count(&app).await, 0);
+}
+
+#[tokio::test]
+async fn a_form_posted_after_the_threshold_goes_through() {
+    let app = spawn_app_with(|c| c.bot_protection.min_submit_seconds = 1).await;
+    expect_emails(&app, 1).await;
+    let form_token = app.get_subscription_form_token().await;
+
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    let response = post_form(
+        &app,
+        &[
+            ("name", "le guin"),
+            ("email", "ursula_le_guin@gmail.com"),
+            ("website", ""),
+            ("form_token", &form_token),
+        ],
+    )
+    .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(subscriber_count(&app).await, 1);
+}
@@ -90,11 +90,12 @@ async fn successful_password_change() {
     .await;
 
     // attempt to change password
+    let new_password = "correct horse battery staple";
     let response = app
         .post_change_password(&serde_json::json!({
             "password": app.test_user.password,
-            "new_password": "new_password",
-            "confirm_password": "new_password"
+            "new_password": new_password,
+            "confirm_password": new_password
         }))
         .await;
 
@@ -112,7 +113,7 @@ async fn successful_password_change() {
     let response = app
         .post_login_form(&serde_json::json!({
             "username": app.test_user.name,
-            "password": "new_password"
+            "password": new_password
         }))
         .await;
     assert_is_redirect_to(&response, "/admin/dashboard");
@@ -120,3 +121,55 @@ async fn successful_password_change() {
     let html_page = app.get_admin_dashboard_html().await;
     assert!(html_page.contains(&format!("Welcome, {}!", app.test_user.name)));
 }
+
+#[tokio::test]
+async fn new_password_must_satisfy_the_password_policy() {
+    let app = spawn_app().await;
+
+    app.post_login_form(&serde_json::json!({
+        "username": app.test_user.name,
+        "password": app.test_user.password
+    }))
+    .await;
+
+    let test_cases = vec![
+        (
+            "".to_string(),
+            "The new password must be at least 12 characters long",
+        ),
+        (
+            "a".repeat(129),
+            "The new password must be at most 128 characters long",
+        ),
+        (
+            "password1234".to_string(),
+            "The new password is too easy to guess",
+        ),
+        (
+            format!("my {} passphrase", app.test_user.name),
+            "The new password must not contain your username",
+        ),
+        (
+            app.test_user.password.clone(),
+            "The new password must be different from the current password",
+        ),
+    ];
+
+    for (new_password, expected_message) in test_cases {
+        let response = app
+            .post_change_password(&serde_json::json!({
+                "password": app.test_user.password,
+                "new_password": new_password,
+                "confirm_password": new_password
+            }))
+            .await;
+        assert_is_redirect_to(&response, "/admin/password");
+
+        let html_page = app.get_change_password_html().await;
+        assert!(
+            html_page.contains(expected_message),
+            "Missing flash message: {}",
+            expected_message
+        );
+    }
+}
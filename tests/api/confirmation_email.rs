@@ -0,0 +1,209 @@
+use wiremock::{matchers::any, Mock, ResponseTemplate};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+async fn get_page_html(app: &TestApp, query: &str) -> String {
+    app.client
+        .get(format!("{}/admin/confirmation-email{query}", app.address))
+        .send()
+        .await
+        .expect("Failed to execute request")
+        .text()
+        .await
+        .unwrap()
+}
+
+async fn post_draft(app: &TestApp, action: &str, draft: serde_json::Value) -> reqwest::Response {
+    let mut body = serde_json::json!({
+        "locale": "en",
+        "subject": "Please confirm {{ list_name }}",
+        "html_content": "<p>Click <a href=\"{{ confirmation_link }}\">this link</a></p>",
+        "text_content": "Open {{ confirmation_link }} to join {{ list_name }}",
+        "action": action,
+    });
+    for (key, value) in draft.as_object().unwrap() {
+        body[key] = value.clone();
+    }
+    app.client
+        .post(format!("{}/admin/confirmation-email", app.address))
+        .form(&app.with_csrf_token(&body).await)
+        .send()
+        .await
+        .expect("Failed to execute request")
+}
+
+/// Subscribe and return the confirmation email.
+async fn subscribe(app: &TestApp, body: &str) -> serde_json::Value {
+    let already_sent = app.email_server.received_requests().await.unwrap().len();
+    let response = app.post_subscriptions(body.into()).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let email_request = &app.email_server.received_requests().await.unwrap()[already_sent];
+    serde_json::from_slice(&email_request.body).unwrap()
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_edit_the_confirmation_email() {
+    let app = spawn_app().await;
+
+    let response = app
+        .client
+        .get(format!("{}/admin/confirmation-email", app.address))
+        .send()
+        .await
+        .unwrap();
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn the_page_starts_from_the_built_in_email() {
+    let app = spawn_app().await;
+    app.log_in().await;
+
+    let html = get_page_html(&app, "?locale=de").await;
+
+    assert!(html.contains("Editing the built-in email."));
+    assert!(html.contains(r#"value="Willkommen bei {{ list_name }}!""#));
+    assert!(html.contains("No versions yet"));
+}
+
+#[tokio::test]
+async fn saved_versions_are_sent_to_new_subscribers() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    app.mount_email_ok().await;
+
+    let response = post_draft(&app, "save", serde_json::json!({})).await;
+    assert_is_redirect_to(&response, "/admin/confirmation-email?locale=en");
+    let html = get_page_html(&app, "").await;
+    assert!(html.contains("Saved version 1 of the en confirmation email."));
+
+    let email = subscribe(&app, "name=le%20guin&email=ursula%40example.com").await;
+    assert_eq!(email["Subject"], "Please confirm Newsletter");
+    assert!(email["TextBody"]
+        .as_str()
+        .unwrap()
+        .ends_with("to join Newsletter"));
+    let email_requests = app.email_server.received_requests().await.unwrap();
+    let confirmation_links = app.get_confirmation_links(email_requests.last().unwrap());
+    let response = reqwest::get(confirmation_links.html).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn versions_only_apply_to_their_locale() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    app.mount_email_ok().await;
+
+    post_draft(&app, "save", serde_json::json!({ "locale": "fr" })).await;
+
+    let email = subscribe(&app, "name=le%20guin&email=ursula%40example.com&locale=en").await;
+    assert_eq!(email["Subject"], "Welcome to Newsletter!");
+    let email = subscribe(&app, "name=le%20guin&email=octavia%40example.com&locale=fr").await;
+    assert_eq!(email["Subject"], "Please confirm Newsletter");
+}
+
+#[tokio::test]
+async fn drafts_without_the_confirmation_link_are_rejected() {
+    let app = spawn_app().await;
+    app.log_in().await;
+
+    for (draft, message) in [
+        (
+            serde_json::json!({ "html_content": "<p>No link</p>" }),
+            "The HTML body must contain {{ confirmation_link }}.",
+        ),
+        (
+            serde_json::json!({ "text_content": "No link" }),
+            "The text body must contain {{ confirmation_link }}.",
+        ),
+        (
+            serde_json::json!({ "subject": " " }),
+            "The confirmation email needs a subject.",
+        ),
+    ] {
+        let response = post_draft(&app, "save", draft).await;
+        assert_eq!(response.status().as_u16(), 200);
+        let html = response.text().await.unwrap();
+        assert!(html.contains(message), "{message} missing");
+    }
+    let versions = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM confirmation_email_templates"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(versions.count, 0);
+}
+
+#[tokio::test]
+async fn every_save_is_a_new_version() {
+    let app = spawn_app().await;
+    app.log_in().await;
+
+    post_draft(&app, "save", serde_json::json!({})).await;
+    post_draft(
+        &app,
+        "save",
+        serde_json::json!({ "subject": "Last step for {{ list_name }}", "editing": 1 }),
+    )
+    .await;
+
+    let html = get_page_html(&app, "").await;
+    assert!(html.contains("Editing from version 2."));
+    assert!(html.contains(r#"value="Last step for {{ list_name }}""#));
+    assert!(html.contains("<td>2 (current)</td>"));
+    assert!(html.contains("<td>1</td>"));
+    assert!(html.contains(&format!("<td>{}</td>", app.test_user.name)));
+
+    let html = get_page_html(&app, "?locale=en&version=1").await;
+    assert!(html.contains("Editing from version 1."));
+    assert!(html.contains(r#"value="Please confirm {{ list_name }}""#));
+
+    let audit = sqlx::query!(
+        "SELECT COUNT(*) AS \"count!\" FROM audit_events WHERE action = 'confirmation_email_changed'"
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(audit.count, 2);
+}
+
+#[tokio::test]
+async fn drafts_can_be_previewed_without_saving_them() {
+    let app = spawn_app().await;
+    app.log_in().await;
+
+    let response = post_draft(&app, "preview", serde_json::json!({})).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let html = response.text().await.unwrap();
+    assert!(html.contains("<p>Subject: Please confirm Newsletter</p>"));
+    assert!(html.contains("subscription_token=test"));
+    assert!(html.contains("No versions yet"));
+}
+
+#[tokio::test]
+async fn drafts_can_be_sent_as_a_test() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = post_draft(
+        &app,
+        "test",
+        serde_json::json!({ "test_recipient": "admin@example.com" }),
+    )
+    .await;
+
+    let html = response.text().await.unwrap();
+    assert!(html.contains("Sent a test to admin@example.com."));
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let email: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(email["To"], "admin@example.com");
+    assert_eq!(email["Subject"], "[Test] Please confirm Newsletter");
+    assert!(html.contains("No versions yet"));
+}
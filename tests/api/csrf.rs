@@ -0,0 +1,94 @@
+use wiremock::matchers::any;
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+
+#[tokio::test]
+async fn admin_forms_embed_the_csrf_token() {
+    let app = spawn_app().await;
+    app.log_in().await;
+
+    let token = app.csrf_token().await;
+    assert_eq!(token.len(), 64);
+    for html_page in [
+        app.get_admin_dashboard_html().await,
+        app.get_change_password_html().await,
+    ] {
+        assert!(html_page.contains(&format!(r#"name="csrf_token" value="{token}""#)));
+    }
+}
+
+#[tokio::test]
+async fn a_newsletter_is_not_sent_without_a_csrf_token() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .client
+        .post(format!("{}/admin/newsletter", app.address))
+        .form(&serde_json::json!({
+            "title": "Newsletter title",
+            "html_content": "<p>Newsletter body</p>",
+            "content": "Newsletter body",
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn a_wrong_csrf_token_is_rejected() {
+    let app = spawn_app().await;
+    app.log_in().await;
+
+    let response = app
+        .client
+        .post(format!("{}/admin/logout", app.address))
+        .form(&serde_json::json!({ "csrf_token": "not-the-token" }))
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(response.status().as_u16(), 403);
+
+    // Still logged in.
+    assert_eq!(app.get_admin_dashboard().await.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn the_csrf_token_can_be_sent_as_a_header() {
+    let app = spawn_app().await;
+    app.log_in().await;
+
+    let response = app
+        .client
+        .post(format!("{}/admin/logout", app.address))
+        .header("X-CSRF-Token", app.csrf_token().await)
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn a_token_from_another_session_is_rejected() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    let other_device = app.log_in_from_another_device("Second device").await;
+
+    let response = other_device
+        .post(format!("{}/admin/logout", app.address))
+        .form(&serde_json::json!({ "csrf_token": app.csrf_token().await }))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(response.status().as_u16(), 403);
+}
@@ -0,0 +1,230 @@
+use wiremock::{matchers::any, Mock, ResponseTemplate};
+
+use crate::helpers::{spawn_app, TestApp, WEBHOOK_SECRET};
+
+async fn subscriber_state(app: &TestApp, email: &str) -> (String, i32) {
+    let row = sqlx::query!(
+        "SELECT status, soft_bounces FROM subscriptions WHERE email = $1",
+        email
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    (row.status, row.soft_bounces)
+}
+
+fn bounce(id: i64, bounce_type: &str, email: &str) -> serde_json::Value {
+    serde_json::json!({
+        "RecordType": "Bounce",
+        "ID": id,
+        "Type": bounce_type,
+        "TypeCode": 1,
+        "Email": email,
+        "BouncedAt": "2026-10-19T12:00:00Z",
+    })
+}
+
+#[tokio::test]
+async fn events_without_valid_credentials_are_rejected() {
+    let app = spawn_app().await;
+    app.insert_confirmed_subscriber("ursula@example.com").await;
+    let url = format!("{}/webhooks/email", app.address);
+    let event = bounce(1, "HardBounce", "ursula@example.com");
+
+    let anonymous = app.client.post(&url).json(&event).send().await.unwrap();
+    let wrong_password = app
+        .client
+        .post(&url)
+        .basic_auth("postmark", Some("wrong"))
+        .json(&event)
+        .send()
+        .await
+        .unwrap();
+    let wrong_secret = app
+        .client
+        .post(&url)
+        .header("X-Webhook-Secret", "wrong")
+        .json(&event)
+        .send()
+        .await
+        .unwrap();
+
+    for response in [anonymous, wrong_password, wrong_secret] {
+        assert_eq!(response.status().as_u16(), 401);
+        assert_eq!(
+            response.headers()["WWW-Authenticate"],
+            r#"Basic realm="webhooks""#
+        );
+    }
+    assert_eq!(
+        subscriber_state(&app, "ursula@example.com").await.0,
+        "confirmed"
+    );
+}
+
+#[tokio::test]
+async fn a_shared_secret_is_accepted_instead_of_basic_auth() {
+    let app = spawn_app().await;
+    app.insert_confirmed_subscriber("ursula@example.com").await;
+
+    let response = app
+        .client
+        .post(format!("{}/webhooks/email", app.address))
+        .header("X-Webhook-Secret", WEBHOOK_SECRET)
+        .json(&bounce(1, "HardBounce", "ursula@example.com"))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        subscriber_state(&app, "ursula@example.com").await.0,
+        "bounced"
+    );
+}
+
+#[tokio::test]
+async fn hard_bounces_and_complaints_stop_future_sends() {
+    let app = spawn_app().await;
+    app.insert_confirmed_subscriber("bounced@example.com").await;
+    app.insert_confirmed_subscriber("Complained@example.com")
+        .await;
+    app.insert_confirmed_subscriber("reader@example.com").await;
+
+    let response = app
+        .post_email_event(&bounce(1, "HardBounce", "bounced@example.com"))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let response = app
+        .post_email_event(&serde_json::json!({
+            "RecordType": "SpamComplaint",
+            "ID": 2,
+            "Email": "complained@example.com",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    assert_eq!(
+        subscriber_state(&app, "bounced@example.com").await.0,
+        "bounced"
+    );
+    assert_eq!(
+        subscriber_state(&app, "Complained@example.com").await.0,
+        "complained"
+    );
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.post_login_form(&serde_json::json!({
+        "username": app.test_user.name,
+        "password": app.test_user.password,
+    }))
+    .await;
+    let response = app
+        .post_newsletters(serde_json::json!({
+            "title": "Newsletter title",
+            "html_content": "Newsletter content",
+            "content": "Newsletter content",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 303);
+}
+
+#[tokio::test]
+async fn soft_bounces_escalate_after_the_threshold() {
+    let app = spawn_app().await;
+    app.insert_confirmed_subscriber("ursula@example.com").await;
+
+    app.post_email_event(&bounce(1, "SoftBounce", "ursula@example.com"))
+        .await;
+    app.post_email_event(&bounce(2, "Transient", "ursula@example.com"))
+        .await;
+    assert_eq!(
+        subscriber_state(&app, "ursula@example.com").await,
+        ("confirmed".to_string(), 2)
+    );
+
+    // A delivery in between starts the count over.
+    app.post_email_event(&serde_json::json!({
+        "RecordType": "Delivery",
+        "MessageID": "883953f4-6105-42a2-a16a-77a8eac79483",
+        "Recipient": "ursula@example.com",
+    }))
+    .await;
+    assert_eq!(
+        subscriber_state(&app, "ursula@example.com").await,
+        ("confirmed".to_string(), 0)
+    );
+
+    for id in 3..6 {
+        let response = app
+            .post_email_event(&bounce(id, "SoftBounce", "ursula@example.com"))
+            .await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+    assert_eq!(
+        subscriber_state(&app, "ursula@example.com").await,
+        ("bounced".to_string(), 3)
+    );
+}
+
+#[tokio::test]
+async fn bounces_do_not_undo_an_unsubscribe() {
+    let app = spawn_app().await;
+    app.insert_confirmed_subscriber("ursula@example.com").await;
+    sqlx::query!("UPDATE subscriptions SET status = 'unsubscribed'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    app.post_email_event(&bounce(1, "HardBounce", "ursula@example.com"))
+        .await;
+    for id in 2..5 {
+        app.post_email_event(&bounce(id, "SoftBounce", "ursula@example.com"))
+            .await;
+    }
+
+    assert_eq!(
+        subscriber_state(&app, "ursula@example.com").await.0,
+        "unsubscribed"
+    );
+}
+
+#[tokio::test]
+async fn redelivered_events_are_only_counted_once() {
+    let app = spawn_app().await;
+    app.insert_confirmed_subscriber("ursula@example.com").await;
+
+    for _ in 0..3 {
+        let response = app
+            .post_email_event(&bounce(1, "SoftBounce", "ursula@example.com"))
+            .await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    assert_eq!(
+        subscriber_state(&app, "ursula@example.com").await,
+        ("confirmed".to_string(), 1)
+    );
+}
+
+#[tokio::test]
+async fn malformed_events_are_rejected_and_unknown_ones_ignored() {
+    let app = spawn_app().await;
+
+    let malformed = app
+        .post_email_event(&serde_json::json!({ "RecordType": "Bounce" }))
+        .await;
+    let unknown = app
+        .post_email_event(&serde_json::json!({
+            "RecordType": "Open",
+            "Recipient": "ursula@example.com",
+        }))
+        .await;
+
+    assert_eq!(malformed.status().as_u16(), 400);
+    assert_eq!(unknown.status().as_u16(), 200);
+}
@@ -0,0 +1,125 @@
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::spawn_app;
+
+#[tokio::test]
+async fn the_callers_request_id_is_echoed_back() {
+    let app = spawn_app().await;
+
+    let response = app
+        .client
+        .get(format!("{}/health_check", app.address))
+        .header("X-Request-Id", "checkout-42")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.headers()["x-request-id"], "checkout-42");
+}
+
+#[tokio::test]
+async fn a_request_id_is_generated_when_missing_or_invalid() {
+    let app = spawn_app().await;
+
+    for header in [None, Some("not a valid id")] {
+        let mut request = app.client.get(format!("{}/health_check", app.address));
+        if let Some(header) = header {
+            request = request.header("X-Request-Id", header);
+        }
+        let response = request.send().await.unwrap();
+
+        let request_id = response.headers()["x-request-id"].to_str().unwrap();
+        assert!(uuid::Uuid::parse_str(request_id).is_ok());
+    }
+}
+
+#[tokio::test]
+async fn api_clients_get_a_json_error() {
+    let app = spawn_app().await;
+
+    let response = app
+        .client
+        .get(format!(
+            "{}/subscriptions/confirm?subscription_token=unknown",
+            app.address
+        ))
+        .header("X-Request-Id", "confirm-1")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(response.headers()["content-type"], "application/json");
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["error"], "unauthorized");
+    assert_eq!(
+        body["message"],
+        "This confirmation link is invalid or has expired."
+    );
+    assert_eq!(body["request_id"], "confirm-1");
+}
+
+#[tokio::test]
+async fn browsers_get_an_error_page() {
+    let app = spawn_app().await;
+
+    let response = app
+        .client
+        .get(format!(
+            "{}/subscriptions/confirm?subscription_token=unknown",
+            app.address
+        ))
+        .header("Accept", "text/html,application/xhtml+xml,*/*;q=0.8")
+        .header("X-Request-Id", "confirm-2")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 401);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("This confirmation link is invalid or has expired."));
+    assert!(html_page.contains("confirm-2"));
+}
+
+#[tokio::test]
+async fn server_errors_do_not_leak_their_cause() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+
+    assert_eq!(response.status().as_u16(), 500);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["error"], "internal_server_error");
+    let message = body["message"].as_str().unwrap();
+    assert!(!message.contains("confirmation email"), "{message}");
+}
+
+#[tokio::test]
+async fn error_responses_without_a_body_get_one() {
+    let app = spawn_app().await;
+
+    let response = app
+        .client
+        .get(format!("{}/no-such-page", app.address))
+        .header("X-Request-Id", "missing-1")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 404);
+    assert_eq!(response.headers()["content-type"], "application/json");
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["error"], "not_found");
+    assert_eq!(body["message"], "Not Found");
+    assert_eq!(body["request_id"], "missing-1");
+}
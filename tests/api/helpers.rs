@@ -1,26 +1,62 @@
-use argon2::{password_hash::SaltString, Algorithm, Argon2, Params, PasswordHasher, Version};
+use argon2::{password_hash::SaltString, Argon2, PasswordHasher};
 use once_cell::sync::Lazy;
+use opentelemetry_sdk::trace::TracerProvider;
 use reqwest::{Response, Url};
+use secrecy::Secret;
 use sqlx::{Connection, Executor, PgConnection, PgPool};
+use std::{net::Ipv4Addr, sync::Arc};
 use uuid::Uuid;
-use wiremock::MockServer;
+use wiremock::{
+    matchers::{any, method, path},
+    Mock, MockServer, ResponseTemplate,
+};
 use zero2prod::{
-    configuration::{get_configuration, DatabaseSettings},
+    configuration::{get_configuration, DatabaseSettings, Settings, WebhookCredentials},
+    preferences::PreferenceLinks,
+    sequences::SequenceScheduler,
     startup::{get_connection_pool, Application},
     telemetry::{get_subscriber, init_subscriber},
 };
 
-static TRACING: Lazy<()> = Lazy::new(|| {
+/// Bearer token the test applications expect on `/metrics`.
+pub const METRICS_TOKEN: &str = "test-metrics-token";
+
+/// Credentials the test applications expect on `/webhooks/email`.
+pub const WEBHOOK_USERNAME: &str = "postmark";
+pub const WEBHOOK_PASSWORD: &str = "test-webhook-password";
+pub const WEBHOOK_SECRET: &str = "test-webhook-secret";
+
+/// A random address in `10.0.0.0/8`, sent in `X-Forwarded-For`.
+pub fn random_ip() -> String {
+    let bytes = Uuid::new_v4().into_bytes();
+    format!("10.{}.{}.{}", bytes[0], bytes[1], bytes[2])
+}
+
+/// Nothing is exported, but spans still carry a trace context. The provider
+/// is kept alive for as long as the tests run.
+static TRACING: Lazy<TracerProvider> = Lazy::new(|| {
     let default_filter_level = "info".to_string();
     let subscriber_name = "test".to_string();
+    let tracer_provider = TracerProvider::default();
 
     if std::env::var("TEST_LOG").is_ok() {
-        let subscriber = get_subscriber(subscriber_name, default_filter_level, std::io::stdout);
+        let subscriber = get_subscriber(
+            subscriber_name,
+            default_filter_level,
+            std::io::stdout,
+            &tracer_provider,
+        );
         init_subscriber(subscriber);
     } else {
-        let subscriber = get_subscriber(subscriber_name, default_filter_level, std::io::sink);
+        let subscriber = get_subscriber(
+            subscriber_name,
+            default_filter_level,
+            std::io::sink,
+            &tracer_provider,
+        );
         init_subscriber(subscriber);
     }
+    tracer_provider
 });
 
 pub struct TestUser {
@@ -68,6 +104,8 @@ pub struct TestApp {
     pub port: u16,
     pub test_user: TestUser,
     pub client: reqwest::Client,
+    /// Tests run the rounds of the sequence scheduler themselves.
+    pub sequence_scheduler: SequenceScheduler,
 }
 
 pub struct ConfirmationLinks {
@@ -76,11 +114,133 @@ pub struct ConfirmationLinks {
 }
 
 impl TestApp {
+    /// The signed render timestamp embedded in the subscription form.
+    pub async fn get_subscription_form_token(&self) -> String {
+        let html_page = self
+            .client
+            .get(format!("{}/", self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .unwrap();
+        let (_, rest) = html_page
+            .split_once(r#"name="form_token" value=""#)
+            .expect("No form token in the subscription form");
+        rest.split('"').next().unwrap().to_string()
+    }
+
+    /// Submit the subscription form, as a person would.
     pub async fn post_subscriptions(&self, body: String) -> Response {
+        let form_token = self.get_subscription_form_token().await;
         self.client
-            .post(&format!("{}/subscriptions", self.address))
+            .post(format!("{}/subscriptions", self.address))
             .header("Content-Type", "application/x-www-form-urlencoded")
-            .body(body)
+            .body(format!("{body}&form_token={form_token}"))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    /// Subscribe through the form and return the links of the confirmation
+    /// email.
+    pub async fn create_unconfirmed_subscriber(
+        &self,
+        name: &str,
+        email: &str,
+    ) -> ConfirmationLinks {
+        let _mock_guard = Mock::given(path("/email"))
+            .and(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount_as_scoped(&self.email_server)
+            .await;
+        let body = serde_urlencoded::to_string([("name", name), ("email", email)]).unwrap();
+        self.post_subscriptions(body)
+            .await
+            .error_for_status()
+            .unwrap();
+
+        let email_requests = self.email_server.received_requests().await.unwrap();
+        self.get_confirmation_links(email_requests.last().unwrap())
+    }
+
+    /// Subscribe through the form and follow the confirmation link.
+    pub async fn create_confirmed_subscriber(&self, name: &str, email: &str) {
+        let confirmation_links = self.create_unconfirmed_subscriber(name, email).await;
+        reqwest::get(confirmation_links.html)
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+    }
+
+    /// A subscriber who confirmed their subscription to the list every
+    /// database starts with, skipping the email flow.
+    pub async fn insert_confirmed_subscriber(&self, email: &str) -> Uuid {
+        let list_id = self.list_id("newsletter").await;
+        self.insert_confirmed_subscriber_to(list_id, email).await
+    }
+
+    pub async fn insert_confirmed_subscriber_to(&self, list_id: Uuid, email: &str) -> Uuid {
+        let subscriber_id = Uuid::new_v4();
+        sqlx::query!(
+            r#"
+            INSERT INTO subscriptions (id, email, name, subscribed_at, status, list_id)
+            VALUES ($1, $2, 'le guin', now(), 'confirmed', $3)
+            "#,
+            subscriber_id,
+            email,
+            list_id,
+        )
+        .execute(&self.db_pool)
+        .await
+        .expect("Failed to insert a confirmed subscriber");
+        subscriber_id
+    }
+
+    /// Another list, next to the one every database starts with.
+    pub async fn create_list(&self, slug: &str, name: &str) -> Uuid {
+        let list_id = Uuid::new_v4();
+        sqlx::query!(
+            "INSERT INTO lists (list_id, slug, name, created_at) VALUES ($1, $2, $3, now())",
+            list_id,
+            slug,
+            name,
+        )
+        .execute(&self.db_pool)
+        .await
+        .expect("Failed to insert a list");
+        list_id
+    }
+
+    pub async fn list_id(&self, slug: &str) -> Uuid {
+        sqlx::query!("SELECT list_id FROM lists WHERE slug = $1", slug)
+            .fetch_one(&self.db_pool)
+            .await
+            .expect("No such list")
+            .list_id
+    }
+
+    /// Post an event as the email provider does, with basic auth.
+    pub async fn post_email_event(&self, event: &serde_json::Value) -> Response {
+        self.client
+            .post(format!("{}/webhooks/email", self.address))
+            .basic_auth(WEBHOOK_USERNAME, Some(WEBHOOK_PASSWORD))
+            .json(event)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_subscriptions_from(&self, ip: &str, body: String) -> Response {
+        let form_token = self.get_subscription_form_token().await;
+        self.client
+            .post(format!("{}/subscriptions", self.address))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .header("X-Forwarded-For", ip)
+            .body(format!("{body}&form_token={form_token}"))
             .send()
             .await
             .expect("Failed to execute request")
@@ -103,8 +263,8 @@ impl TestApp {
             confirmation_link
         };
 
-        let html_body = get_link(&body["HtmlBody"].as_str().unwrap());
-        let text_body = get_link(&body["TextBody"].as_str().unwrap());
+        let html_body = get_link(body["HtmlBody"].as_str().unwrap());
+        let text_body = get_link(body["TextBody"].as_str().unwrap());
 
         ConfirmationLinks {
             html: html_body,
@@ -114,8 +274,8 @@ impl TestApp {
 
     pub async fn post_newsletters(&self, body: serde_json::Value) -> Response {
         self.client
-            .post(&format!("{}/admin/newsletter", self.address))
-            .form(&body)
+            .post(format!("{}/admin/newsletter", self.address))
+            .form(&self.with_csrf_token(&body).await)
             .send()
             .await
             .expect("Failed to execute request")
@@ -126,16 +286,68 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.client
-            .post(&format!("{}/login", self.address))
+            .post(format!("{}/login", self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    /// Log in as the test user.
+    pub async fn log_in(&self) {
+        let response = self
+            .post_login_form(&serde_json::json!({
+                "username": &self.test_user.name,
+                "password": &self.test_user.password,
+            }))
+            .await;
+        assert_is_redirect_to(&response, "/admin/dashboard");
+    }
+
+    /// Accept every email the application sends.
+    pub async fn mount_email_ok(&self) {
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&self.email_server)
+            .await;
+    }
+
+    pub async fn post_login_form_from<Body>(&self, ip: &str, body: &Body) -> Response
+    where
+        Body: serde::Serialize,
+    {
+        self.client
+            .post(format!("{}/login", self.address))
+            .header("X-Forwarded-For", ip)
             .form(body)
             .send()
             .await
             .expect("Failed to execute request")
     }
 
+    pub async fn get_lockouts_html(&self) -> String {
+        self.client
+            .get(format!("{}/admin/lockouts", self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_clear_lockout(&self, body: &serde_json::Value) -> Response {
+        self.client
+            .post(format!("{}/admin/lockouts/clear", self.address))
+            .form(&self.with_csrf_token(body).await)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
     pub async fn get_login_html(&self) -> String {
         self.client
-            .get(&format!("{}/login", self.address))
+            .get(format!("{}/login", self.address))
             .send()
             .await
             .expect("Failed to execute request")
@@ -146,7 +358,7 @@ impl TestApp {
 
     pub async fn get_admin_dashboard(&self) -> Response {
         self.client
-            .get(&format!("{}/admin/dashboard", self.address))
+            .get(format!("{}/admin/dashboard", self.address))
             .send()
             .await
             .expect("Failed to execute request")
@@ -156,9 +368,20 @@ impl TestApp {
         self.get_admin_dashboard().await.text().await.unwrap()
     }
 
+    pub async fn get_admin_dashboard_stats_html(&self, query: &str) -> String {
+        self.client
+            .get(format!("{}/admin/dashboard?{}", self.address, query))
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .unwrap()
+    }
+
     pub async fn get_change_password(&self) -> Response {
         self.client
-            .get(&format!("{}/admin/password", self.address))
+            .get(format!("{}/admin/password", self.address))
             .send()
             .await
             .expect("Failed to execute request")
@@ -170,22 +393,200 @@ impl TestApp {
 
     pub async fn post_change_password(&self, body: &serde_json::Value) -> Response {
         self.client
-            .post(&format!("{}/admin/password", self.address))
+            .post(format!("{}/admin/password", self.address))
+            .form(&self.with_csrf_token(body).await)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_totp_enrollment(&self) -> Response {
+        self.client
+            .get(format!("{}/admin/totp", self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_totp_enrollment_html(&self) -> String {
+        self.get_totp_enrollment().await.text().await.unwrap()
+    }
+
+    pub async fn post_totp_enrollment(&self, body: &serde_json::Value) -> Response {
+        self.client
+            .post(format!("{}/admin/totp", self.address))
+            .form(&self.with_csrf_token(body).await)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_login_totp_html(&self) -> String {
+        self.client
+            .get(format!("{}/login/totp", self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_login_totp(&self, body: &serde_json::Value) -> Response {
+        self.client
+            .post(format!("{}/login/totp", self.address))
             .form(body)
             .send()
             .await
             .expect("Failed to execute request")
     }
 
+    /// The CSRF token of the current session, as embedded in the admin forms.
+    pub async fn csrf_token(&self) -> String {
+        let html_page = self
+            .client
+            .get(format!("{}/admin/newsletter", self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .unwrap();
+        let marker = r#"name="csrf_token" value=""#;
+        match html_page.find(marker) {
+            Some(start) => html_page[start + marker.len()..]
+                .split('"')
+                .next()
+                .unwrap()
+                .to_string(),
+            // Not logged in: there is no token to submit.
+            None => String::new(),
+        }
+    }
+
+    pub async fn with_csrf_token(&self, body: &serde_json::Value) -> serde_json::Value {
+        let mut body = body.clone();
+        body["csrf_token"] = self.csrf_token().await.into();
+        body
+    }
+
+    pub async fn get_sessions_html(&self) -> String {
+        self.client
+            .get(format!("{}/admin/sessions", self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_revoke_session(&self, body: &serde_json::Value) -> Response {
+        self.client
+            .post(format!("{}/admin/sessions/revoke", self.address))
+            .form(&self.with_csrf_token(body).await)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_revoke_all_sessions(&self) -> Response {
+        self.client
+            .post(format!("{}/admin/sessions/revoke_all", self.address))
+            .form(&self.with_csrf_token(&serde_json::json!({})).await)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    /// Log the test user in from a second client, standing in for another device.
+    pub async fn log_in_from_another_device(&self, user_agent: &str) -> reqwest::Client {
+        let client = client_builder()
+            .user_agent(user_agent)
+            .build()
+            .expect("Failed to create reqwest client");
+        let response = client
+            .post(format!("{}/login", self.address))
+            .form(&serde_json::json!({
+                "username": &self.test_user.name,
+                "password": &self.test_user.password,
+            }))
+            .send()
+            .await
+            .expect("Failed to execute request");
+        assert_is_redirect_to(&response, "/admin/dashboard");
+        client
+    }
+
+    pub async fn get_admin_dashboard_with(&self, client: &reqwest::Client) -> Response {
+        client
+            .get(format!("{}/admin/dashboard", self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_audit_log_html(&self, query: &str) -> String {
+        self.client
+            .get(format!("{}/admin/audit?{}", self.address, query))
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_audit_log_export(&self, query: &str) -> Response {
+        self.client
+            .get(format!("{}/admin/audit/export?{}", self.address, query))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_ready(&self) -> Response {
+        reqwest::Client::new()
+            .get(format!("{}/ready", self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_metrics(&self, bearer_token: Option<&str>) -> Response {
+        let mut request = reqwest::Client::new().get(format!("{}/metrics", self.address));
+        if let Some(token) = bearer_token {
+            request = request.bearer_auth(token);
+        }
+        request.send().await.expect("Failed to execute request")
+    }
+
     pub async fn post_logout(&self) -> Response {
         self.client
-            .post(&format!("{}/admin/logout", self.address))
+            .post(format!("{}/admin/logout", self.address))
+            .form(&self.with_csrf_token(&serde_json::json!({})).await)
             .send()
             .await
             .expect("Failed to execute request")
     }
 }
 pub async fn spawn_app() -> TestApp {
+    spawn_app_with(|_| {}).await
+}
+
+/// Spawn a test application after adjusting its configuration.
+pub async fn spawn_app_with(configure: impl FnOnce(&mut Settings)) -> TestApp {
+    let (test_app, application) = build_app_with(configure).await;
+    tokio::spawn(application.server);
+    test_app
+}
+
+/// Set up a test application without starting it.
+pub async fn build_app() -> (TestApp, Application) {
+    build_app_with(|_| {}).await
+}
+
+async fn build_app_with(configure: impl FnOnce(&mut Settings)) -> (TestApp, Application) {
     Lazy::force(&TRACING);
 
     let email_server = MockServer::start().await;
@@ -195,6 +596,29 @@ pub async fn spawn_app() -> TestApp {
         c.database.database_name = Uuid::new_v4().to_string();
         c.application_settings.port = 0;
         c.email_configuration.base_url = email_server.uri();
+        // Keep the progressive login delay from slowing the test suite down
+        c.login_throttle.base_delay_milliseconds = 1;
+        c.login_throttle.max_delay_milliseconds = 10;
+        c.metrics.enabled = true;
+        c.metrics.bearer_token = Some(Secret::new(METRICS_TOKEN.to_string()));
+        c.email_webhooks.basic_auth = Some(WebhookCredentials {
+            username: WEBHOOK_USERNAME.to_string(),
+            password: Secret::new(WEBHOOK_PASSWORD.to_string()),
+        });
+        c.email_webhooks.shared_secret = Some(Secret::new(WEBHOOK_SECRET.to_string()));
+        c.readiness.check_email_provider = true;
+        c.shutdown.timeout_seconds = 2;
+        // Test clients fill in the subscription form instantly
+        c.bot_protection.min_submit_seconds = 0;
+        // Tests share the Redis instance and subscribe from the same address
+        c.subscribe_rate_limit.per_ip.capacity = 1000;
+        c.subscribe_rate_limit.per_ip.refill_interval_seconds = 1;
+        c.subscribe_rate_limit.per_email.capacity = 1000;
+        c.subscribe_rate_limit.per_email.refill_interval_seconds = 1;
+        c.sequences.enabled = false;
+        // Test clients connect locally and pass their address in `X-Forwarded-For`
+        c.application_settings.trusted_proxies = vec![Ipv4Addr::LOCALHOST.into()];
+        configure(&mut c);
         c
     };
 
@@ -207,12 +631,17 @@ pub async fn spawn_app() -> TestApp {
 
     let application_port = application.port();
     println!("{}", application_port);
-    let _ = tokio::spawn(application.server);
-    let client = reqwest::Client::builder()
-        .redirect(reqwest::redirect::Policy::none())
-        .cookie_store(true)
+    let client = client_builder()
         .build()
         .expect("Failed to create reqwest client");
+    let sequence_scheduler = SequenceScheduler::new(
+        get_connection_pool(&configuration.database),
+        Arc::new(configuration.email_configuration.client().unwrap()),
+        Arc::new(PreferenceLinks::new(
+            configuration.application_settings.hmac_secret.clone(),
+            configuration.application_settings.base_url.clone(),
+        )),
+    );
     let test_app = TestApp {
         address: format!("http://127.0.0.1:{}", application.port),
         db_pool: get_connection_pool(&configuration.database),
@@ -220,9 +649,22 @@ pub async fn spawn_app() -> TestApp {
         port: application_port,
         test_user: TestUser::generate(),
         client,
+        sequence_scheduler,
     };
     test_app.test_user.store(&test_app.db_pool).await;
-    test_app
+    (test_app, application)
+}
+
+/// A client keeping cookies and not following redirects. Each client gets
+/// its own address, so that failed logins in one test do not lock out the
+/// others.
+fn client_builder() -> reqwest::ClientBuilder {
+    let mut headers = reqwest::header::HeaderMap::new();
+    headers.insert("X-Forwarded-For", random_ip().parse().unwrap());
+    reqwest::Client::builder()
+        .default_headers(headers)
+        .redirect(reqwest::redirect::Policy::none())
+        .cookie_store(true)
 }
 
 async fn configure_database(config: &DatabaseSettings) -> PgPool {
@@ -0,0 +1,201 @@
+use wiremock::{
+    matchers::{any, method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+async fn get_html(app: &TestApp, path: &str) -> String {
+    app.client
+        .get(format!("{}{path}", app.address))
+        .send()
+        .await
+        .expect("Failed to execute request")
+        .text()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn subscriptions_to_different_lists_are_confirmed_independently() {
+    let app = spawn_app().await;
+    app.create_list("weekly", "Weekly digest").await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    for list in ["newsletter", "weekly"] {
+        let response = app
+            .post_subscriptions(format!(
+                "name=le%20guin&email=ursula%40example.com&list={list}"
+            ))
+            .await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    let email_requests = app.email_server.received_requests().await.unwrap();
+    let email: serde_json::Value = serde_json::from_slice(&email_requests[1].body).unwrap();
+    assert_eq!(email["Subject"], "Welcome to Weekly digest!");
+    let confirmation_links = app.get_confirmation_links(&email_requests[1]);
+    let response = reqwest::get(confirmation_links.html).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.text().await.unwrap(),
+        "Your subscription to Weekly digest is confirmed."
+    );
+
+    let statuses = sqlx::query!(
+        r#"
+        SELECT l.slug, s.status
+        FROM subscriptions s JOIN lists l USING (list_id)
+        ORDER BY l.slug
+        "#
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    let statuses: Vec<_> = statuses
+        .iter()
+        .map(|row| (row.slug.as_str(), row.status.as_str()))
+        .collect();
+    assert_eq!(
+        statuses,
+        [
+            ("newsletter", "pending_confirmation"),
+            ("weekly", "confirmed")
+        ]
+    );
+}
+
+#[tokio::test]
+async fn subscriptions_must_name_a_list_once_there_are_several() {
+    let app = spawn_app().await;
+    app.create_list("weekly", "Weekly digest").await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    for (body, message) in [
+        (
+            "name=le%20guin&email=ursula%40example.com",
+            "Please choose a newsletter to subscribe to.",
+        ),
+        (
+            "name=le%20guin&email=ursula%40example.com&list=monthly",
+            "There is no newsletter called monthly.",
+        ),
+    ] {
+        let response = app.post_subscriptions(body.into()).await;
+        assert_eq!(response.status().as_u16(), 400);
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["message"], message);
+    }
+}
+
+#[tokio::test]
+async fn the_subscription_form_offers_every_list() {
+    let app = spawn_app().await;
+
+    let html = get_html(&app, "/").await;
+    assert!(html.contains(r#"<input type="hidden" name="list" value="newsletter" />"#));
+
+    app.create_list("weekly", "Weekly digest").await;
+    let html = get_html(&app, "/?list=weekly").await;
+    assert!(html.contains(r#"<option value="newsletter">Newsletter</option>"#));
+    assert!(html.contains(r#"<option value="weekly" selected>Weekly digest</option>"#));
+}
+
+#[tokio::test]
+async fn issues_only_reach_the_subscribers_of_their_list() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    let weekly = app.create_list("weekly", "Weekly digest").await;
+    app.insert_confirmed_subscriber("ursula@example.com").await;
+    app.insert_confirmed_subscriber_to(weekly, "octavia@example.com")
+        .await;
+
+    let html = get_html(&app, "/admin/newsletter").await;
+    assert!(html.contains("<tr><td>All confirmed subscribers</td><td>1</td><td>1</td></tr>"));
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let response = app
+        .post_newsletters(serde_json::json!({
+            "title": "Newsletter title",
+            "html_content": "Newsletter content",
+            "content": "Newsletter content",
+            "list_id": weekly.to_string(),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 303);
+
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let email: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(email["To"], "octavia@example.com");
+    let issue = sqlx::query!("SELECT list_id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(issue.list_id, weekly);
+}
+
+#[tokio::test]
+async fn issues_must_name_a_list_once_there_are_several() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    app.create_list("weekly", "Weekly digest").await;
+    app.insert_confirmed_subscriber("ursula@example.com").await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_newsletters(serde_json::json!({
+            "title": "Newsletter title",
+            "html_content": "Newsletter content",
+            "content": "Newsletter content",
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/admin/newsletter");
+    let html = get_html(&app, "/admin/newsletter").await;
+    assert!(html.contains("Choose one of the lists to send this issue to."));
+}
+
+#[tokio::test]
+async fn lists_can_be_created_from_the_admin_ui() {
+    let app = spawn_app().await;
+    app.log_in().await;
+
+    for _ in 0..2 {
+        let response = app
+            .client
+            .post(format!("{}/admin/lists", app.address))
+            .form(
+                &app.with_csrf_token(&serde_json::json!({
+                    "name": "Weekly digest",
+                    "slug": "Weekly",
+                }))
+                .await,
+            )
+            .send()
+            .await
+            .unwrap();
+        assert_is_redirect_to(&response, "/admin/lists");
+    }
+
+    let html = get_html(&app, "/admin/lists").await;
+    assert!(html.contains("There is a list called weekly already."));
+    assert!(html.contains("<td>Weekly digest</td>"));
+    assert_eq!(html.matches("<td>weekly</td>").count(), 1);
+}
@@ -0,0 +1,140 @@
+use wiremock::{matchers::any, Mock, ResponseTemplate};
+use zero2prod::domain::Locale;
+
+use crate::helpers::{spawn_app, spawn_app_with, TestApp};
+
+async fn get_home_html(app: &TestApp, query: &str, accept_language: &str) -> String {
+    app.client
+        .get(format!("{}/{query}", app.address))
+        .header("Accept-Language", accept_language)
+        .send()
+        .await
+        .expect("Failed to execute request")
+        .text()
+        .await
+        .unwrap()
+}
+
+/// Subscribe `ursula@example.com` from a browser asking for
+/// `accept_language`, and return the confirmation email.
+async fn subscribe(app: &TestApp, accept_language: &str, extra: &str) -> serde_json::Value {
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let form_token = app.get_subscription_form_token().await;
+    let response = app
+        .client
+        .post(format!("{}/subscriptions", app.address))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .header("Accept-Language", accept_language)
+        .body(format!(
+            "name=le%20guin&email=ursula%40example.com&form_token={form_token}{extra}"
+        ))
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(response.status().as_u16(), 200);
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    serde_json::from_slice(&email_request.body).unwrap()
+}
+
+async fn stored_locale(app: &TestApp) -> String {
+    sqlx::query!("SELECT locale FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .locale
+}
+
+#[tokio::test]
+async fn the_home_page_speaks_the_language_of_the_browser() {
+    let app = spawn_app().await;
+
+    let html = get_home_html(&app, "", "de-CH, fr;q=0.8").await;
+    assert!(html.contains(r#"<html lang="de">"#));
+    assert!(html.contains("Willkommen bei meinem Newsletter"));
+    assert!(html.contains(r#"<input type="hidden" name="locale" value="de" />"#));
+
+    let html = get_home_html(&app, "?locale=fr", "de-CH").await;
+    assert!(html.contains(r#"<html lang="fr">"#));
+    assert!(html.contains(r#"<input type="hidden" name="locale" value="fr" />"#));
+}
+
+#[tokio::test]
+async fn unsupported_languages_fall_back_to_the_default_locale() {
+    let app = spawn_app().await;
+    let html = get_home_html(&app, "?locale=xx", "es, it;q=0.5").await;
+    assert!(html.contains("Welcome to my newsletter"));
+
+    let app = spawn_app_with(|c| c.localization.default_locale = Locale::German).await;
+    let html = get_home_html(&app, "", "es").await;
+    assert!(html.contains("Willkommen bei meinem Newsletter"));
+}
+
+#[tokio::test]
+async fn the_locale_of_the_form_is_stored_and_used_for_the_confirmation_email() {
+    let app = spawn_app().await;
+
+    let email = subscribe(&app, "de", "&locale=fr").await;
+
+    assert_eq!(stored_locale(&app).await, "fr");
+    assert_eq!(email["Subject"], "Bienvenue dans Newsletter !");
+    assert!(email["TextBody"]
+        .as_str()
+        .unwrap()
+        .contains("pour confirmer votre abonnement"));
+    assert!(email["HtmlBody"]
+        .as_str()
+        .unwrap()
+        .contains("pour confirmer votre abonnement"));
+}
+
+#[tokio::test]
+async fn without_a_form_locale_the_browser_language_is_stored() {
+    let app = spawn_app().await;
+
+    let email = subscribe(&app, "de-AT, en;q=0.5", "").await;
+
+    assert_eq!(stored_locale(&app).await, "de");
+    assert_eq!(email["Subject"], "Willkommen bei Newsletter!");
+}
+
+#[tokio::test]
+async fn the_confirmation_page_is_in_the_language_of_the_subscriber() {
+    let app = spawn_app().await;
+    subscribe(&app, "fr", "").await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+
+    let response = reqwest::get(confirmation_links.html).await.unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.text().await.unwrap(),
+        "Votre abonnement à Newsletter est confirmé."
+    );
+}
+
+#[tokio::test]
+async fn an_invalid_confirmation_link_is_reported_in_the_language_of_the_browser() {
+    let app = spawn_app().await;
+
+    let response = app
+        .client
+        .get(format!(
+            "{}/subscriptions/confirm?subscription_token=unknown",
+            app.address
+        ))
+        .header("Accept-Language", "fr")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 401);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(
+        body["message"],
+        "Ce lien de confirmation est invalide ou a expiré."
+    );
+}
@@ -1,3 +1,7 @@
+use std::time::Duration;
+
+use argon2::{password_hash::SaltString, Algorithm, Argon2, Params, PasswordHasher, Version};
+
 use crate::helpers::{assert_is_redirect_to, spawn_app};
 
 #[tokio::test]
@@ -34,3 +38,55 @@ async fn redirect_to_admin_dashaboard_on_success() {
     let html_page = app.get_admin_dashboard_html().await;
     assert!(html_page.contains(&format!("Welcome, {}", &app.test_user.name)));
 }
+
+#[tokio::test]
+async fn a_hash_with_outdated_parameters_is_upgraded_on_login() {
+    let app = spawn_app().await;
+    let salt = SaltString::generate(&mut rand::thread_rng());
+    let weak_hash = Argon2::new(
+        Algorithm::Argon2id,
+        Version::V0x13,
+        Params::new(8192, 1, 1, None).unwrap(),
+    )
+    .hash_password(app.test_user.password.as_bytes(), &salt)
+    .unwrap()
+    .to_string();
+    sqlx::query!(
+        "UPDATE users SET password_hash = $1 WHERE user_id = $2",
+        weak_hash,
+        app.test_user.user_id,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let login_body = serde_json::json!({
+        "username": &app.test_user.name,
+        "password": &app.test_user.password,
+    });
+    let response = app.post_login_form(&login_body).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    // The hash is upgraded in the background, give it a moment.
+    let mut password_hash = weak_hash.clone();
+    for _ in 0..50 {
+        password_hash = sqlx::query!(
+            "SELECT password_hash FROM users WHERE user_id = $1",
+            app.test_user.user_id,
+        )
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .password_hash;
+        if password_hash != weak_hash {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(password_hash.contains("m=19456,t=2,p=1"));
+
+    // The upgraded hash still matches the same password.
+    app.post_logout().await;
+    let response = app.post_login_form(&login_body).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
@@ -0,0 +1,172 @@
+use uuid::Uuid;
+
+use crate::helpers::{assert_is_redirect_to, random_ip, spawn_app, spawn_app_with};
+
+#[tokio::test]
+async fn a_username_is_locked_out_after_too_many_failed_attempts() {
+    let app = spawn_app().await;
+
+    for _ in 0..5 {
+        let response = app
+            .post_login_form_from(
+                &random_ip(),
+                &serde_json::json!({
+                    "username": app.test_user.name,
+                    "password": "wrong-password"
+                }),
+            )
+            .await;
+        assert_is_redirect_to(&response, "/login");
+    }
+
+    // Even the right password is refused while the lockout is active...
+    let response = app
+        .post_login_form(&serde_json::json!({
+            "username": app.test_user.name,
+            "password": app.test_user.password
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+
+    // ...and the error does not reveal that the account is locked.
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("Authentication failed"));
+    assert!(!html_page.contains("locked"));
+}
+
+#[tokio::test]
+async fn an_ip_is_locked_out_after_too_many_failed_attempts() {
+    let app = spawn_app().await;
+    let ip = random_ip();
+
+    for _ in 0..20 {
+        app.post_login_form_from(
+            &ip,
+            &serde_json::json!({
+                "username": Uuid::new_v4().to_string(),
+                "password": "wrong-password"
+            }),
+        )
+        .await;
+    }
+
+    let response = app
+        .post_login_form_from(
+            &ip,
+            &serde_json::json!({
+                "username": app.test_user.name,
+                "password": app.test_user.password
+            }),
+        )
+        .await;
+    assert_is_redirect_to(&response, "/login");
+
+    // Other clients are not affected
+    let response = app
+        .post_login_form_from(
+            &random_ip(),
+            &serde_json::json!({
+                "username": app.test_user.name,
+                "password": app.test_user.password
+            }),
+        )
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn an_admin_can_clear_a_lockout() {
+    let app = spawn_app().await;
+    let locked_username = Uuid::new_v4().to_string();
+    for _ in 0..5 {
+        app.post_login_form_from(
+            &random_ip(),
+            &serde_json::json!({
+                "username": locked_username,
+                "password": "wrong-password"
+            }),
+        )
+        .await;
+    }
+
+    app.post_login_form(&serde_json::json!({
+        "username": app.test_user.name,
+        "password": app.test_user.password
+    }))
+    .await;
+    let target = format!("user:{}", locked_username);
+    let html_page = app.get_lockouts_html().await;
+    assert!(html_page.contains(&target));
+
+    let response = app
+        .post_clear_lockout(&serde_json::json!({ "target": target }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/lockouts");
+
+    let html_page = app.get_lockouts_html().await;
+    assert!(html_page.contains(&format!("Lockout cleared for {}", target)));
+    assert!(!html_page.contains(&format!(r#"value="{}""#, target)));
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_lockouts() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_clear_lockout(&serde_json::json!({ "target": "user:admin" }))
+        .await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn locked_out_usernames_are_escaped_on_the_lockouts_page() {
+    let app = spawn_app().await;
+    let malicious_username = "<script>alert('pwned')</script>";
+    for _ in 0..5 {
+        app.post_login_form_from(
+            &random_ip(),
+            &serde_json::json!({
+                "username": malicious_username,
+                "password": "wrong-password"
+            }),
+        )
+        .await;
+    }
+
+    app.post_login_form(&serde_json::json!({
+        "username": app.test_user.name,
+        "password": app.test_user.password
+    }))
+    .await;
+    let html_page = app.get_lockouts_html().await;
+    assert!(!html_page.contains("<script>"));
+    assert!(html_page.contains("&lt;script&gt;"));
+}
+
+#[tokio::test]
+async fn forwarding_headers_are_only_believed_from_trusted_proxies() {
+    for (trusted, expected_ip) in [(true, "10.1.2.3"), (false, "127.0.0.1")] {
+        let app = spawn_app_with(|c| {
+            if !trusted {
+                c.application_settings.trusted_proxies = vec![];
+            }
+        })
+        .await;
+
+        app.post_login_form_from(
+            "10.1.2.3",
+            &serde_json::json!({
+                "username": Uuid::new_v4().to_string(),
+                "password": "wrong-password"
+            }),
+        )
+        .await;
+
+        let event = sqlx::query!("SELECT ip FROM audit_events WHERE action = 'login_failed'")
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+        assert_eq!(event.ip.as_deref(), Some(expected_ip));
+    }
+}
@@ -1,8 +1,29 @@
 mod admin_dashboard;
+mod audit;
+mod bot_protection;
 mod change_password;
+mod confirmation_email;
+mod csrf;
+mod email_webhook;
+mod errors;
 mod health_check;
 mod helpers;
+mod lists;
+mod localization;
 mod login;
+mod login_throttle;
+mod metrics;
 mod newsletter;
+mod preferences;
+mod ready;
+mod segments;
+mod sequences;
+mod sessions;
+mod shutdown;
+mod subscribe_rate_limit;
+mod subscriber_emails;
 mod subscriptions;
 mod subscriptions_confirm;
+mod totp;
+mod trace_propagation;
+mod tracking;
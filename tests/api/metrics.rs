@@ -0,0 +1,71 @@
+use crate::helpers::{spawn_app, spawn_app_with, METRICS_TOKEN};
+
+#[tokio::test]
+async fn metrics_are_not_exposed_unless_enabled() {
+    let app = spawn_app_with(|c| c.metrics.enabled = false).await;
+
+    let response = app.get_metrics(Some(METRICS_TOKEN)).await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn metrics_require_the_configured_bearer_token() {
+    let app = spawn_app().await;
+
+    let response = app.get_metrics(None).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.get_metrics(Some("wrong-token")).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.get_metrics(Some(METRICS_TOKEN)).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn metrics_are_exposed_in_the_prometheus_text_format() {
+    let app = spawn_app().await;
+    reqwest::get(format!("{}/health_check", app.address))
+        .await
+        .unwrap();
+
+    let response = app.get_metrics(Some(METRICS_TOKEN)).await;
+    assert!(response.headers()["Content-Type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/plain; version=0.0.4"));
+    let body = response.text().await.unwrap();
+
+    assert!(body.contains(
+        r#"http_request_duration_seconds_count{method="GET",route="/health_check",status="200"}"#
+    ));
+    assert!(body.contains("# TYPE email_send_attempts_total counter"));
+    assert!(body.contains("subscriptions_total "));
+    assert!(body.contains(r#"db_pool_connections{state="in_use"}"#));
+}
+
+#[tokio::test]
+async fn requests_are_labelled_by_route_pattern() {
+    let app = spawn_app().await;
+    reqwest::get(format!(
+        "{}/subscriptions/confirm?subscription_token=abc",
+        app.address
+    ))
+    .await
+    .unwrap();
+    reqwest::get(format!("{}/no/such/page", app.address))
+        .await
+        .unwrap();
+
+    let body = app
+        .get_metrics(Some(METRICS_TOKEN))
+        .await
+        .text()
+        .await
+        .unwrap();
+
+    assert!(body.contains(r#"route="/subscriptions/confirm",status="#));
+    assert!(body.contains(r#"http_requests_total{method="GET",route="unmatched",status="404"}"#));
+    assert!(!body.contains("/no/such/page"));
+}
@@ -1,12 +1,13 @@
-use crate::helpers::{assert_is_redirect_to, spawn_app, ConfirmationLinks, TestApp};
-use wiremock::matchers::{any, method, path};
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+use wiremock::matchers::any;
 use wiremock::{Mock, ResponseTemplate};
 
 #[tokio::test]
 async fn newsletter_are_not_delivered_to_unconfirmed_subscribers() {
     let app = spawn_app().await;
 
-    create_unconfirmed_subscriber(&app).await;
+    app.create_unconfirmed_subscriber("le guin", "ursula_le_guin@gmail.com")
+        .await;
 
     Mock::given(any())
         .respond_with(ResponseTemplate::new(200))
@@ -36,7 +37,9 @@ async fn newsletter_are_not_delivered_to_unconfirmed_subscribers() {
 async fn newsletters_are_delivered_to_confirmed_subscribers() {
     let app = spawn_app().await;
 
-    create_confirmed_subscriber(&app).await;
+    app.create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com")
+        .await;
+    app.log_in().await;
 
     let newsletter_request_body = serde_json::json!({
         "title": "Newsletter title",
@@ -107,40 +110,3 @@ async fn not_logged_in_cannot_send_newsletter() {
 
     assert_is_redirect_to(&response, "/login");
 }
-
-async fn create_unconfirmed_subscriber(app: &TestApp) -> ConfirmationLinks {
-    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
-
-    let _mock_guard = Mock::given(path("/email"))
-        .and(method("POST"))
-        .respond_with(ResponseTemplate::new(200))
-        .expect(1)
-        .mount_as_scoped(&app.email_server)
-        .await;
-
-    let _response = app
-        .post_login_form(&serde_json::json!({
-            "username": app.test_user.name,
-            "password": app.test_user.password,
-        }))
-        .await;
-
-    app.post_subscriptions(body.into())
-        .await
-        .error_for_status()
-        .unwrap();
-
-    let email_request = &app.email_server.received_requests().await.unwrap()[0];
-
-    app.get_confirmation_links(&email_request)
-}
-
-async fn create_confirmed_subscriber(app: &TestApp) {
-    let confirmation_link = create_unconfirmed_subscriber(app).await;
-
-    reqwest::get(confirmation_link.html)
-        .await
-        .unwrap()
-        .error_for_status()
-        .unwrap();
-}
@@ -0,0 +1,337 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, spawn_app_with, TestApp};
+
+/// Send an issue to every confirmed subscriber and return the emails they got.
+async fn send_issue(app: &TestApp) -> Vec<serde_json::Value> {
+    app.log_in().await;
+    let already_sent = app.email_server.received_requests().await.unwrap().len();
+    let response = app
+        .post_newsletters(serde_json::json!({
+            "title": "Newsletter title",
+            "html_content": "<html><body>Newsletter content</body></html>",
+            "content": "Newsletter content",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 303);
+    app.email_server.received_requests().await.unwrap()[already_sent..]
+        .iter()
+        .map(|request| serde_json::from_slice(&request.body).unwrap())
+        .collect()
+}
+
+/// The path of the preference center link in an email.
+fn preferences_path(email: &serde_json::Value) -> String {
+    let text = email["TextBody"].as_str().unwrap();
+    let (_, link) = text
+        .split_once("Manage your subscription: ")
+        .unwrap_or_else(|| panic!("No preference link in {text}"));
+    let link = reqwest::Url::parse(link.trim()).unwrap();
+    format!("{}?{}", link.path(), link.query().unwrap())
+}
+
+/// The token of the preference link `ursula@example.com` gets.
+async fn preferences_token(app: &TestApp) -> String {
+    app.insert_confirmed_subscriber("ursula@example.com").await;
+    app.mount_email_ok().await;
+    let emails = send_issue(app).await;
+    let path = preferences_path(&emails[0]);
+    path.split_once("token=").unwrap().1.to_string()
+}
+
+async fn get_preferences_html(app: &TestApp, token: &str) -> String {
+    app.client
+        .get(format!("{}/preferences?token={token}", app.address))
+        .send()
+        .await
+        .expect("Failed to execute request")
+        .text()
+        .await
+        .unwrap()
+}
+
+async fn post_preferences(app: &TestApp, path: &str, body: &[(&str, &str)]) -> reqwest::Response {
+    app.client
+        .post(format!("{}/preferences{path}", app.address))
+        .form(body)
+        .send()
+        .await
+        .expect("Failed to execute request")
+}
+
+#[tokio::test]
+async fn every_issue_links_to_the_preference_center() {
+    let app = spawn_app().await;
+    let token = preferences_token(&app).await;
+
+    let html = get_preferences_html(&app, &token).await;
+    assert!(html.contains("We write to ursula@example.com."));
+    assert!(html.contains(r#"value="le guin""#));
+    assert!(html.contains(r#"name="list.newsletter" checked"#));
+}
+
+#[tokio::test]
+async fn the_html_body_links_to_the_preference_center_too() {
+    let app = spawn_app().await;
+    app.insert_confirmed_subscriber("ursula@example.com").await;
+    app.mount_email_ok().await;
+
+    let emails = send_issue(&app).await;
+
+    let html = emails[0]["HtmlBody"].as_str().unwrap();
+    assert!(html.contains("/preferences?token="));
+    assert!(html.ends_with("</a></p></body></html>"));
+}
+
+#[tokio::test]
+async fn invalid_preference_links_are_rejected() {
+    let app = spawn_app().await;
+    let token = preferences_token(&app).await;
+
+    for token in ["garbage", &format!("{token}0"), &token[1..]] {
+        let response = app
+            .client
+            .get(format!("{}/preferences?token={token}", app.address))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 404);
+        let response = post_preferences(&app, "/name", &[("token", token), ("name", "x")]).await;
+        assert_eq!(response.status().as_u16(), 404);
+    }
+}
+
+#[tokio::test]
+async fn subscribers_can_change_their_name() {
+    let app = spawn_app().await;
+    let token = preferences_token(&app).await;
+
+    let response = post_preferences(
+        &app,
+        "/name",
+        &[("token", &token), ("name", "Ursula K. Le Guin")],
+    )
+    .await;
+    assert_is_redirect_to(&response, &format!("/preferences?token={token}"));
+    let html = get_preferences_html(&app, &token).await;
+    assert!(html.contains("Your name was updated."));
+    assert!(html.contains(r#"value="Ursula K. Le Guin""#));
+
+    post_preferences(&app, "/name", &[("token", &token), ("name", "")]).await;
+    let html = get_preferences_html(&app, &token).await;
+    assert!(!html.contains("Your name was updated."));
+    assert!(html.contains(r#"value="Ursula K. Le Guin""#));
+}
+
+#[tokio::test]
+async fn a_new_address_is_used_once_confirmed() {
+    let app = spawn_app().await;
+    let token = preferences_token(&app).await;
+    let already_sent = app.email_server.received_requests().await.unwrap().len();
+
+    let response = post_preferences(
+        &app,
+        "/email",
+        &[("token", &token), ("email", "ursula@new.example")],
+    )
+    .await;
+    assert_is_redirect_to(&response, &format!("/preferences?token={token}"));
+    let html = get_preferences_html(&app, &token).await;
+    assert!(html.contains("We sent a confirmation link to ursula@new.example."));
+    assert!(html.contains("We write to ursula@example.com."));
+    assert!(html.contains("Waiting for you to confirm ursula@new.example."));
+
+    let email_requests = app.email_server.received_requests().await.unwrap();
+    assert_eq!(email_requests.len(), already_sent + 1);
+    let email: serde_json::Value =
+        serde_json::from_slice(&email_requests[already_sent].body).unwrap();
+    assert_eq!(email["To"], "ursula@new.example");
+    let confirmation_link = app.get_confirmation_links(&email_requests[already_sent]);
+
+    let response = app
+        .client
+        .get(confirmation_link.html.clone())
+        .send()
+        .await
+        .unwrap();
+    assert_is_redirect_to(&response, &format!("/preferences?token={token}"));
+    let html = get_preferences_html(&app, &token).await;
+    assert!(html.contains("Your email address is now ursula@new.example."));
+    assert!(html.contains("We write to ursula@new.example."));
+
+    let response = app.client.get(confirmation_link.html).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn address_changes_are_rate_limited() {
+    let app = spawn_app_with(|c| {
+        c.subscribe_rate_limit.per_ip.capacity = 1;
+        c.subscribe_rate_limit.per_ip.refill_interval_seconds = 600;
+    })
+    .await;
+    let token = preferences_token(&app).await;
+    let already_sent = app.email_server.received_requests().await.unwrap().len();
+
+    for email in ["ursula@new.example", "ursula@other.example"] {
+        post_preferences(&app, "/email", &[("token", &token), ("email", email)]).await;
+    }
+
+    let html = get_preferences_html(&app, &token).await;
+    assert!(html.contains("Too many attempts. Please try again later."));
+    assert_eq!(
+        app.email_server.received_requests().await.unwrap().len(),
+        already_sent + 1
+    );
+}
+
+#[tokio::test]
+async fn the_new_address_is_written_to_in_the_language_of_the_subscriber() {
+    let app = spawn_app().await;
+    let token = preferences_token(&app).await;
+    sqlx::query!("UPDATE subscriptions SET locale = 'de'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    post_preferences(
+        &app,
+        "/email",
+        &[("token", &token), ("email", "ursula@new.example")],
+    )
+    .await;
+
+    let email_request = app.email_server.received_requests().await.unwrap();
+    let email: serde_json::Value =
+        serde_json::from_slice(&email_request.last().unwrap().body).unwrap();
+    assert_eq!(email["Subject"], "Bestätigen Sie Ihre neue E-Mail-Adresse");
+}
+
+#[tokio::test]
+async fn addresses_in_use_cannot_be_taken_over() {
+    let app = spawn_app().await;
+    let token = preferences_token(&app).await;
+    app.insert_confirmed_subscriber("octavia@example.com").await;
+    let already_sent = app.email_server.received_requests().await.unwrap().len();
+
+    post_preferences(
+        &app,
+        "/email",
+        &[("token", &token), ("email", "Octavia@example.com")],
+    )
+    .await;
+
+    // The form does not tell whether the address is subscribed.
+    let html = get_preferences_html(&app, &token).await;
+    assert!(html.contains("We sent a confirmation link to Octavia@example.com."));
+    let email_requests = app.email_server.received_requests().await.unwrap();
+    assert_eq!(email_requests.len(), already_sent + 1);
+
+    let confirmation_link = app.get_confirmation_links(&email_requests[already_sent]);
+    let response = app.client.get(confirmation_link.html).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 400);
+    let html = get_preferences_html(&app, &token).await;
+    assert!(html.contains("We write to ursula@example.com."));
+}
+
+#[tokio::test]
+async fn subscribers_choose_their_lists() {
+    let app = spawn_app().await;
+    let token = preferences_token(&app).await;
+    app.create_list("weekly", "Weekly digest").await;
+
+    let response =
+        post_preferences(&app, "/lists", &[("token", &token), ("list.weekly", "on")]).await;
+    assert_is_redirect_to(&response, &format!("/preferences?token={token}"));
+
+    let statuses = sqlx::query!(
+        r#"
+        SELECT l.slug, s.status
+        FROM subscriptions s JOIN lists l USING (list_id)
+        ORDER BY l.slug
+        "#
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    let statuses: Vec<_> = statuses
+        .iter()
+        .map(|row| (row.slug.as_str(), row.status.as_str()))
+        .collect();
+    assert_eq!(
+        statuses,
+        [("newsletter", "unsubscribed"), ("weekly", "confirmed")]
+    );
+    let html = get_preferences_html(&app, &token).await;
+    assert!(html.contains("Your lists were updated."));
+    assert!(html.contains(r#"name="list.weekly" checked"#));
+    assert!(html.contains(r#"name="list.newsletter">"#));
+}
+
+#[tokio::test]
+async fn choosing_lists_does_not_confirm_pending_subscriptions() {
+    let app = spawn_app().await;
+    let token = preferences_token(&app).await;
+    let weekly = app.create_list("weekly", "Weekly digest").await;
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status, list_id)
+        VALUES (gen_random_uuid(), 'ursula@example.com', 'Ursula', now(), 'pending_confirmation', $1)
+        "#,
+        weekly,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    post_preferences(
+        &app,
+        "/lists",
+        &[
+            ("token", &token),
+            ("list.newsletter", "on"),
+            ("list.weekly", "on"),
+        ],
+    )
+    .await;
+
+    let row = sqlx::query!(
+        "SELECT status, confirmed_at FROM subscriptions WHERE list_id = $1",
+        weekly
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(row.status, "pending_confirmation");
+    assert!(row.confirmed_at.is_none());
+}
+
+#[tokio::test]
+async fn paused_subscribers_get_no_issues_until_they_resume() {
+    let app = spawn_app().await;
+    let token = preferences_token(&app).await;
+
+    post_preferences(&app, "/pause", &[("token", &token), ("weeks", "2")]).await;
+    let html = get_preferences_html(&app, &token).await;
+    assert!(html.contains("Delivery is paused until"));
+    assert!(send_issue(&app).await.is_empty());
+
+    post_preferences(&app, "/pause", &[("token", &token), ("weeks", "0")]).await;
+    let html = get_preferences_html(&app, &token).await;
+    assert!(html.contains("Delivery resumed."));
+    assert_eq!(send_issue(&app).await.len(), 1);
+}
+
+#[tokio::test]
+async fn the_preference_center_is_in_the_language_of_the_subscriber() {
+    let app = spawn_app().await;
+    let token = preferences_token(&app).await;
+    sqlx::query!("UPDATE subscriptions SET locale = 'fr'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    let html = get_preferences_html(&app, &token).await;
+
+    assert!(html.contains(r#"<html lang="fr">"#));
+    assert!(html.contains("Nous vous écrivons à ursula@example.com."));
+    assert!(html.contains(r#"<option value="2">2 semaines</option>"#));
+}
@@ -0,0 +1,74 @@
+use sqlx::{Connection, Executor, PgConnection};
+use zero2prod::configuration::get_configuration;
+
+use crate::helpers::{spawn_app, TestApp};
+
+/// Drop the application's database from under it.
+async fn drop_database(app: &TestApp) {
+    let database_name: String = sqlx::query_scalar("SELECT current_database()")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    let configuration = get_configuration().unwrap();
+    PgConnection::connect_with(&configuration.database.without_db())
+        .await
+        .unwrap()
+        .execute(format!(r#"DROP DATABASE "{database_name}" WITH (FORCE);"#).as_str())
+        .await
+        .unwrap();
+}
+
+fn check<'a>(body: &'a serde_json::Value, name: &str) -> &'a serde_json::Value {
+    body["checks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|check| check["name"] == name)
+        .unwrap_or_else(|| panic!("No {name} check in {body}"))
+}
+
+#[tokio::test]
+async fn ready_reports_every_dependency() {
+    let app = spawn_app().await;
+
+    let response = app.get_ready().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["ready"], true);
+    for name in ["postgres", "redis", "email_provider"] {
+        let check = check(&body, name);
+        assert_eq!(check["status"], "up");
+        assert!(check["latency_ms"].is_u64());
+    }
+}
+
+#[tokio::test]
+async fn ready_returns_503_when_the_database_is_gone() {
+    let app = spawn_app().await;
+    drop_database(&app).await;
+
+    let response = app.get_ready().await;
+
+    assert_eq!(response.status().as_u16(), 503);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["ready"], false);
+    let postgres = check(&body, "postgres");
+    assert_eq!(postgres["status"], "down");
+    // The probe is public: the error stays in the logs.
+    assert!(postgres["error"].is_null(), "{postgres}");
+    assert!(postgres["latency_ms"].is_u64());
+    assert_eq!(check(&body, "redis")["status"], "up");
+}
+
+#[tokio::test]
+async fn the_health_check_stays_a_liveness_probe() {
+    let app = spawn_app().await;
+    drop_database(&app).await;
+
+    let response = reqwest::get(format!("{}/health_check", app.address))
+        .await
+        .unwrap();
+
+    assert!(response.status().is_success());
+}
@@ -0,0 +1,209 @@
+use uuid::Uuid;
+use wiremock::{matchers::any, Mock, ResponseTemplate};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+async fn post_admin_form(app: &TestApp, path: &str, body: serde_json::Value) -> reqwest::Response {
+    app.client
+        .post(format!("{}{path}", app.address))
+        .form(&app.with_csrf_token(&body).await)
+        .send()
+        .await
+        .expect("Failed to execute request")
+}
+
+async fn get_html(app: &TestApp, path: &str) -> String {
+    app.client
+        .get(format!("{}{path}", app.address))
+        .send()
+        .await
+        .expect("Failed to execute request")
+        .text()
+        .await
+        .unwrap()
+}
+
+async fn tag(app: &TestApp, email: &str, tag: &str) -> reqwest::Response {
+    post_admin_form(
+        app,
+        "/admin/subscribers/tags",
+        serde_json::json!({ "email": email, "tag": tag, "action": "add" }),
+    )
+    .await
+}
+
+async fn segment_id(app: &TestApp, name: &str) -> Uuid {
+    sqlx::query!("SELECT segment_id FROM segments WHERE name = $1", name)
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("No such segment")
+        .segment_id
+}
+
+#[tokio::test]
+async fn subscribers_can_be_tagged_and_untagged_from_the_admin_ui() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    app.insert_confirmed_subscriber("ursula@example.com").await;
+
+    let response = tag(&app, "Ursula@Example.com", " VIP ").await;
+    assert_is_redirect_to(&response, "/admin/subscribers");
+    let html = get_html(&app, "/admin/subscribers").await;
+    assert!(html.contains("Tagged Ursula@example.com with vip"));
+    let html = get_html(&app, "/admin/subscribers?tag=vip").await;
+    assert!(html.contains("ursula@example.com"));
+
+    post_admin_form(
+        &app,
+        "/admin/subscribers/tags",
+        serde_json::json!({ "email": "ursula@example.com", "tag": "vip", "action": "remove" }),
+    )
+    .await;
+    let html = get_html(&app, "/admin/subscribers?tag=vip").await;
+    assert!(html.contains("Removed vip from ursula@example.com"));
+    assert!(!html.contains("<td>ursula@example.com</td>"));
+}
+
+#[tokio::test]
+async fn invalid_tags_are_rejected() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    app.insert_confirmed_subscriber("ursula@example.com").await;
+
+    let response = tag(&app, "ursula@example.com", "two words").await;
+    assert_is_redirect_to(&response, "/admin/subscribers");
+    let html = get_html(&app, "/admin/subscribers").await;
+    assert!(html.contains("is not a valid label"));
+}
+
+#[tokio::test]
+async fn the_newsletter_form_previews_recipient_counts() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    app.insert_confirmed_subscriber("ursula@example.com").await;
+    app.insert_confirmed_subscriber("octavia@example.com").await;
+    tag(&app, "ursula@example.com", "vip").await;
+
+    let response = post_admin_form(
+        &app,
+        "/admin/segments",
+        serde_json::json!({ "name": "VIPs", "tag": "vip" }),
+    )
+    .await;
+    assert_is_redirect_to(&response, "/admin/segments");
+    let html = get_html(&app, "/admin/segments").await;
+    assert!(html.contains("Segment VIPs created"));
+    assert!(html.contains("tagged vip"));
+
+    let html = get_html(&app, "/admin/newsletter").await;
+    assert!(html.contains("<tr><td>All confirmed subscribers</td><td>2</td></tr>"));
+    assert!(html.contains("<tr><td>VIPs</td><td>1</td></tr>"));
+}
+
+#[tokio::test]
+async fn segments_with_invalid_conditions_are_rejected() {
+    let app = spawn_app().await;
+    app.log_in().await;
+
+    for (body, error) in [
+        (
+            serde_json::json!({ "name": " " }),
+            "A segment needs a name.",
+        ),
+        (
+            serde_json::json!({ "name": "Dates", "subscribed_from": "yesterday" }),
+            "is not a date",
+        ),
+        (
+            serde_json::json!({
+                "name": "Dates",
+                "subscribed_from": "2026-02-01",
+                "subscribed_until": "2026-01-01",
+            }),
+            "The sign up period ends before it starts.",
+        ),
+    ] {
+        let response = post_admin_form(&app, "/admin/segments", body).await;
+        assert_is_redirect_to(&response, "/admin/segments");
+        let html = get_html(&app, "/admin/segments").await;
+        assert!(html.contains(error), "Missing {error:?} in {html}");
+    }
+}
+
+#[tokio::test]
+async fn issues_sent_to_a_segment_only_reach_its_members() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    app.insert_confirmed_subscriber("ursula@example.com").await;
+    app.insert_confirmed_subscriber("octavia@example.com").await;
+    tag(&app, "ursula@example.com", "vip").await;
+    post_admin_form(
+        &app,
+        "/admin/segments",
+        serde_json::json!({ "name": "VIPs", "tag": "vip" }),
+    )
+    .await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let response = app
+        .post_newsletters(serde_json::json!({
+            "title": "Newsletter title",
+            "html_content": "Newsletter content",
+            "content": "Newsletter content",
+            "segment_id": segment_id(&app, "VIPs").await.to_string(),
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 303);
+
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let email: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(email["To"], "ursula@example.com");
+}
+
+#[tokio::test]
+async fn issues_cannot_be_sent_to_an_unknown_segment() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    app.insert_confirmed_subscriber("ursula@example.com").await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+    let response = app
+        .post_newsletters(serde_json::json!({
+            "title": "Newsletter title",
+            "html_content": "Newsletter content",
+            "content": "Newsletter content",
+            "segment_id": Uuid::new_v4().to_string(),
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletter");
+    let html = get_html(&app, "/admin/newsletter").await;
+    assert!(html.contains("The segment this issue was addressed to does not exist."));
+}
+
+#[tokio::test]
+async fn the_source_of_a_subscription_is_recorded() {
+    let app = spawn_app().await;
+    app.mount_email_ok().await;
+
+    let html = get_html(&app, "/?source=Podcast").await;
+    assert!(html.contains(r#"name="source" value="podcast""#));
+
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula%40example.com&source=podcast".into())
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let saved = sqlx::query!("SELECT source FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription");
+    assert_eq!(saved.source.as_deref(), Some("podcast"));
+}
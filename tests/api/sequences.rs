@@ -0,0 +1,415 @@
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+use wiremock::{matchers::any, Mock, ResponseTemplate};
+use zero2prod::preferences::pause_delivery;
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+async fn get_sequences_html(app: &TestApp) -> String {
+    app.client
+        .get(format!("{}/admin/sequences", app.address))
+        .send()
+        .await
+        .expect("Failed to execute request")
+        .text()
+        .await
+        .unwrap()
+}
+
+async fn post_admin(app: &TestApp, path: &str, body: serde_json::Value) -> reqwest::Response {
+    app.client
+        .post(format!("{}{path}", app.address))
+        .form(&app.with_csrf_token(&body).await)
+        .send()
+        .await
+        .expect("Failed to execute request")
+}
+
+/// Create a sequence on the default list from the admin UI.
+async fn create_sequence(app: &TestApp, name: &str) -> Uuid {
+    let list_id = app.list_id("newsletter").await;
+    let response = post_admin(
+        app,
+        "/admin/sequences",
+        serde_json::json!({ "name": name, "list_id": list_id.to_string() }),
+    )
+    .await;
+    assert_is_redirect_to(&response, "/admin/sequences");
+    sqlx::query!("SELECT sequence_id FROM sequences WHERE name = $1", name)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .sequence_id
+}
+
+async fn add_step(app: &TestApp, sequence_id: Uuid, delay_hours: i32, subject: &str) {
+    let response = post_admin(
+        app,
+        "/admin/sequences/steps",
+        serde_json::json!({
+            "sequence_id": sequence_id.to_string(),
+            "delay_hours": delay_hours,
+            "subject": subject,
+            "html_content": format!("<p>{subject}</p>"),
+            "text_content": subject,
+        }),
+    )
+    .await;
+    assert_is_redirect_to(&response, "/admin/sequences");
+}
+
+/// Subscribe `ursula@example.com` and follow the confirmation link.
+async fn subscribe_and_confirm(app: &TestApp) {
+    let already_sent = app.email_server.received_requests().await.unwrap().len();
+    app.post_subscriptions("name=le%20guin&email=ursula%40example.com".into())
+        .await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[already_sent];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    let response = reqwest::get(confirmation_links.html).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+/// The subjects of the emails sent since `already_sent` requests.
+async fn subjects_since(app: &TestApp, already_sent: usize) -> Vec<String> {
+    app.email_server.received_requests().await.unwrap()[already_sent..]
+        .iter()
+        .map(|request| {
+            let email: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+            email["Subject"].as_str().unwrap().to_string()
+        })
+        .collect()
+}
+
+/// Move enrollments and steps `hours` into the past.
+async fn travel_back(app: &TestApp, hours: i32) {
+    sqlx::query!(
+        "UPDATE sequence_enrollments SET enrolled_at = enrolled_at - make_interval(hours => $1)",
+        hours,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    sqlx::query!(
+        "UPDATE sequence_steps SET created_at = created_at - make_interval(hours => $1)",
+        hours,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn sequences_and_steps_are_created_from_the_admin_ui() {
+    let app = spawn_app().await;
+    app.log_in().await;
+
+    let sequence_id = create_sequence(&app, "Onboarding").await;
+    add_step(&app, sequence_id, 48, "Getting started").await;
+    let html = get_sequences_html(&app).await;
+    assert!(html.contains("Step Getting started added"));
+    assert!(html.contains("<h2>Onboarding (Newsletter)</h2>"));
+    assert!(html.contains("<tr><td>48 hours</td><td>Getting started</td><td>0</td><td>0</td></tr>"));
+
+    create_sequence(&app, "Onboarding").await;
+    let html = get_sequences_html(&app).await;
+    assert!(html.contains("There is a sequence named Onboarding already."));
+}
+
+#[tokio::test]
+async fn invalid_steps_are_rejected() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    let sequence_id = create_sequence(&app, "Onboarding").await;
+
+    for (sequence_id, delay_hours, subject, message) in [
+        (
+            sequence_id,
+            -1,
+            "Welcome",
+            "Steps go out between 0 and 8784 hours after confirmation.",
+        ),
+        (sequence_id, 0, " ", "A step needs a subject and content."),
+        (
+            Uuid::new_v4(),
+            0,
+            "Welcome",
+            "The sequence of this step does not exist.",
+        ),
+    ] {
+        add_step(&app, sequence_id, delay_hours, subject).await;
+        let html = get_sequences_html(&app).await;
+        assert!(html.contains(message), "{message} missing");
+    }
+    let steps = sqlx::query!("SELECT COUNT(*) AS \"count!\" FROM sequence_steps")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(steps.count, 0);
+}
+
+#[tokio::test]
+async fn steps_go_out_once_their_delay_has_passed() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    app.mount_email_ok().await;
+    let sequence_id = create_sequence(&app, "Onboarding").await;
+    add_step(&app, sequence_id, 0, "Welcome aboard").await;
+    add_step(&app, sequence_id, 24, "A day in").await;
+    subscribe_and_confirm(&app).await;
+    let already_sent = app.email_server.received_requests().await.unwrap().len();
+
+    let summary = app.sequence_scheduler.run_round().await.unwrap();
+    assert_eq!((summary.enqueued, summary.sent), (1, 1));
+    assert_eq!(subjects_since(&app, already_sent).await, ["Welcome aboard"]);
+
+    let summary = app.sequence_scheduler.run_round().await.unwrap();
+    assert_eq!((summary.enqueued, summary.sent), (0, 0));
+
+    travel_back(&app, 25).await;
+    app.sequence_scheduler.run_round().await.unwrap();
+    assert_eq!(
+        subjects_since(&app, already_sent).await,
+        ["Welcome aboard", "A day in"]
+    );
+    let html = get_sequences_html(&app).await;
+    assert!(html.contains("<tr><td>0 hours</td><td>Welcome aboard</td><td>1</td><td>0</td></tr>"));
+}
+
+#[tokio::test]
+async fn steps_carry_the_preference_center_link() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    app.mount_email_ok().await;
+    let sequence_id = create_sequence(&app, "Onboarding").await;
+    add_step(&app, sequence_id, 0, "Welcome aboard").await;
+    subscribe_and_confirm(&app).await;
+
+    app.sequence_scheduler.run_round().await.unwrap();
+
+    let email_request = app.email_server.received_requests().await.unwrap();
+    let email: serde_json::Value =
+        serde_json::from_slice(&email_request.last().unwrap().body).unwrap();
+    assert_eq!(email["To"], "ursula@example.com");
+    assert!(email["TextBody"]
+        .as_str()
+        .unwrap()
+        .contains("Manage your subscription: "));
+}
+
+#[tokio::test]
+async fn lists_joined_from_the_preference_center_enroll_in_their_sequences() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    app.mount_email_ok().await;
+    let sequence_id = create_sequence(&app, "Onboarding").await;
+    add_step(&app, sequence_id, 0, "Welcome aboard").await;
+    subscribe_and_confirm(&app).await;
+    let weekly = app.create_list("weekly", "Weekly digest").await;
+    post_admin(
+        &app,
+        "/admin/sequences",
+        serde_json::json!({ "name": "Weekly onboarding", "list_id": weekly.to_string() }),
+    )
+    .await;
+    let weekly_sequence_id = sqlx::query!(
+        "SELECT sequence_id FROM sequences WHERE list_id = $1",
+        weekly
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .sequence_id;
+    add_step(&app, weekly_sequence_id, 0, "Welcome to the digest").await;
+    app.sequence_scheduler.run_round().await.unwrap();
+    let email_request = app.email_server.received_requests().await.unwrap();
+    let email: serde_json::Value =
+        serde_json::from_slice(&email_request.last().unwrap().body).unwrap();
+    let (_, link) = email["TextBody"]
+        .as_str()
+        .unwrap()
+        .split_once("Manage your subscription: ")
+        .unwrap();
+    let link = reqwest::Url::parse(link.trim()).unwrap();
+    let (_, token) = link.query_pairs().find(|(key, _)| key == "token").unwrap();
+
+    let response = app
+        .client
+        .post(format!("{}/preferences/lists", app.address))
+        .form(&[
+            ("token", token.as_ref()),
+            ("list.newsletter", "on"),
+            ("list.weekly", "on"),
+        ])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 303);
+    let already_sent = app.email_server.received_requests().await.unwrap().len();
+    app.sequence_scheduler.run_round().await.unwrap();
+
+    assert_eq!(
+        subjects_since(&app, already_sent).await,
+        ["Welcome to the digest"]
+    );
+}
+
+#[tokio::test]
+async fn steps_added_after_they_were_due_are_skipped() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    app.mount_email_ok().await;
+    let sequence_id = create_sequence(&app, "Onboarding").await;
+    subscribe_and_confirm(&app).await;
+    travel_back(&app, 2).await;
+
+    add_step(&app, sequence_id, 1, "Too late").await;
+    add_step(&app, sequence_id, 3, "Still ahead").await;
+    travel_back(&app, 2).await;
+    let already_sent = app.email_server.received_requests().await.unwrap().len();
+    app.sequence_scheduler.run_round().await.unwrap();
+
+    assert_eq!(subjects_since(&app, already_sent).await, ["Still ahead"]);
+}
+
+#[tokio::test]
+async fn steps_for_subscribers_who_left_are_cancelled() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    app.mount_email_ok().await;
+    let sequence_id = create_sequence(&app, "Onboarding").await;
+    add_step(&app, sequence_id, 0, "Welcome aboard").await;
+    subscribe_and_confirm(&app).await;
+    // The step came due, then the subscriber left before it went out
+    sqlx::query!(
+        r#"
+        INSERT INTO sequence_deliveries (
+            step_id, subscriber_id, status, attempts, queued_at, next_attempt_at
+        )
+        SELECT st.step_id, e.subscriber_id, 'queued', 0, now(), now()
+        FROM sequence_enrollments e JOIN sequence_steps st USING (sequence_id)
+        "#
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    sqlx::query!("UPDATE subscriptions SET status = 'unsubscribed'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    let already_sent = app.email_server.received_requests().await.unwrap().len();
+
+    let summary = app.sequence_scheduler.run_round().await.unwrap();
+
+    assert_eq!((summary.sent, summary.cancelled), (0, 1));
+    assert!(subjects_since(&app, already_sent).await.is_empty());
+}
+
+#[tokio::test]
+async fn steps_wait_while_delivery_is_paused() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    app.mount_email_ok().await;
+    let sequence_id = create_sequence(&app, "Onboarding").await;
+    add_step(&app, sequence_id, 0, "Welcome aboard").await;
+    subscribe_and_confirm(&app).await;
+    sqlx::query!("UPDATE subscriptions SET paused_until = now() + interval '7 days'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    let already_sent = app.email_server.received_requests().await.unwrap().len();
+
+    let summary = app.sequence_scheduler.run_round().await.unwrap();
+    assert_eq!((summary.enqueued, summary.sent), (1, 0));
+    assert!(subjects_since(&app, already_sent).await.is_empty());
+
+    sqlx::query!("UPDATE subscriptions SET paused_until = NULL")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    sqlx::query!("UPDATE sequence_deliveries SET next_attempt_at = now()")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    app.sequence_scheduler.run_round().await.unwrap();
+    assert_eq!(subjects_since(&app, already_sent).await, ["Welcome aboard"]);
+}
+
+#[tokio::test]
+async fn steps_follow_a_pause_that_is_shortened_or_cleared() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    app.mount_email_ok().await;
+    let sequence_id = create_sequence(&app, "Onboarding").await;
+    add_step(&app, sequence_id, 0, "Welcome aboard").await;
+    subscribe_and_confirm(&app).await;
+    let email = "ursula@example.com";
+    pause_delivery(&app.db_pool, email, Some(Utc::now() + Duration::days(7)))
+        .await
+        .unwrap();
+    let already_sent = app.email_server.received_requests().await.unwrap().len();
+    let summary = app.sequence_scheduler.run_round().await.unwrap();
+    assert_eq!((summary.enqueued, summary.sent), (1, 0));
+
+    let resume_at = Utc::now() + Duration::days(1);
+    pause_delivery(&app.db_pool, email, Some(resume_at))
+        .await
+        .unwrap();
+    let delivery = sqlx::query!("SELECT next_attempt_at FROM sequence_deliveries")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert!((delivery.next_attempt_at - resume_at).num_seconds().abs() < 1);
+
+    pause_delivery(&app.db_pool, email, None).await.unwrap();
+    app.sequence_scheduler.run_round().await.unwrap();
+    assert_eq!(subjects_since(&app, already_sent).await, ["Welcome aboard"]);
+}
+
+#[tokio::test]
+async fn the_scheduler_stops_between_steps_on_shutdown() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    app.mount_email_ok().await;
+    let sequence_id = create_sequence(&app, "Onboarding").await;
+    add_step(&app, sequence_id, 0, "Welcome aboard").await;
+    subscribe_and_confirm(&app).await;
+    let already_sent = app.email_server.received_requests().await.unwrap().len();
+
+    app.sequence_scheduler
+        .clone()
+        .run_until(std::time::Duration::from_secs(60), std::future::ready(()))
+        .await;
+
+    assert!(subjects_since(&app, already_sent).await.is_empty());
+    let delivery = sqlx::query!("SELECT status FROM sequence_deliveries")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(delivery.status, "queued");
+}
+
+#[tokio::test]
+async fn failed_steps_are_retried_later() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    let sequence_id = create_sequence(&app, "Onboarding").await;
+    add_step(&app, sequence_id, 0, "Welcome aboard").await;
+    app.mount_email_ok().await;
+    subscribe_and_confirm(&app).await;
+    app.email_server.reset().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&app.email_server)
+        .await;
+
+    let summary = app.sequence_scheduler.run_round().await.unwrap();
+    assert_eq!((summary.sent, summary.failed), (0, 1));
+    let summary = app.sequence_scheduler.run_round().await.unwrap();
+    assert_eq!(summary.failed, 0);
+
+    let delivery = sqlx::query!("SELECT status, attempts FROM sequence_deliveries")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!((delivery.status.as_str(), delivery.attempts), ("queued", 1));
+}
@@ -0,0 +1,103 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+
+fn revocable_session_id(html_page: &str) -> String {
+    let marker = r#"name="session_id" value=""#;
+    let start = html_page.find(marker).expect("No revocable session") + marker.len();
+    html_page[start..start + 36].to_string()
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_your_sessions() {
+    let app = spawn_app().await;
+
+    let response = app
+        .client
+        .get(format!("{}/admin/sessions", app.address))
+        .send()
+        .await
+        .unwrap();
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn every_logged_in_device_is_listed() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    app.log_in_from_another_device("Second device").await;
+
+    let html_page = app.get_sessions_html().await;
+    assert!(html_page.contains("Second device"));
+    assert!(html_page.contains("This session"));
+    assert_eq!(html_page.matches("<li>").count(), 2);
+}
+
+#[tokio::test]
+async fn a_revoked_session_is_logged_out() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    let other_device = app.log_in_from_another_device("Second device").await;
+
+    let session_id = revocable_session_id(&app.get_sessions_html().await);
+    let response = app
+        .post_revoke_session(&serde_json::json!({ "session_id": session_id }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/sessions");
+
+    let html_page = app.get_sessions_html().await;
+    assert!(html_page.contains("The session has been revoked"));
+    assert!(!html_page.contains("Second device"));
+
+    let response = app.get_admin_dashboard_with(&other_device).await;
+    assert_is_redirect_to(&response, "/login");
+    // The current session is unaffected.
+    assert_eq!(app.get_admin_dashboard().await.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn sessions_of_other_users_cannot_be_revoked() {
+    let app = spawn_app().await;
+    app.log_in().await;
+
+    let response = app
+        .post_revoke_session(&serde_json::json!({ "session_id": uuid::Uuid::new_v4() }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/sessions");
+    assert!(app.get_sessions_html().await.contains("Unknown session"));
+}
+
+#[tokio::test]
+async fn logging_out_everywhere_revokes_every_session() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    let other_device = app.log_in_from_another_device("Second device").await;
+
+    let response = app.post_revoke_all_sessions().await;
+    assert_is_redirect_to(&response, "/login");
+
+    let response = app.get_admin_dashboard_with(&other_device).await;
+    assert_is_redirect_to(&response, "/login");
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn changing_password_revokes_all_other_sessions() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    let other_device = app.log_in_from_another_device("Second device").await;
+
+    let new_password = "correct horse battery staple";
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "password": &app.test_user.password,
+            "new_password": new_password,
+            "confirm_password": new_password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    let response = app.get_admin_dashboard_with(&other_device).await;
+    assert_is_redirect_to(&response, "/login");
+    assert_eq!(app.get_admin_dashboard().await.status().as_u16(), 200);
+}
@@ -0,0 +1,52 @@
+use std::time::Duration;
+
+use tokio::sync::oneshot;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::build_app;
+
+#[tokio::test]
+async fn an_in_flight_newsletter_send_finishes_before_shutdown() {
+    let (app, application) = build_app().await;
+    let (trigger_shutdown, shutdown) = oneshot::channel::<()>();
+    let server = tokio::spawn(application.run_until(async {
+        let _ = shutdown.await;
+    }));
+
+    app.create_confirmed_subscriber("le guin", "ursula_le_guin@gmail.com")
+        .await;
+    app.log_in().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(1)))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Shut down while the newsletter is being delivered.
+    let (response, _) = tokio::join!(
+        app.post_newsletters(serde_json::json!({
+            "title": "Newsletter title",
+            "content": "Newsletter content",
+            "html_content": "<p>Newsletter content</p>",
+        })),
+        async {
+            // Wait for the delivery to reach the email provider.
+            while app.email_server.received_requests().await.unwrap().len() < 2 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            trigger_shutdown.send(()).unwrap();
+        }
+    );
+
+    assert_eq!(response.status().as_u16(), 303);
+    tokio::time::timeout(Duration::from_secs(10), server)
+        .await
+        .expect("The server did not shut down in time")
+        .unwrap()
+        .unwrap();
+    assert!(reqwest::get(format!("{}/health_check", app.address))
+        .await
+        .is_err());
+}
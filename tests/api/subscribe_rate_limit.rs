@@ -0,0 +1,137 @@
+use uuid::Uuid;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{random_ip, spawn_app_with, TestApp};
+
+fn subscription_for(email: &str) -> String {
+    format!("name=le%20guin&email={}", urlencoding::encode(email))
+}
+
+fn random_email() -> String {
+    format!("{}@example.com", Uuid::new_v4())
+}
+
+async fn accept_emails(app: &TestApp, expected: u64) {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(expected)
+        .mount(&app.email_server)
+        .await;
+}
+
+fn retry_after(response: &reqwest::Response) -> u64 {
+    response.headers()["retry-after"]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn an_ip_is_rate_limited_after_using_up_its_bucket() {
+    let app = spawn_app_with(|c| {
+        c.subscribe_rate_limit.per_ip.capacity = 2;
+        c.subscribe_rate_limit.per_ip.refill_interval_seconds = 600;
+    })
+    .await;
+    accept_emails(&app, 3).await;
+    let ip = random_ip();
+
+    for _ in 0..2 {
+        let response = app
+            .post_subscriptions_from(&ip, subscription_for(&random_email()))
+            .await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+
+    let response = app
+        .post_subscriptions_from(&ip, subscription_for(&random_email()))
+        .await;
+    assert_eq!(response.status().as_u16(), 429);
+    let retry_after = retry_after(&response);
+    assert!(retry_after > 0 && retry_after <= 600, "{retry_after}");
+
+    // Other clients are not affected
+    let response = app
+        .post_subscriptions_from(&random_ip(), subscription_for(&random_email()))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn an_email_address_is_rate_limited_whatever_the_ip() {
+    let app = spawn_app_with(|c| {
+        c.subscribe_rate_limit.per_email.capacity = 1;
+        c.subscribe_rate_limit.per_email.refill_interval_seconds = 3600;
+    })
+    .await;
+    accept_emails(&app, 1).await;
+    let email = random_email();
+
+    let response = app
+        .post_subscriptions_from(&random_ip(), subscription_for(&email))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = app
+        .post_subscriptions_from(&random_ip(), subscription_for(&email.to_uppercase()))
+        .await;
+    assert_eq!(response.status().as_u16(), 429);
+    assert!(retry_after(&response) > 0);
+}
+
+#[tokio::test]
+async fn a_refused_request_does_not_use_up_the_other_buckets() {
+    let app = spawn_app_with(|c| {
+        c.subscribe_rate_limit.per_ip.capacity = 2;
+        c.subscribe_rate_limit.per_ip.refill_interval_seconds = 600;
+        c.subscribe_rate_limit.per_email.capacity = 1;
+        c.subscribe_rate_limit.per_email.refill_interval_seconds = 3600;
+    })
+    .await;
+    accept_emails(&app, 2).await;
+    let ip = random_ip();
+    let email = random_email();
+
+    app.post_subscriptions_from(&ip, subscription_for(&email))
+        .await;
+    let response = app
+        .post_subscriptions_from(&ip, subscription_for(&email))
+        .await;
+    assert_eq!(response.status().as_u16(), 429);
+
+    // The IP still has a token left
+    let response = app
+        .post_subscriptions_from(&ip, subscription_for(&random_email()))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn confirmation_emails_stop_once_the_daily_cap_is_reached() {
+    let app = spawn_app_with(|c| c.subscribe_rate_limit.daily_confirmation_emails = 1).await;
+    accept_emails(&app, 1).await;
+
+    let response = app
+        .post_subscriptions_from(&random_ip(), subscription_for(&random_email()))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let email = random_email();
+    let response = app
+        .post_subscriptions_from(&random_ip(), subscription_for(&email))
+        .await;
+    assert_eq!(response.status().as_u16(), 503);
+    assert!(retry_after(&response) <= 24 * 60 * 60);
+
+    // The subscription was not kept either
+    let saved = sqlx::query!("SELECT id FROM subscriptions WHERE email = $1", email)
+        .fetch_optional(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(saved.is_none());
+}
@@ -0,0 +1,108 @@
+use uuid::Uuid;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{spawn_app, spawn_app_with};
+
+#[tokio::test]
+async fn subscriber_emails_are_stored_in_canonical_form() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let body = "name=le%20guin&email=%20Ursula_Le_Guin%40B%C3%BCcher.Example%20";
+    let response = app.post_subscriptions(body.into()).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.email, "Ursula_Le_Guin@xn--bcher-kva.example");
+}
+
+#[tokio::test]
+async fn emails_differing_only_in_case_are_the_same_subscriber() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula%40example.com".into())
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let response = app
+        .post_subscriptions("name=le%20guin&email=URSULA%40Example.com".into())
+        .await;
+    assert!(!response.status().is_success());
+
+    let subscribers = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscribers.len(), 1);
+    assert_eq!(subscribers[0].email, "ursula@example.com");
+}
+
+#[tokio::test]
+async fn disposable_email_domains_are_rejected_with_a_clear_message() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    for (email, domain) in [
+        ("ursula%40mailinator.com", "mailinator.com"),
+        ("ursula%40eu.MAILINATOR.com", "eu.mailinator.com"),
+    ] {
+        let response = app
+            .post_subscriptions(format!("name=le%20guin&email={email}"))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 400);
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(
+            body["message"],
+            format!("Email addresses at {domain} are not accepted. Please use another address.")
+        );
+    }
+}
+
+#[tokio::test]
+async fn an_allowlist_only_accepts_the_listed_domains() {
+    let allowlist = std::env::temp_dir().join(format!("allowlist-{}.txt", Uuid::new_v4()));
+    std::fs::write(&allowlist, "# Staff only\nexample.com\n").unwrap();
+    let app = spawn_app_with(|c| {
+        c.email_domains.allowlist_path = Some(allowlist.to_string_lossy().into_owned());
+    })
+    .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let refused = app
+        .post_subscriptions("name=le%20guin&email=ursula%40example.org".into())
+        .await;
+    let accepted = app
+        .post_subscriptions("name=le%20guin&email=ursula%40example.com".into())
+        .await;
+
+    assert_eq!(refused.status().as_u16(), 400);
+    assert_eq!(accepted.status().as_u16(), 200);
+    std::fs::remove_file(allowlist).unwrap();
+}
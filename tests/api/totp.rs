@@ -0,0 +1,216 @@
+use totp_rs::{Algorithm, Secret, TOTP};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+fn current_code(secret: &str) -> String {
+    let bytes = Secret::Encoded(secret.to_string()).to_bytes().unwrap();
+    TOTP::new(Algorithm::SHA1, 6, 1, 30, bytes, None, "".into())
+        .unwrap()
+        .generate_current()
+        .unwrap()
+}
+
+fn extract_between<'a>(html: &'a str, start: &str, end: &str) -> &'a str {
+    let from = html.find(start).expect("start marker not found") + start.len();
+    let to = from + html[from..].find(end).expect("end marker not found");
+    &html[from..to]
+}
+
+/// Log in, enroll in two-factor authentication and log out again.
+/// Returns the TOTP secret and the recovery codes.
+async fn enroll(app: &TestApp) -> (String, Vec<String>) {
+    app.post_login_form(&serde_json::json!({
+        "username": app.test_user.name,
+        "password": app.test_user.password
+    }))
+    .await;
+
+    let html_page = app.get_totp_enrollment_html().await;
+    let secret = extract_between(&html_page, r#"<code id="totp_secret">"#, "</code>").to_string();
+
+    let response = app
+        .post_totp_enrollment(&serde_json::json!({ "code": current_code(&secret) }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    let recovery_codes = extract_between(&html_page, r#"<ul id="recovery_codes">"#, "</ul>")
+        .split("</li>")
+        .filter(|c| !c.is_empty())
+        .map(|c| c.replace("<li><code>", "").replace("</code>", ""))
+        .collect();
+
+    app.post_logout().await;
+    (secret, recovery_codes)
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_enroll_in_two_factor_authentication() {
+    let app = spawn_app().await;
+
+    let response = app.get_totp_enrollment().await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn an_invalid_code_does_not_enable_two_factor_authentication() {
+    let app = spawn_app().await;
+    app.post_login_form(&serde_json::json!({
+        "username": app.test_user.name,
+        "password": app.test_user.password
+    }))
+    .await;
+    app.get_totp_enrollment_html().await;
+
+    let response = app
+        .post_totp_enrollment(&serde_json::json!({ "code": "000000x" }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/totp");
+
+    let html_page = app.get_totp_enrollment_html().await;
+    assert!(html_page.contains("Invalid authentication code"));
+    assert!(html_page.contains("Set up two-factor authentication"));
+}
+
+#[tokio::test]
+async fn password_alone_does_not_grant_access_once_enrolled() {
+    let app = spawn_app().await;
+    let (secret, _) = enroll(&app).await;
+
+    let response = app
+        .post_login_form(&serde_json::json!({
+            "username": app.test_user.name,
+            "password": app.test_user.password
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login/totp");
+
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login/totp");
+
+    let response = app
+        .post_login_totp(&serde_json::json!({ "code": current_code(&secret) }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(html_page.contains(&format!("Welcome, {}!", app.test_user.name)));
+}
+
+#[tokio::test]
+async fn an_invalid_second_factor_is_rejected() {
+    let app = spawn_app().await;
+    enroll(&app).await;
+
+    app.post_login_form(&serde_json::json!({
+        "username": app.test_user.name,
+        "password": app.test_user.password
+    }))
+    .await;
+    let response = app
+        .post_login_totp(&serde_json::json!({ "code": "not-a-code" }))
+        .await;
+    assert_is_redirect_to(&response, "/login/totp");
+
+    let html_page = app.get_login_totp_html().await;
+    assert!(html_page.contains("Invalid authentication code"));
+
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login/totp");
+}
+
+#[tokio::test]
+async fn a_recovery_code_can_only_be_used_once() {
+    let app = spawn_app().await;
+    let (_, recovery_codes) = enroll(&app).await;
+    assert_eq!(recovery_codes.len(), 10);
+    let recovery_code = &recovery_codes[0];
+
+    for expected_location in ["/admin/dashboard", "/login/totp"] {
+        app.post_login_form(&serde_json::json!({
+            "username": app.test_user.name,
+            "password": app.test_user.password
+        }))
+        .await;
+        let response = app
+            .post_login_totp(&serde_json::json!({ "code": recovery_code }))
+            .await;
+        assert_is_redirect_to(&response, expected_location);
+        app.post_logout().await;
+    }
+}
+
+#[tokio::test]
+async fn a_totp_code_can_only_be_used_once() {
+    let app = spawn_app().await;
+    let (secret, _) = enroll(&app).await;
+    let code = current_code(&secret);
+
+    for expected_location in ["/admin/dashboard", "/login/totp"] {
+        app.post_login_form(&serde_json::json!({
+            "username": app.test_user.name,
+            "password": app.test_user.password
+        }))
+        .await;
+        let response = app
+            .post_login_totp(&serde_json::json!({ "code": code }))
+            .await;
+        assert_is_redirect_to(&response, expected_location);
+        app.post_logout().await;
+    }
+}
+
+#[tokio::test]
+async fn the_second_factor_is_locked_out_after_too_many_failed_attempts() {
+    let app = spawn_app().await;
+    let (secret, _) = enroll(&app).await;
+
+    app.post_login_form(&serde_json::json!({
+        "username": app.test_user.name,
+        "password": app.test_user.password
+    }))
+    .await;
+    for _ in 0..5 {
+        let response = app
+            .post_login_totp(&serde_json::json!({ "code": "000000" }))
+            .await;
+        assert_is_redirect_to(&response, "/login/totp");
+    }
+
+    // Even the right code is refused, and the password has to be entered again.
+    let response = app
+        .post_login_totp(&serde_json::json!({ "code": current_code(&secret) }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn the_password_alone_does_not_reset_failed_attempts() {
+    let app = spawn_app().await;
+    let (secret, _) = enroll(&app).await;
+
+    for _ in 0..4 {
+        app.post_login_form(&serde_json::json!({
+            "username": app.test_user.name,
+            "password": "wrong-password"
+        }))
+        .await;
+    }
+    let response = app
+        .post_login_form(&serde_json::json!({
+            "username": app.test_user.name,
+            "password": app.test_user.password
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login/totp");
+    app.post_login_totp(&serde_json::json!({ "code": "000000" }))
+        .await;
+
+    // That was the fifth failure in a row.
+    let response = app
+        .post_login_totp(&serde_json::json!({ "code": current_code(&secret) }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+}
@@ -0,0 +1,89 @@
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::spawn_app;
+
+const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+const PARENT_SPAN_ID: &str = "00f067aa0ba902b7";
+
+fn traceparent_of(request: &wiremock::Request) -> Vec<String> {
+    let traceparent = request
+        .headers
+        .get(&"traceparent".into())
+        .expect("No traceparent header on the email request");
+    traceparent
+        .as_str()
+        .split('-')
+        .map(ToString::to_string)
+        .collect()
+}
+
+#[tokio::test]
+async fn the_callers_trace_is_continued_to_the_email_provider() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .client
+        .post(format!("{}/subscriptions", app.address))
+        .header("traceparent", format!("00-{TRACE_ID}-{PARENT_SPAN_ID}-01"))
+        .form(&[
+            ("name", "le guin"),
+            ("email", "ursula_le_guin@gmail.com"),
+            (
+                "form_token",
+                app.get_subscription_form_token().await.as_str(),
+            ),
+        ])
+        .send()
+        .await
+        .expect("Failed to execute request");
+    assert_eq!(response.status().as_u16(), 200);
+
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let parts = traceparent_of(email_request);
+    assert_eq!(parts.len(), 4);
+    assert_eq!(parts[0], "00");
+    assert_eq!(parts[1], TRACE_ID);
+    assert_ne!(parts[2], PARENT_SPAN_ID);
+    assert_eq!(parts[3], "01");
+}
+
+#[tokio::test]
+async fn a_new_trace_is_started_without_a_valid_traceparent() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    app.client
+        .post(format!("{}/subscriptions", app.address))
+        .header("traceparent", "not-a-traceparent")
+        .form(&[
+            ("name", "le guin"),
+            ("email", "ursula_le_guin@gmail.com"),
+            (
+                "form_token",
+                app.get_subscription_form_token().await.as_str(),
+            ),
+        ])
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let parts = traceparent_of(email_request);
+    assert_eq!(parts.len(), 4);
+    assert_ne!(parts[1], TRACE_ID);
+    assert_ne!(parts[1], "0".repeat(32));
+}
@@ -0,0 +1,130 @@
+use wiremock::{matchers::any, Mock, ResponseTemplate};
+
+use crate::helpers::{spawn_app, spawn_app_with, TestApp};
+
+const HTML_CONTENT: &str =
+    r#"<html><body><a href="https://example.com/story?a=1&amp;b=2">Read on</a></body></html>"#;
+
+/// Send an issue to a single confirmed subscriber and return the HTML body
+/// they got.
+async fn send_issue(app: &TestApp, tracking: &[(&str, &str)]) -> String {
+    app.insert_confirmed_subscriber("ursula@example.com").await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.log_in().await;
+    let mut body = serde_json::json!({
+        "title": "Newsletter title",
+        "html_content": HTML_CONTENT,
+        "content": "Newsletter content",
+    });
+    for (field, value) in tracking {
+        body[field] = (*value).into();
+    }
+    let response = app.post_newsletters(body).await;
+    assert_eq!(response.status().as_u16(), 303);
+
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let email: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    email["HtmlBody"].as_str().unwrap().to_string()
+}
+
+/// The first tracking link of `kind`, as a path on the application.
+fn tracking_path(html: &str, kind: &str) -> String {
+    let (_, rest) = html
+        .split_once(&format!("/{kind}/"))
+        .unwrap_or_else(|| panic!("No /{kind}/ link in {html}"));
+    format!("/{kind}/{}", rest.split('"').next().unwrap())
+}
+
+/// The issue as written, give or take the preference center link at the end.
+fn assert_untracked(html: &str) {
+    let (body, _) = HTML_CONTENT.split_once("</body>").unwrap();
+    assert!(html.starts_with(body), "{html}");
+    assert!(!html.contains("/o/") && !html.contains("/r/"), "{html}");
+}
+
+#[tokio::test]
+async fn opens_and_clicks_are_recorded_and_reported() {
+    let app = spawn_app().await;
+    let html = send_issue(&app, &[("track_opens", "on"), ("track_clicks", "on")]).await;
+    assert!(!html.contains("https://example.com/story"));
+
+    let pixel = app
+        .client
+        .get(format!("{}{}", app.address, tracking_path(&html, "o")))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(pixel.status().as_u16(), 200);
+    assert_eq!(pixel.headers()["Content-Type"], "image/gif");
+
+    let click = app
+        .client
+        .get(format!("{}{}", app.address, tracking_path(&html, "r")))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(click.status().as_u16(), 302);
+    assert_eq!(
+        click.headers()["Location"],
+        "https://example.com/story?a=1&b=2"
+    );
+
+    let dashboard = app.get_admin_dashboard_html().await;
+    assert!(dashboard.contains("<td>1 (100.0%)</td><td>1 (100.0%)</td>"));
+}
+
+#[tokio::test]
+async fn issues_are_not_tracked_unless_asked() {
+    let app = spawn_app().await;
+
+    let html = send_issue(&app, &[]).await;
+
+    assert_untracked(&html);
+    let dashboard = app.get_admin_dashboard_html().await;
+    assert!(dashboard.contains("<td>not tracked</td><td>not tracked</td>"));
+}
+
+#[tokio::test]
+async fn tracking_can_be_disabled_globally() {
+    let app = spawn_app_with(|c| c.tracking.enabled = false).await;
+
+    let html = send_issue(&app, &[("track_opens", "on"), ("track_clicks", "on")]).await;
+
+    assert_untracked(&html);
+    let form = app
+        .client
+        .get(format!("{}/admin/newsletter", app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(!form.contains("track_opens"));
+}
+
+#[tokio::test]
+async fn tampered_tracking_links_are_rejected() {
+    let app = spawn_app().await;
+    let html = send_issue(&app, &[("track_clicks", "on")]).await;
+    let path = tracking_path(&html, "r");
+    let tampered = path.replacen("/r/", "/r/x", 1);
+
+    let response = app
+        .client
+        .get(format!("{}{}", app.address, tampered))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 404);
+    let clicks = sqlx::query!(r#"SELECT COUNT(*) AS "count!" FROM issue_clicks"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(clicks.count, 0);
+}